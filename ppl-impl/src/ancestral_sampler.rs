@@ -1,9 +1,4 @@
-use crate::{ast::Expression, types::Value};
-
-enum FactorType {
-    Sample,
-    Observe,
-}
+use crate::types::Value;
 
 // impl From<serde_json::Value> for Expression {
 //     fn from(v: serde_json::Value) -> Self {
@@ -62,11 +57,3 @@ impl From<serde_json::Value> for Value {
         }
     }
 }
-
-struct Pgm {
-    variables: Vec<(usize, String)>,
-    arcs: Vec<(usize, Vec<usize>)>,
-    factors: Vec<(FactorType, Expression)>,
-    observations: Vec<(usize, Value)>,
-    query: usize,
-}
@@ -1,9 +1,35 @@
 #[derive(Debug)]
 pub struct Program {
     pub definitions: Vec<Definition>,
+    pub distributions: Vec<DistributionDefinition>,
     pub expression: Expression,
 }
 
+impl Program {
+    pub fn new(items: Vec<TopLevel>, expression: Expression) -> Self {
+        let mut definitions = Vec::new();
+        let mut distributions = Vec::new();
+        for item in items {
+            match item {
+                TopLevel::Function(d) => definitions.push(d),
+                TopLevel::Distribution(d) => distributions.push(d),
+            }
+        }
+        Program {
+            definitions,
+            distributions,
+            expression,
+        }
+    }
+}
+
+/// A declaration before the program's expression.
+#[derive(Debug)]
+pub enum TopLevel {
+    Function(Definition),
+    Distribution(DistributionDefinition),
+}
+
 #[derive(Debug)]
 pub struct Ident(pub String);
 
@@ -14,6 +40,17 @@ pub struct Definition {
     pub body: Expression,
 }
 
+/// `(defdist name [params] (sampler expr) (log-density [value] expr))`.
+#[derive(Debug)]
+pub struct DistributionDefinition {
+    pub ident: Ident,
+    pub params: Vec<Ident>,
+    pub sampler: Expression,
+    /// The name the log density gives to the value it is evaluated at.
+    pub value: Ident,
+    pub log_density: Expression,
+}
+
 #[derive(Debug)]
 pub struct Let {
     pub bindings: Vec<(Ident, Expression)>,
@@ -35,19 +72,38 @@ pub struct Loop {
     pub params: Vec<Expression>,
 }
 
+/// `(infer alg n_samples fn_name params)` runs inference on a function application, with its own inference
+/// algorithm, giving the results as a distribution.
+#[derive(Debug)]
+pub struct Infer {
+    pub alg: Ident,
+    pub n_samples: usize,
+    pub fn_name: Ident,
+    pub params: Vec<Expression>,
+}
+
 #[derive(Debug)]
 pub enum Expression {
     Variable(Ident),
     Let(Let),
     Sample(Box<Expression>, Option<usize>),
     Observe(Box<Expression>, Box<Expression>, Option<usize>),
+    /// `(factor log-weight)` adds an arbitrary log weight to the current execution.
+    Factor(Box<Expression>),
+    /// `(condition expr)` rules out executions where `expr` is false.
+    Condition(Box<Expression>),
     If(Box<Expression>, Box<Expression>, Box<Expression>),
     FunctionApplication(Ident, Vec<Expression>),
+    /// `(mem f args)` applies `f`, but only once per run for each distinct list of arguments.
+    Mem(Ident, Vec<Expression>),
     Boolean(bool),
     Integer(i64),
     Float(f64),
     Vector(Vec<Expression>),
     ForEach(ForEach),
     Loop(Loop),
+    Infer(Infer),
+    /// `(load-empirical "path")`, the results in a data file as an empirical distribution.
+    LoadEmpirical(String),
     Null,
 }
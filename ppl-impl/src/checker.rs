@@ -0,0 +1,582 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    ast::{self, Definition, DistributionDefinition, Expression, ForEach, Ident, Let, Program},
+    functions::{builtin_signature, Param, Returns},
+    inference::NESTED_ALGS,
+    interpreter::Binding,
+    shapes::{builtin_result_shape, Shape},
+    types::{Value, ValueType},
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Type {
+    // `None` means the type can't be known until runtime, e.g. the result of `sample` or `get`.
+    pub value_type: Option<ValueType>,
+    // Only known for vectors whose dimensions can be worked out statically. `Value::Matrix` is treated as a
+    // vector of rows here, since that's how the language sees it.
+    pub shape: Option<Shape>,
+}
+
+impl Type {
+    const UNKNOWN: Type = Type {
+        value_type: None,
+        shape: None,
+    };
+
+    fn of(value_type: ValueType) -> Self {
+        Type {
+            value_type: Some(value_type),
+            shape: None,
+        }
+    }
+
+    fn vector(shape: Option<Shape>) -> Self {
+        Type {
+            value_type: Some(ValueType::Vector),
+            shape,
+        }
+    }
+
+    /// The type of a value known before the program runs, such as one bound by `--data`.
+    fn of_value(val: &Value) -> Self {
+        match val {
+            Value::Vector(elements) => {
+                let element_shapes = elements
+                    .iter()
+                    .map(|v| Type::of_value(v).shape)
+                    .collect::<Vec<_>>();
+                Type::vector(Some(Shape::of_elements(&element_shapes)))
+            }
+            Value::Matrix(m) => Type::vector(Some(Shape::Matrix(m.n_rows(), m.n_cols()))),
+            val => Type::of(val.get_type()),
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.value_type, self.shape) {
+            (Some(t), Some(shape)) => write!(f, "{} {}", t, shape),
+            (Some(t), None) => write!(f, "{}", t),
+            (None, _) => write!(f, "unknown"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct CheckError {
+    // Which `defn` (or the main expression) the error was found in.
+    context: String,
+    message: String,
+}
+
+impl fmt::Display for CheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "In {}: {}", self.context, self.message)
+    }
+}
+
+/// The inferred type of one `let` binding, for printing with `check --shapes`.
+pub struct BindingType {
+    pub context: String,
+    pub name: String,
+    pub t: Type,
+}
+
+pub struct CheckReport {
+    pub result_type: Type,
+    pub bindings: Vec<BindingType>,
+    pub errors: Vec<CheckError>,
+}
+
+struct FunctionInfo {
+    n_params: usize,
+    returns: Type,
+}
+
+/// What kind of body is being checked, since the parts of a `defdist` can't use every form.
+#[derive(Clone, Copy, PartialEq)]
+enum Body {
+    Function,
+    Sampler,
+    LogDensity,
+}
+
+struct Checker<'p> {
+    functions: HashMap<&'p str, FunctionInfo>,
+    scope: Vec<(&'p str, Type)>,
+    context: String,
+    body: Body,
+    bindings: Vec<BindingType>,
+    errors: Vec<CheckError>,
+}
+
+/// Statically resolves every variable and function application in the program, and infers enough
+/// about types and matrix shapes to catch obviously wrong arguments. All errors are collected, rather than
+/// stopping at the first. The `data` bound by `--data` is in scope everywhere.
+pub fn check_program<'p>(program: &'p Program, data: &'p [Binding]) -> CheckReport {
+    let data_scope: Vec<(&'p str, Type)> = data
+        .iter()
+        .map(|Binding { ident, val }| (ident.as_str(), Type::of_value(val)))
+        .collect();
+
+    let mut checker = Checker {
+        functions: HashMap::new(),
+        scope: Vec::new(),
+        context: String::new(),
+        body: Body::Function,
+        bindings: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    // Functions can be called before they are defined, so register them all before checking any bodies.
+    for Definition {
+        ident: Ident(name),
+        params,
+        body: _,
+    } in program.definitions.iter()
+    {
+        checker.context = format!("`defn {}`", name);
+        checker.define(name, params.len(), Type::UNKNOWN);
+    }
+    for DistributionDefinition {
+        ident: Ident(name),
+        params,
+        ..
+    } in program.distributions.iter()
+    {
+        checker.context = format!("`defdist {}`", name);
+        checker.define(name, params.len(), Type::of(ValueType::Distribution));
+    }
+
+    for Definition {
+        ident: Ident(name),
+        params,
+        body,
+    } in program.definitions.iter()
+    {
+        checker.context = format!("`defn {}`", name);
+        checker.scope = data_scope.clone();
+        checker
+            .scope
+            .extend(params.iter().map(|Ident(p)| (p.as_str(), Type::UNKNOWN)));
+        let returns = checker.check(body);
+        if let Some(function) = checker.functions.get_mut(name.as_str()) {
+            function.returns = returns;
+        }
+    }
+
+    for DistributionDefinition {
+        ident: Ident(name),
+        params,
+        sampler,
+        value: Ident(value),
+        log_density,
+    } in program.distributions.iter()
+    {
+        checker.context = format!("`defdist {}`", name);
+        checker.scope = data_scope.clone();
+        checker
+            .scope
+            .extend(params.iter().map(|Ident(p)| (p.as_str(), Type::UNKNOWN)));
+        checker.body = Body::Sampler;
+        checker.check(sampler);
+
+        checker.scope.push((value, Type::UNKNOWN));
+        checker.body = Body::LogDensity;
+        let t = checker.check(log_density);
+        if let Some(found) = t.value_type {
+            if found != ValueType::Float && found != ValueType::Integer {
+                checker.error(format!("The log density must be a number, but found {}.", found));
+            }
+        }
+    }
+
+    checker.context = "the program body".to_owned();
+    checker.body = Body::Function;
+    checker.scope = data_scope;
+    let result_type = checker.check(&program.expression);
+
+    CheckReport {
+        result_type,
+        bindings: checker.bindings,
+        errors: checker.errors,
+    }
+}
+
+impl<'p> Checker<'p> {
+    fn error(&mut self, message: String) {
+        self.errors.push(CheckError {
+            context: self.context.clone(),
+            message,
+        });
+    }
+
+    fn define(&mut self, name: &'p str, n_params: usize, returns: Type) {
+        if builtin_signature(name).is_some() {
+            self.error(format!(
+                "`{}` is a built-in function and can't be redefined.",
+                name
+            ));
+        }
+        if self.functions.contains_key(name) {
+            self.error(format!("`{}` is defined more than once.", name));
+        }
+
+        self.functions.insert(name, FunctionInfo { n_params, returns });
+    }
+
+    /// `observe`, `factor` and `condition` weight the program's execution, which a distribution's definition can't.
+    fn forbid_weighting(&mut self, form: &str) {
+        if self.body != Body::Function {
+            self.error(format!("`{}` can't be used in the definition of a distribution.", form));
+        }
+    }
+
+    fn lookup_var(&self, name: &str) -> Option<Type> {
+        self.scope
+            .iter()
+            .rev()
+            .find(|(ident, _)| *ident == name)
+            .map(|(_, t)| *t)
+    }
+
+    fn expect(&mut self, found: Type, expected: ValueType, what: &str) {
+        if let Some(found) = found.value_type {
+            if found != expected {
+                self.error(format!(
+                    "{} must be a {}, but found {}.",
+                    what, expected, found
+                ));
+            }
+        }
+    }
+
+    fn check_all(&mut self, exprs: &'p [Expression]) -> Vec<Type> {
+        exprs.iter().map(|e| self.check(e)).collect()
+    }
+
+    fn check(&mut self, expr: &'p Expression) -> Type {
+        match expr {
+            Expression::Variable(Ident(name)) => match self.lookup_var(name) {
+                Some(t) => t,
+                None => {
+                    self.error(format!("Variable `{}` is not defined.", name));
+                    Type::UNKNOWN
+                }
+            },
+            Expression::Let(Let { bindings, body }) => {
+                if bindings.is_empty() {
+                    self.error("`let` must have at least one binding.".to_owned());
+                }
+                if body.is_empty() {
+                    self.error("`let` must have a body.".to_owned());
+                }
+
+                let old_scope_count = self.scope.len();
+                for (Ident(name), expr) in bindings {
+                    let t = self.check(expr);
+                    self.bindings.push(BindingType {
+                        context: self.context.clone(),
+                        name: name.clone(),
+                        t,
+                    });
+                    self.scope.push((name, t));
+                }
+                let body_types = self.check_all(body);
+                self.scope.truncate(old_scope_count);
+
+                body_types.last().copied().unwrap_or(Type::UNKNOWN)
+            }
+            Expression::Sample(dist, _) => {
+                if self.body == Body::LogDensity {
+                    self.error("`sample` can't be used in a log density.".to_owned());
+                }
+                let t = self.check(dist);
+                self.expect(t, ValueType::Distribution, "The argument to `sample`");
+                Type::UNKNOWN
+            }
+            Expression::Observe(dist, val, _) => {
+                self.forbid_weighting("observe");
+                let t = self.check(dist);
+                self.expect(
+                    t,
+                    ValueType::Distribution,
+                    "The first argument to `observe`",
+                );
+                self.check(val)
+            }
+            Expression::Factor(log_weight) => {
+                self.forbid_weighting("factor");
+                let t = self.check(log_weight);
+                if let Some(found) = t.value_type {
+                    if found != ValueType::Float && found != ValueType::Integer {
+                        self.error(format!("The argument to `factor` must be a number, but found {}.", found));
+                    }
+                }
+                Type::of(ValueType::Float)
+            }
+            Expression::Condition(cond) => {
+                self.forbid_weighting("condition");
+                let t = self.check(cond);
+                self.expect(t, ValueType::Boolean, "The argument to `condition`");
+                Type::of(ValueType::Boolean)
+            }
+            Expression::If(cond, true_branch, false_branch) => {
+                let t = self.check(cond);
+                self.expect(t, ValueType::Boolean, "The condition of `if`");
+                let true_t = self.check(true_branch);
+                let false_t = self.check(false_branch);
+                if true_t == false_t {
+                    true_t
+                } else if true_t.value_type == false_t.value_type {
+                    Type {
+                        value_type: true_t.value_type,
+                        shape: None,
+                    }
+                } else {
+                    Type::UNKNOWN
+                }
+            }
+            Expression::FunctionApplication(Ident(name), args) | Expression::Mem(Ident(name), args) => {
+                let arg_types = self.check_all(args);
+                let int_literals = args
+                    .iter()
+                    .map(|e| match e {
+                        Expression::Integer(i) => Some(*i),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                self.check_application(name, &arg_types, &int_literals)
+            }
+            Expression::ForEach(ForEach {
+                n_iters,
+                bindings,
+                body,
+            }) => {
+                if body.is_empty() {
+                    self.error("`foreach` must have a body.".to_owned());
+                }
+
+                let old_scope_count = self.scope.len();
+                for (Ident(name), expr) in bindings {
+                    let t = self.check(expr);
+                    self.expect(
+                        t,
+                        ValueType::Vector,
+                        &format!("`foreach` binding `{}`", name),
+                    );
+                    let element = match t.shape {
+                        Some(shape) => {
+                            if shape.length() != *n_iters {
+                                self.error(format!(
+                                    "`foreach` binding `{}` has shape {}, but the loop runs {} times.",
+                                    name, shape, n_iters
+                                ));
+                            }
+                            shape
+                                .element()
+                                .map_or(Type::UNKNOWN, |s| Type::vector(Some(s)))
+                        }
+                        None => Type::UNKNOWN,
+                    };
+                    self.scope.push((name, element));
+                }
+                let body_types = self.check_all(body);
+                self.scope.truncate(old_scope_count);
+
+                let body_shape = body_types.last().and_then(|t| t.shape);
+                Type::vector(Some(Shape::repeated(*n_iters, body_shape)))
+            }
+            Expression::Loop(ast::Loop {
+                n_iters: _,
+                accumulator,
+                fn_name: Ident(name),
+                params,
+            }) => {
+                // `loop` calls the function with the loop index and accumulator, followed by the extra params.
+                let mut arg_types = vec![Type::of(ValueType::Integer), self.check(accumulator)];
+                arg_types.extend(self.check_all(params));
+                self.check_application(name, &arg_types, &[])
+            }
+            Expression::Infer(ast::Infer {
+                alg: Ident(alg),
+                n_samples,
+                fn_name: Ident(name),
+                params,
+            }) => {
+                if !NESTED_ALGS.contains(&alg.as_str()) {
+                    self.error(format!(
+                        "`{}` is not an inference algorithm `infer` can run. Use one of {}.",
+                        alg,
+                        NESTED_ALGS.join(", ")
+                    ));
+                }
+                if *n_samples == 0 {
+                    self.error("`infer` must run at least one sample.".to_owned());
+                }
+                let arg_types = self.check_all(params);
+                self.check_application(name, &arg_types, &[]);
+                Type::of(ValueType::Distribution)
+            }
+            Expression::Boolean(_) => Type::of(ValueType::Boolean),
+            Expression::Integer(_) => Type::of(ValueType::Integer),
+            Expression::Float(_) => Type::of(ValueType::Float),
+            Expression::Vector(elements) => {
+                let element_types = self.check_all(elements);
+                let element_shapes = element_types.iter().map(|t| t.shape).collect::<Vec<_>>();
+                Type::vector(Some(Shape::of_elements(&element_shapes)))
+            }
+            Expression::LoadEmpirical(path) => {
+                if !std::path::Path::new(path).is_file() {
+                    self.error(format!("`load-empirical` can't find the file `{}`.", path));
+                }
+                Type::of(ValueType::Distribution)
+            }
+            Expression::Null => Type::of(ValueType::Null),
+        }
+    }
+
+    fn check_application(
+        &mut self,
+        name: &str,
+        arg_types: &[Type],
+        int_literals: &[Option<i64>],
+    ) -> Type {
+        if let Some(signature) = builtin_signature(name) {
+            let n_args = arg_types.len();
+            let too_few = n_args < signature.min_args;
+            let too_many = signature.max_args.is_some_and(|max| n_args > max);
+            if too_few || too_many {
+                let expected = match signature.max_args {
+                    Some(max) if max == signature.min_args => format!("{}", max),
+                    Some(max) => format!("{} to {}", signature.min_args, max),
+                    None => format!("at least {}", signature.min_args),
+                };
+                self.error(format!(
+                    "`{}` expects {} arguments, but was given {}.",
+                    name, expected, n_args
+                ));
+                return Type::UNKNOWN;
+            }
+
+            for (i, t) in arg_types.iter().enumerate() {
+                let param = signature.params[i.min(signature.params.len() - 1)];
+                self.check_param(name, i, param, *t);
+            }
+            if let [a, b] = arg_types {
+                if COMPARISONS.contains(&name) {
+                    self.check_comparison(name, *a, *b);
+                }
+            }
+
+            let value_type = match signature.returns {
+                Returns::Type(t) => Some(t),
+                Returns::Unknown => None,
+                Returns::NumericPromotion => promote_numeric(arg_types),
+            };
+
+            let arg_shapes = arg_types.iter().map(|t| t.shape).collect::<Vec<_>>();
+            let shape = match builtin_result_shape(name, &arg_shapes, int_literals) {
+                Ok(shape) => shape,
+                Err(message) => {
+                    self.error(message);
+                    None
+                }
+            };
+
+            // Indexing into a matrix gives a vector, even though `get` can return anything in general.
+            let value_type = match shape {
+                Some(_) => Some(ValueType::Vector),
+                None => value_type,
+            };
+
+            return Type { value_type, shape };
+        }
+
+        if let Some(function) = self.functions.get(name) {
+            let (n_params, returns) = (function.n_params, function.returns);
+            if arg_types.len() != n_params {
+                self.error(format!(
+                    "`{}` expects {} arguments, but was given {}.",
+                    name,
+                    n_params,
+                    arg_types.len()
+                ));
+            }
+            return returns;
+        }
+
+        self.error(format!("Function `{}` is not defined.", name));
+        Type::UNKNOWN
+    }
+
+    /// Only the pairs of values the comparison built-ins accept at runtime: numbers with numbers, booleans with
+    /// booleans, and matrices with matrices for `=` and `<>`. Vectors and matrices can't be told apart here.
+    fn check_comparison(&mut self, name: &str, a: Type, b: Type) {
+        let (a, b) = match (a.value_type, b.value_type) {
+            (Some(a), Some(b)) => (a, b),
+            _ => return,
+        };
+        let numeric = |t| t == ValueType::Integer || t == ValueType::Float;
+        let vector = |t| t == ValueType::Vector || t == ValueType::Matrix;
+        let ok = (numeric(a) && numeric(b))
+            || (a == ValueType::Boolean && b == ValueType::Boolean)
+            || ((name == "=" || name == "<>") && vector(a) && vector(b));
+        if !ok {
+            self.error(format!("`{}` can't compare {} with {}.", name, a, b));
+        }
+    }
+
+    fn check_param(&mut self, name: &str, i: usize, param: Param, found: Type) {
+        let found = match found.value_type {
+            Some(t) => t,
+            None => return,
+        };
+
+        let (ok, expected) = match param {
+            Param::Any => (true, ""),
+            Param::Numeric => (
+                found == ValueType::Integer || found == ValueType::Float,
+                "a numeric value",
+            ),
+            Param::Elementwise => (
+                found == ValueType::Integer
+                    || found == ValueType::Float
+                    || found == ValueType::Vector,
+                "a numeric value or a Vector",
+            ),
+            Param::Integer => (found == ValueType::Integer, "an Integer"),
+            Param::Boolean => (found == ValueType::Boolean, "a Boolean"),
+            Param::Vector => (found == ValueType::Vector, "a Vector"),
+            Param::Distribution => (found == ValueType::Distribution, "a Distribution"),
+        };
+
+        if !ok {
+            self.error(format!(
+                "Argument {} to `{}` must be {}, but found {}.",
+                i + 1,
+                name,
+                expected,
+                found
+            ));
+        }
+    }
+}
+
+const COMPARISONS: &[&str] = &["<", "<=", "<>", "=", ">=", ">"];
+
+fn promote_numeric(arg_types: &[Type]) -> Option<ValueType> {
+    let mut all_t = ValueType::Integer;
+    for t in arg_types {
+        match t.value_type {
+            Some(ValueType::Integer) => {}
+            Some(ValueType::Float) if all_t != ValueType::Vector => all_t = ValueType::Float,
+            Some(ValueType::Float) => {}
+            Some(ValueType::Vector) => all_t = ValueType::Vector,
+            _ => return None,
+        }
+    }
+    Some(all_t)
+}
@@ -0,0 +1,203 @@
+//! Saving the state of a single-site Metropolis chain to a file, and loading it again to carry on the chain.
+
+use std::{fmt, path::Path, rc::Rc};
+
+use rand_chacha::ChaCha8Rng;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::{
+    distributions::Empirical,
+    inference::{single_site_metropolis::SavedChain, Address},
+    matrix::Matrix,
+    types::{RuntimeError, Value, ValueImpls},
+};
+
+/// A float which is saved exactly, including infinities and NaN, which JSON numbers can't represent. It's written
+/// as the shortest string which parses back to the same float.
+#[derive(Clone, Copy, Debug)]
+pub struct SavedFloat(pub f64);
+
+impl Serialize for SavedFloat {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format!("{:?}", self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for SavedFloat {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl<'de> de::Visitor<'de> for Visitor {
+            type Value = SavedFloat;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a float written as a string")
+            }
+
+            fn visit_str<E: de::Error>(self, s: &str) -> Result<SavedFloat, E> {
+                s.parse().map(SavedFloat).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_str(Visitor)
+    }
+}
+
+pub fn save_floats(xs: &[f64]) -> Vec<SavedFloat> {
+    xs.iter().copied().map(SavedFloat).collect()
+}
+
+pub fn restore_floats(xs: Vec<SavedFloat>) -> Vec<f64> {
+    xs.into_iter().map(|SavedFloat(x)| x).collect()
+}
+
+/// A value, with distributions saved as their name and parameters, to be built again by calling the distribution's
+/// function with the parameters.
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum SavedValue {
+    Float(SavedFloat),
+    Integer(i64),
+    Boolean(bool),
+    Vector(Vec<SavedValue>),
+    Matrix {
+        rows: usize,
+        cols: usize,
+        data: Vec<SavedFloat>,
+    },
+    Distribution {
+        name: String,
+        params: Vec<SavedValue>,
+    },
+    Null,
+}
+
+/// Calls a built-in function, `defn` or `defdist` by name, as the program would.
+pub type Rebuild<'r> = dyn FnMut(&str, Vec<Value>) -> Result<Value, RuntimeError> + 'r;
+
+impl SavedValue {
+    pub fn save(val: &Value) -> Self {
+        match val {
+            Value::Float(x) => SavedValue::Float(SavedFloat(*x)),
+            Value::Integer(x) => SavedValue::Integer(*x),
+            Value::Boolean(x) => SavedValue::Boolean(*x),
+            Value::Vector(xs) => SavedValue::Vector(xs.iter().map(SavedValue::save).collect()),
+            Value::Matrix(m) => SavedValue::Matrix {
+                rows: m.n_rows(),
+                cols: m.n_cols(),
+                data: (0..m.n_rows())
+                    .flat_map(|i| save_floats(m.row(i)))
+                    .collect(),
+            },
+            Value::Distribution(d) => SavedValue::Distribution {
+                name: d.name().to_owned(),
+                params: d
+                    .params()
+                    .iter()
+                    .map(|(_, param)| SavedValue::save(param))
+                    .collect(),
+            },
+            Value::Null => SavedValue::Null,
+        }
+    }
+
+    pub fn restore(self, rebuild: &mut Rebuild) -> Result<Value, RuntimeError> {
+        Ok(match self {
+            SavedValue::Float(SavedFloat(x)) => Value::Float(x),
+            SavedValue::Integer(x) => Value::Integer(x),
+            SavedValue::Boolean(x) => Value::Boolean(x),
+            SavedValue::Vector(xs) => Value::Vector(
+                xs.into_iter()
+                    .map(|x| x.restore(rebuild))
+                    .collect::<Result<_, _>>()?,
+            ),
+            SavedValue::Matrix { rows, cols, data } => {
+                if rows * cols != data.len() {
+                    return err!("A saved matrix has the wrong number of elements.");
+                }
+                Value::Matrix(Rc::new(Matrix::new(rows, cols, restore_floats(data))))
+            }
+            SavedValue::Distribution { name, params } => {
+                let params = params
+                    .into_iter()
+                    .map(|x| x.restore(rebuild))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match name.as_str() {
+                    // Made by `infer` and `load-empirical` rather than by a function.
+                    "empirical" => {
+                        let (values, weights) =
+                            params.try_into_two("A saved `empirical` needs values and weights.")?;
+                        let values = values.try_into_vector("A saved `empirical` needs values.")?;
+                        let log_weights = weights
+                            .try_into_vector("A saved `empirical` needs weights.")?
+                            .try_into_numeric("A saved `empirical` needs numeric weights.")?
+                            .into_iter()
+                            .map(f64::ln)
+                            .collect();
+                        Value::Distribution(Rc::new(Empirical::new(values, log_weights)?))
+                    }
+                    "Bernoulli" => rebuild("bernoulli", params)?,
+                    name => rebuild(name, params)?,
+                }
+            }
+            SavedValue::Null => Value::Null,
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SavedAddress {
+    mem_call: Option<String>,
+    site: usize,
+    count: usize,
+}
+
+impl SavedAddress {
+    pub fn save(address: &Address) -> Self {
+        SavedAddress {
+            mem_call: address.mem_call.as_deref().map(str::to_owned),
+            site: address.site,
+            count: address.count,
+        }
+    }
+
+    pub fn restore(self) -> Address {
+        Address {
+            mem_call: self.mem_call.map(Rc::from),
+            site: self.site,
+            count: self.count,
+        }
+    }
+}
+
+/// Everything needed to carry on a run of `infer` with single-site Metropolis.
+#[derive(Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// The program's source, so that a chain isn't resumed with a different program.
+    pub source: String,
+    pub n_samples: usize,
+    /// How many runs of the program have been made so far.
+    pub completed: usize,
+    pub rng: ChaCha8Rng,
+    pub chain: SavedChain,
+}
+
+impl Checkpoint {
+    /// Writes to a temporary file first, so that stopping part way through writing doesn't lose the last
+    /// checkpoint.
+    pub fn save(&self, path: &Path) -> Result<(), RuntimeError> {
+        let json =
+            serde_json::to_string(self).or_else(|e| err!("Couldn't save the checkpoint: {}", e))?;
+        let temporary = path.with_extension("tmp");
+        std::fs::write(&temporary, json)
+            .and_then(|_| std::fs::rename(&temporary, path))
+            .or_else(|e| err!("Couldn't write the checkpoint `{}`: {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, RuntimeError> {
+        let json = std::fs::read_to_string(path)
+            .or_else(|e| err!("Couldn't read the checkpoint `{}`: {}", path.display(), e))?;
+        serde_json::from_str(&json)
+            .or_else(|e| err!("`{}` isn't a checkpoint: {}", path.display(), e))
+    }
+}
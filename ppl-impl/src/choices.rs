@@ -0,0 +1,214 @@
+//! Choice maps: values for some of a program's `sample`s, read from a file by `--choices`, which make those
+//! `sample`s observations instead, so that one program can be conditioned on different things without editing it.
+
+use std::{cell::Cell, collections::HashMap, path::Path};
+
+use crate::{
+    ast::{Expression, Let, Program},
+    data,
+    inference::Address,
+    interpreter,
+    types::{RuntimeError, Value},
+};
+
+/// The `sample`s a choice map fixes, and their values.
+#[derive(Default)]
+pub struct ChoiceMap {
+    choices: Vec<Choice>,
+}
+
+/// A value for every choice made at `site`, or only the one with the given count, in any memoised call or only
+/// the given one. The count is the one in the choice's address: how many random choices the run, or the memoised
+/// call, had made before it.
+struct Choice {
+    key: String,
+    mem_call: Option<String>,
+    site: usize,
+    count: Option<usize>,
+    value: Value,
+    matched: Cell<bool>,
+}
+
+impl Choice {
+    fn matches(&self, address: &Address) -> bool {
+        self.site == address.site
+            && self.count.is_none_or(|count| count == address.count)
+            && self
+                .mem_call
+                .as_deref()
+                .is_none_or(|mem_call| address.mem_call.as_deref() == Some(mem_call))
+    }
+
+    /// Choices which fix fewer `sample`s take precedence.
+    fn specificity(&self) -> usize {
+        self.count.is_some() as usize + self.mem_call.is_some() as usize
+    }
+}
+
+/// What the program does at each site, and the sites of the `sample`s whose values `let` binds to each name.
+#[derive(Default)]
+struct Sites {
+    samples: HashMap<usize, bool>,
+    names: HashMap<String, Vec<usize>>,
+}
+
+impl Sites {
+    fn of(program: &mut Program) -> Self {
+        let mut sites = Sites::default();
+        interpreter::traverse_program(program, &mut |expr| match expr {
+            Expression::Sample(_, Some(site)) => {
+                sites.samples.insert(*site, true);
+            }
+            Expression::Observe(_, _, Some(site)) => {
+                sites.samples.insert(*site, false);
+            }
+            Expression::Let(Let { bindings, .. }) => {
+                for (name, e) in bindings.iter() {
+                    let names = sites.names.entry(name.0.clone()).or_default();
+                    returned_samples(e, names);
+                }
+            }
+            _ => {}
+        });
+        sites.names.retain(|_, sites| !sites.is_empty());
+        sites
+    }
+}
+
+/// The sites of the `sample`s whose value `expr` can be, as with both branches of `(if c (sample a) (sample b))`.
+fn returned_samples(expr: &Expression, sites: &mut Vec<usize>) {
+    match expr {
+        Expression::Sample(_, Some(site)) => sites.push(*site),
+        Expression::If(_, then, otherwise) => {
+            returned_samples(then, sites);
+            returned_samples(otherwise, sites);
+        }
+        Expression::Let(Let { body, .. }) => {
+            if let Some(last) = body.last() {
+                returned_samples(last, sites);
+            }
+        }
+        _ => {}
+    }
+}
+
+impl ChoiceMap {
+    /// Reads a JSON object whose keys are names bound by `let` to the value of a `sample`, or addresses as written in
+    /// traces: `3` for every choice at site 3, `3/5` for the one there with count 5, i.e. after 5 other random
+    /// choices in the run, and `effect [1]/3` or `effect [1]/3/0` inside the memoised call `(mem effect 1)`. Numbers the program's random choices as running it will, so that names
+    /// can be turned into sites.
+    pub fn read(path: &Path, program: &mut Program) -> Result<Self, RuntimeError> {
+        let entries = match data::read_json(path)? {
+            serde_json::Value::Object(entries) => entries,
+            _ => {
+                return err!(
+                    "`{}` isn't a choice map: it must be an object of names or addresses and their values.",
+                    path.display()
+                )
+            }
+        };
+        interpreter::assign_variable_numbers(program);
+        let sites = Sites::of(program);
+
+        let mut choices = Vec::new();
+        for (key, json) in entries {
+            let value = data::value_from_json(json, path)?;
+            if let Some(named) = sites.names.get(&key) {
+                for &site in named {
+                    choices.push(Choice {
+                        key: key.clone(),
+                        mem_call: None,
+                        site,
+                        count: None,
+                        value: value.clone(),
+                        matched: Cell::new(false),
+                    });
+                }
+                continue;
+            }
+
+            let (mem_call, site, count) = match parse_address(&key) {
+                Some(address) => address,
+                None => {
+                    return err!(
+                        "`{}` in `{}` isn't a name bound by `let` to a `sample`, or an address like `3`, `3/0` \
+                         or `effect [1]/3/0`.",
+                        key,
+                        path.display()
+                    )
+                }
+            };
+            match sites.samples.get(&site) {
+                Some(true) => {}
+                Some(false) => {
+                    return err!(
+                        "`{}` in `{}` is an `observe`, which is already observed.",
+                        key,
+                        path.display()
+                    )
+                }
+                None => {
+                    return err!(
+                        "`{}` in `{}` isn't an address in the program: it has no site {}.",
+                        key,
+                        path.display(),
+                        site
+                    )
+                }
+            }
+            choices.push(Choice {
+                key,
+                mem_call,
+                site,
+                count,
+                value,
+                matched: Cell::new(false),
+            });
+        }
+        choices.sort_by_key(|choice| std::cmp::Reverse(choice.specificity()));
+
+        Ok(ChoiceMap { choices })
+    }
+
+    /// The value the choice at `address` is fixed to, if any.
+    pub fn get(&self, address: &Address) -> Option<&Value> {
+        let choice = self.choices.iter().find(|choice| choice.matches(address))?;
+        choice.matched.set(true);
+        Some(&choice.value)
+    }
+
+    /// The keys that haven't fixed any choice yet. A name counts as used once any of its `sample`s has been.
+    pub fn unmatched(&self) -> Vec<&str> {
+        let mut unmatched: Vec<&str> = Vec::new();
+        for choice in &self.choices {
+            let used = self
+                .choices
+                .iter()
+                .any(|other| other.key == choice.key && other.matched.get());
+            if !used && !unmatched.contains(&choice.key.as_str()) {
+                unmatched.push(&choice.key);
+            }
+        }
+        unmatched
+    }
+}
+
+/// Splits `[mem call/]site[/count]`. A memoised call's key never ends in a number, so two parts are a site and
+/// count if both are numbers, and otherwise a call and a site.
+fn parse_address(key: &str) -> Option<(Option<String>, usize, Option<usize>)> {
+    let parts: Vec<&str> = key.split('/').map(str::trim).collect();
+    let number = |part: &str| part.parse::<usize>().ok();
+    match parts.as_slice() {
+        [site] => Some((None, number(site)?, None)),
+        [site, count] if number(site).is_some() => {
+            Some((None, number(site)?, Some(number(count)?)))
+        }
+        [mem_call, site] => Some((Some(mem_call.to_string()), number(site)?, None)),
+        [mem_call @ .., site, count] => Some((
+            Some(mem_call.join("/")),
+            number(site)?,
+            Some(number(count)?),
+        )),
+        [] => None,
+    }
+}
@@ -0,0 +1,326 @@
+//! Reading values into programs from files.
+
+use std::{
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use crate::{
+    interpreter::Binding,
+    types::{RuntimeError, Value},
+    DataFile, DistributionResult, ProgramResult, ResultValue,
+};
+
+pub fn read_json(path: &Path) -> Result<serde_json::Value, RuntimeError> {
+    let text = std::fs::read_to_string(path)
+        .or_else(|e| err!("Couldn't read `{}`: {}", path.display(), e))?;
+    serde_json::from_str(&text).or_else(|e| err!("`{}` is not valid JSON: {}", path.display(), e))
+}
+
+/// Converts JSON to a value, refusing the strings and objects that the language has no values for.
+pub fn value_from_json(json: serde_json::Value, path: &Path) -> Result<Value, RuntimeError> {
+    match json {
+        serde_json::Value::String(_) | serde_json::Value::Object(_) => err!(
+            "`{}` contains {}, which can't be read as a value.",
+            path.display(),
+            json
+        ),
+        serde_json::Value::Array(items) => Ok(Value::Vector(
+            items
+                .into_iter()
+                .map(|item| value_from_json(item, path))
+                .collect::<Result<_, _>>()?,
+        )),
+        json => Ok(Value::from(json)),
+    }
+}
+
+/// Reads the results written by `infer` or `prior-only`, as values and their log weights. Unweighted results all
+/// have a log weight of 0, and a `null` weight is minus infinity.
+pub fn read_results(path: &Path) -> Result<(Vec<Value>, Vec<f64>), RuntimeError> {
+    let (_, results) = read_result_json(path)?;
+    let mut values = Vec::with_capacity(results.len());
+    let mut log_weights = Vec::with_capacity(results.len());
+    for (json, log_weight) in results {
+        values.push(value_from_json(json, path)?);
+        log_weights.push(log_weight.unwrap_or(0.0));
+    }
+    Ok((values, log_weights))
+}
+
+/// Reads the results written by `infer` or `prior-only` back into the data file they were written from, including
+/// any distributions.
+pub fn read_data_file(path: &Path) -> Result<DataFile, RuntimeError> {
+    let (has_weights, results) = read_result_json(path)?;
+    let data = results
+        .into_iter()
+        .map(|(json, log_weight)| {
+            let result = result_from_json(json, path)?;
+            Ok(match log_weight {
+                Some(w) => {
+                    ProgramResult::Many(vec![result, ProgramResult::One(ResultValue::Float(w))])
+                }
+                None => result,
+            })
+        })
+        .collect::<Result<_, RuntimeError>>()?;
+    Ok(DataFile {
+        has_weights,
+        data,
+        sample_stats: None,
+    })
+}
+
+/// Each result as JSON, with its log weight if the results are weighted.
+type ResultJson = Vec<(serde_json::Value, Option<f64>)>;
+
+/// Whether the results have weights, and the results.
+fn read_result_json(path: &Path) -> Result<(bool, ResultJson), RuntimeError> {
+    let json = read_json(path)?;
+    let has_weights = json.get("has_weights").and_then(|w| w.as_bool());
+    let (has_weights, data) = match (has_weights, json.get("data")) {
+        (Some(has_weights), Some(serde_json::Value::Array(data))) => (has_weights, data.clone()),
+        _ => {
+            return err!(
+                "`{}` is not a data file written by thisppl, with `has_weights` and `data`.",
+                path.display()
+            )
+        }
+    };
+
+    let mut results = Vec::with_capacity(data.len());
+    for item in data {
+        if !has_weights {
+            results.push((item, None));
+            continue;
+        }
+        match item {
+            serde_json::Value::Array(mut pair) if pair.len() == 2 => {
+                let weight = pair.pop().unwrap();
+                let log_weight = match weight {
+                    serde_json::Value::Null => f64::NEG_INFINITY,
+                    w => match w.as_f64() {
+                        Some(w) => w,
+                        None => return err!("`{}` has a weight of {}, which isn't a number.", path.display(), w),
+                    },
+                };
+                results.push((pair.pop().unwrap(), Some(log_weight)));
+            }
+            item => {
+                return err!(
+                    "`{}` has weights, so every result should be a `[value, log-weight]` pair, but found {}.",
+                    path.display(),
+                    item
+                )
+            }
+        }
+    }
+    Ok((has_weights, results))
+}
+
+/// The inverse of how results are serialised, with distributions read from
+/// `{"distribution": name, "params": {...}}`.
+fn result_from_json(json: serde_json::Value, path: &Path) -> Result<ProgramResult, RuntimeError> {
+    match json {
+        serde_json::Value::Bool(x) => Ok(ProgramResult::One(ResultValue::Boolean(x))),
+        serde_json::Value::Number(x) => Ok(ProgramResult::One(match x.as_i64() {
+            Some(x) => ResultValue::Int(x),
+            None => ResultValue::Float(x.as_f64().unwrap_or(f64::NAN)),
+        })),
+        // Infinities and NaN are written as `null`.
+        serde_json::Value::Null => Ok(ProgramResult::One(ResultValue::Float(f64::NAN))),
+        serde_json::Value::Array(items) => Ok(ProgramResult::Many(
+            items
+                .into_iter()
+                .map(|item| result_from_json(item, path))
+                .collect::<Result<_, _>>()?,
+        )),
+        serde_json::Value::Object(mut map) => {
+            match (map.remove("distribution"), map.remove("params")) {
+                (
+                    Some(serde_json::Value::String(name)),
+                    Some(serde_json::Value::Object(params)),
+                ) => Ok(ProgramResult::Distribution(DistributionResult {
+                    distribution: leak(name),
+                    params: params
+                        .into_iter()
+                        .map(|(param, json)| Ok((leak(param), result_from_json(json, path)?)))
+                        .collect::<Result<_, RuntimeError>>()?,
+                })),
+                _ => err!(
+                    "`{}` contains an object which isn't a distribution.",
+                    path.display()
+                ),
+            }
+        }
+        serde_json::Value::String(_) => err!(
+            "`{}` contains {}, which can't be read as a result.",
+            path.display(),
+            json
+        ),
+    }
+}
+
+/// Distribution and parameter names are `&'static str`, since they usually come from the built-ins.
+fn leak(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
+/// A `--data` option, either `name=path`, binding the file's contents to `name`, or just `path`, binding each key
+/// of a JSON object (as in a Stan data file) or each column of a CSV file to its own name.
+#[derive(Debug)]
+pub struct DataArg {
+    name: Option<String>,
+    path: PathBuf,
+}
+
+impl FromStr for DataArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, path) = match s.split_once('=') {
+            Some((name, path)) => (Some(name), path),
+            None => (None, s),
+        };
+        if let Some(name) = name {
+            if !is_identifier(name) {
+                return Err(format!("`{}` can't be used as a variable name.", name));
+            }
+        }
+        if path.is_empty() {
+            return Err("Expected `name=path` or `path`.".to_owned());
+        }
+        Ok(DataArg {
+            name: name.map(str::to_owned),
+            path: PathBuf::from(path),
+        })
+    }
+}
+
+/// The same characters as the grammar allows in identifiers, so that every name can be used in the program.
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    let first_ok = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    first_ok && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Reads every `--data` file, in order, into the bindings they give.
+pub fn read_data(args: &[DataArg]) -> Result<Vec<Binding>, RuntimeError> {
+    let mut bindings: Vec<Binding> = Vec::new();
+    for arg in args {
+        let extension = arg.path.extension().and_then(|e| e.to_str());
+        let named = match extension {
+            Some("json") => read_json_data(arg)?,
+            Some("csv") => read_csv_data(arg)?,
+            _ => {
+                return err!(
+                    "`{}` should be a `.json` or `.csv` file.",
+                    arg.path.display()
+                )
+            }
+        };
+        for (ident, val) in named {
+            if bindings.iter().any(|b| b.ident == ident) {
+                return err!("`{}` is bound by more than one `--data` option.", ident);
+            }
+            if !is_identifier(&ident) {
+                return err!(
+                    "`{}` binds `{}`, which can't be used as a variable name.",
+                    arg.path.display(),
+                    ident
+                );
+            }
+            bindings.push(Binding { ident, val });
+        }
+    }
+    Ok(bindings)
+}
+
+fn read_json_data(arg: &DataArg) -> Result<Vec<(String, Value)>, RuntimeError> {
+    let path = arg.path.as_path();
+    match (&arg.name, read_json(path)?) {
+        (None, serde_json::Value::Object(map)) => map
+            .into_iter()
+            .map(|(key, json)| Ok((key, value_from_json(json, path)?)))
+            .collect(),
+        (None, _) => err!(
+            "`{}` isn't an object of named values, so it needs a name, as in `--data name={}`.",
+            path.display(),
+            path.display()
+        ),
+        (Some(name), json) => Ok(vec![(name.clone(), value_from_json(json, path)?)]),
+    }
+}
+
+/// The first line of a CSV file names the columns. There is no quoting, so cells can't contain commas.
+fn read_csv_data(arg: &DataArg) -> Result<Vec<(String, Value)>, RuntimeError> {
+    let path = arg.path.as_path();
+    let text = std::fs::read_to_string(path)
+        .or_else(|e| err!("Couldn't read `{}`: {}", path.display(), e))?;
+    let mut lines = text.lines().filter(|line| !line.trim().is_empty());
+    let header: Vec<String> = match lines.next() {
+        Some(line) => line.split(',').map(|c| c.trim().to_owned()).collect(),
+        None => return err!("`{}` is empty.", path.display()),
+    };
+
+    let mut rows: Vec<Vec<Value>> = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let row = line
+            .split(',')
+            .map(|cell| csv_cell(cell.trim(), path))
+            .collect::<Result<Vec<_>, _>>()?;
+        if row.len() != header.len() {
+            return err!(
+                "Row {} of `{}` has {} cells, but the header names {} columns.",
+                i + 1,
+                path.display(),
+                row.len(),
+                header.len()
+            );
+        }
+        rows.push(row);
+    }
+
+    match &arg.name {
+        // A single column is a vector, and several columns are a vector of rows, like a matrix.
+        Some(name) if header.len() == 1 => Ok(vec![(
+            name.clone(),
+            Value::Vector(rows.into_iter().flatten().collect()),
+        )]),
+        Some(name) => Ok(vec![(
+            name.clone(),
+            Value::Vector(rows.into_iter().map(Value::Vector).collect()),
+        )]),
+        None => {
+            let mut columns: Vec<Vec<Value>> = vec![Vec::with_capacity(rows.len()); header.len()];
+            for row in rows {
+                for (column, cell) in columns.iter_mut().zip(row) {
+                    column.push(cell);
+                }
+            }
+            Ok(header
+                .into_iter()
+                .zip(columns)
+                .map(|(name, column)| (name, Value::Vector(column)))
+                .collect())
+        }
+    }
+}
+
+fn csv_cell(cell: &str, path: &Path) -> Result<Value, RuntimeError> {
+    if let Ok(x) = cell.parse::<i64>() {
+        Ok(Value::Integer(x))
+    } else if let Ok(x) = cell.parse::<f64>() {
+        Ok(Value::Float(x))
+    } else if let Ok(x) = cell.parse::<bool>() {
+        Ok(Value::Boolean(x))
+    } else {
+        err!(
+            "`{}` contains `{}`, which isn't a number or boolean.",
+            path.display(),
+            cell
+        )
+    }
+}
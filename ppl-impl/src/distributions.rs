@@ -1,9 +1,313 @@
-use crate::types::{RuntimeError, Value};
+use std::{
+    f64::consts::{PI, SQRT_2},
+    fmt,
+    rc::Rc,
+};
+
+use crate::{
+    matrix::Matrix,
+    types::{RuntimeError, Value, ValueImpls},
+};
+
+mod combinators;
+mod continuous;
+mod discrete;
+mod empirical;
+mod multivariate;
+#[cfg(test)]
+mod tests;
+mod user;
+
+pub use combinators::{Iid, Mixture, Product, ShiftScale, Truncated};
+pub use continuous::{Beta, Cauchy, Exponential, HalfNormal, Laplace, LogNormal, StudentT, UniformContinuous};
+pub use discrete::{Binomial, Categorical, Geometric, NegativeBinomial, Poisson, UniformDiscrete};
+pub use empirical::Empirical;
+pub use multivariate::{DiagonalNormal, InverseWishart, Lkj, Multinomial, MultivariateNormal, Wishart};
+pub use user::UserDistribution;
 
 pub trait Distribution: std::fmt::Debug {
     fn sample(&self) -> Result<Value, RuntimeError>;
     fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError>;
     fn name(&self) -> &'static str;
+
+    /// The parameters the distribution was created with, in the order its built-in takes them.
+    fn params(&self) -> Vec<(&'static str, Value)>;
+    fn support(&self) -> Support;
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        unavailable("mean", self.name())
+    }
+
+    /// The variance, or the covariance matrix for distributions over vectors. For distributions over matrices,
+    /// the variance of each element.
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        unavailable("variance", self.name())
+    }
+
+    /// The probability of drawing a value no greater than `x`.
+    fn cdf(&self, _x: &Value) -> Result<f64, RuntimeError> {
+        unavailable("cdf", self.name())
+    }
+
+    /// The smallest value whose `cdf` is at least `p`, which must be between 0 and 1.
+    fn quantile(&self, _p: f64) -> Result<Value, RuntimeError> {
+        unavailable("quantile function", self.name())
+    }
+
+    /// The entropy in nats, which is the differential entropy for continuous distributions.
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        unavailable("entropy", self.name())
+    }
+}
+
+/// The set of values a distribution can produce.
+#[derive(Clone, Debug)]
+pub enum Support {
+    /// Numbers from `low` to `high`, either of which may be infinite.
+    Real { low: f64, high: f64 },
+    /// Integers from `low` to `high` inclusive, with no upper bound when `high` is `None`.
+    Integer { low: i64, high: Option<i64> },
+    Boolean,
+    /// Exactly the listed values.
+    Values(Vec<Value>),
+    /// Vectors of this many numbers.
+    RealVector(usize),
+    /// Vectors of this many non-negative numbers which sum to 1.
+    Simplex(usize),
+    /// Vectors of `len` non-negative integers which sum to `total`.
+    Counts { len: usize, total: u64 },
+    /// Symmetric positive definite matrices of this size.
+    PositiveDefinite(usize),
+    /// Positive definite matrices of this size with ones on the diagonal.
+    Correlation(usize),
+    /// Vectors whose elements are in each of these supports in turn.
+    Independent(Vec<Support>),
+    /// When the support can't be worked out, e.g. for distributions declared in the program.
+    Unknown,
+}
+
+impl Support {
+    /// The support as a program value: `[low high]` for ranges, or every value when there are finitely many.
+    /// `None` for supports which can't be written down that way.
+    pub fn to_value(&self) -> Option<Value> {
+        match self {
+            Support::Real { low, high } => Some(Value::Vector(vec![Value::Float(*low), Value::Float(*high)])),
+            Support::Integer { low, high } => Some(Value::Vector(vec![
+                Value::Integer(*low),
+                high.map_or(Value::Float(f64::INFINITY), Value::Integer),
+            ])),
+            Support::Boolean => Some(Value::Vector(vec![Value::Boolean(false), Value::Boolean(true)])),
+            Support::Values(values) => Some(Value::Vector(values.clone())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Support {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Support::Real { low, high } => write!(
+                f,
+                "{}{:?}, {:?}{}",
+                if low.is_finite() { "[" } else { "(" },
+                low,
+                high,
+                if high.is_finite() { "]" } else { ")" }
+            ),
+            Support::Integer { low, high: Some(high) } => write!(f, "integers from {} to {}", low, high),
+            Support::Integer { low, high: None } => write!(f, "integers from {} up", low),
+            Support::Boolean => write!(f, "booleans"),
+            Support::Values(values) => write!(f, "{}", Value::Vector(values.clone())),
+            Support::RealVector(len) => write!(f, "vectors of {} numbers", len),
+            Support::Simplex(len) => write!(f, "vectors of {} non-negative numbers summing to 1", len),
+            Support::Counts { len, total } => {
+                write!(f, "vectors of {} non-negative integers summing to {}", len, total)
+            }
+            Support::PositiveDefinite(dim) => write!(f, "{} x {} positive definite matrices", dim, dim),
+            Support::Correlation(dim) => write!(f, "{} x {} correlation matrices", dim, dim),
+            Support::Independent(supports) if supports.windows(2).all(|pair| pair[0].to_string() == pair[1].to_string()) => {
+                write!(f, "vectors of {} elements, each in {}", supports.len(), supports[0])
+            }
+            Support::Independent(supports) => {
+                write!(f, "vectors whose elements are in ")?;
+                for (i, support) in supports.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", support)?;
+                }
+                Ok(())
+            }
+            Support::Unknown => write!(f, "not known"),
+        }
+    }
+}
+
+fn unavailable<T>(what: &str, dist: &str) -> Result<T, RuntimeError> {
+    err!("The {} of `{}` is not available.", what, dist)
+}
+
+/// For moments which don't exist, such as the mean of a Cauchy distribution.
+fn undefined<T>(what: &str, dist: &str, condition: &str) -> Result<T, RuntimeError> {
+    err!("The {} of `{}` is undefined{}.", what, dist, condition)
+}
+
+/// Writes `name(param=value, ...)`, which is how every distribution prints.
+fn fmt_distribution(dist: &dyn Distribution, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}(", dist.name())?;
+    for (i, (name, val)) in dist.params().iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}={}", name, val)?;
+    }
+    write!(f, ")")
+}
+
+fn float_vector(xs: &[f64]) -> Value {
+    Value::Vector(xs.iter().map(|x| Value::Float(*x)).collect())
+}
+
+fn matrix_value(m: Matrix) -> Value {
+    Value::Matrix(Rc::new(m))
+}
+
+/// The point at which to evaluate the cdf of a distribution over numbers.
+fn cdf_point(val: &Value, dist: &str) -> Result<f64, RuntimeError> {
+    val.try_get_numeric(&format!("`{}` can only evaluate the cdf of a number.", dist))
+}
+
+fn std_normal_cdf(z: f64) -> f64 {
+    0.5 * special::Error::compl_error(-z / SQRT_2)
+}
+
+/// By bisection, since `special`'s inverse error function is only accurate to single precision.
+fn std_normal_quantile(p: f64) -> f64 {
+    if p <= 0.0 {
+        f64::NEG_INFINITY
+    } else if p >= 1.0 {
+        f64::INFINITY
+    } else {
+        invert_cdf(std_normal_cdf, p, -1.0, 1.0)
+    }
+}
+
+/// Inverts an increasing, continuous `cdf` by bisection. The bracket `[low, high]` is widened until it contains the
+/// answer, so a bound of the support can be passed in to stop it from being crossed. `p` must be strictly between
+/// 0 and 1.
+fn invert_cdf<F: Fn(f64) -> f64>(cdf: F, p: f64, mut low: f64, mut high: f64) -> f64 {
+    while cdf(low) > p && low.is_finite() {
+        low -= 2.0 * (high - low);
+    }
+    while cdf(high) < p && high.is_finite() {
+        high += 2.0 * (high - low);
+    }
+    for _ in 0..200 {
+        let mid = 0.5 * (low + high);
+        if cdf(mid) < p {
+            low = mid;
+        } else {
+            high = mid;
+        }
+        if high - low <= 1e-14 * mid.abs().max(1.0) {
+            break;
+        }
+    }
+    0.5 * (low + high)
+}
+
+/// Evaluates the cdf of a distribution over the integers `[low, high]` at `x`, which may fall between integers.
+/// `cdf` is only called with integers inside the support.
+fn integer_cdf<F: Fn(i64) -> f64>(x: f64, low: i64, high: Option<i64>, cdf: F) -> f64 {
+    let k = x.floor();
+    if k < low as f64 {
+        0.0
+    } else if k == f64::INFINITY || high.is_some_and(|high| k >= high as f64) {
+        1.0
+    } else {
+        cdf(k as i64).min(1.0)
+    }
+}
+
+/// The smallest integer from `low` whose `cdf` is at least `p`, found by doubling and then bisecting. Never goes
+/// past `high`. The search is done in `i128`, so that bounds near the ends of `i64` don't overflow.
+fn integer_quantile<F: Fn(i64) -> f64>(cdf: F, p: f64, low: i64, high: Option<i64>) -> i64 {
+    let limit = high.unwrap_or(i64::MAX / 2) as i128;
+    let (mut below, mut above) = (low as i128 - 1, low as i128);
+    let mut step = 1;
+    while above < limit && cdf(above as i64) < p {
+        below = above;
+        above = (above + step).min(limit);
+        step *= 2;
+    }
+    // The answer is in (below, above].
+    while above - below > 1 {
+        let mid = below + (above - below) / 2;
+        if cdf(mid as i64) < p {
+            below = mid;
+        } else {
+            above = mid;
+        }
+    }
+    above as i64
+}
+
+/// The entropy of a distribution over the integers `[low, high]`. Without an upper bound, sums until the mass is
+/// falling away and what remains is negligible.
+fn integer_entropy<F: Fn(i64) -> f64>(log_pmf: F, low: i64, high: Option<i64>) -> f64 {
+    let mut entropy = 0.0;
+    let mut previous = f64::NEG_INFINITY;
+    let mut k = low;
+    while high.is_none_or(|high| k <= high) {
+        let log_p = log_pmf(k);
+        if log_p > f64::NEG_INFINITY {
+            entropy -= log_p.exp() * log_p;
+        }
+        if high.is_none() && log_p < previous && log_p < -45.0 {
+            break;
+        }
+        previous = log_p;
+        k += 1;
+    }
+    entropy
+}
+
+/// A quantile of 1 for a distribution with no largest value.
+fn unbounded_quantile(p: f64, dist: &str) -> Result<(), RuntimeError> {
+    if p >= 1.0 {
+        return err!("The quantile of `{}` at 1 is infinite.", dist);
+    }
+    Ok(())
+}
+
+/// Returns an error describing the invalid parameter unless `valid` holds.
+fn check_param(valid: bool, dist: &str, param: &str, requirement: &str, value: f64) -> Result<(), RuntimeError> {
+    if valid {
+        Ok(())
+    } else {
+        err!("`{}` needs {} to be {}, but was given {}.", dist, param, requirement, value)
+    }
+}
+
+/// `x * ln(y)`, taken to be 0 when `x` is 0 so that probabilities of 0 or 1 don't produce NaN.
+fn x_ln_y(x: f64, y: f64) -> f64 {
+    if x == 0.0 {
+        0.0
+    } else {
+        x * y.ln()
+    }
+}
+
+fn ln_gamma(x: f64) -> f64 {
+    special::Gamma::ln_gamma(x).0
+}
+
+fn digamma(x: f64) -> f64 {
+    special::Gamma::digamma(x)
+}
+
+fn ln_beta(a: f64, b: f64) -> f64 {
+    special::Beta::ln_beta(a, b)
 }
 
 pub struct Normal {
@@ -11,15 +315,23 @@ pub struct Normal {
     pub sigma: f64,
 }
 
+impl Normal {
+    pub fn new(mu: f64, sigma: f64) -> Result<Self, RuntimeError> {
+        check_param(mu.is_finite(), "normal", "mu", "finite", mu)?;
+        check_param(sigma > 0.0 && sigma.is_finite(), "normal", "sigma", "positive", sigma)?;
+        Ok(Normal { mu, sigma })
+    }
+}
+
 impl Distribution for Normal {
     fn sample(&self) -> Result<Value, RuntimeError> {
         use rand::prelude::*;
         use rand_distr::Normal;
         let distr = match Normal::new(self.mu, self.sigma) {
             Ok(dist) => dist,
-            Err(_) => return err!("Error creating discrete distribution."),
+            Err(_) => return err!("Error creating `normal` distribution."),
         };
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::random::rng();
         Ok(Value::Float(rng.sample::<f64, _>(distr)))
     }
 
@@ -31,7 +343,7 @@ impl Distribution for Normal {
         };
 
         let log_density = -(self.sigma * std::f64::consts::TAU.sqrt()).ln()
-            + -(1f64 / 2f64) * ((val - self.mu).powi(2) / self.sigma);
+            + -(1f64 / 2f64) * ((val - self.mu) / self.sigma).powi(2);
 
         Ok(log_density)
     }
@@ -39,18 +351,67 @@ impl Distribution for Normal {
     fn name(&self) -> &'static str {
         "normal"
     }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![("mu", Value::Float(self.mu)), ("sigma", Value::Float(self.sigma))]
+    }
+
+    fn support(&self) -> Support {
+        Support::Real { low: f64::NEG_INFINITY, high: f64::INFINITY }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.mu))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.sigma * self.sigma))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        Ok(std_normal_cdf((cdf_point(x, self.name())? - self.mu) / self.sigma))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.mu + self.sigma * std_normal_quantile(p)))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(0.5 * (2.0 * PI * std::f64::consts::E * self.sigma * self.sigma).ln())
+    }
 }
 
 impl std::fmt::Debug for Normal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}(...)", self.name())
+        fmt_distribution(self, f)
     }
 }
 
+/// A distribution over the indices of `weights`, in proportion to each weight.
 pub struct Discrete {
     pub weights: Vec<f64>,
 }
 
+impl Discrete {
+    pub fn new(weights: Vec<f64>) -> Result<Self, RuntimeError> {
+        if weights.is_empty() {
+            return err!("`discrete` needs at least one weight.");
+        }
+        for w in &weights {
+            check_param(*w >= 0.0 && w.is_finite(), "discrete", "each weight", "non-negative", *w)?;
+        }
+        if weights.iter().sum::<f64>() <= 0.0 {
+            return err!("`discrete` needs at least one non-zero weight.");
+        }
+        Ok(Discrete { weights })
+    }
+
+    fn probs(&self) -> Vec<f64> {
+        let total: f64 = self.weights.iter().sum();
+        self.weights.iter().map(|w| w / total).collect()
+    }
+}
+
 impl Distribution for Discrete {
     fn sample(&self) -> Result<Value, RuntimeError> {
         use rand::prelude::*;
@@ -59,31 +420,70 @@ impl Distribution for Discrete {
             Ok(w) => w,
             Err(_) => return err!("Error creating `discrete` distribution."),
         };
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::random::rng();
         let val = rng.sample::<usize, _>(distr);
         Ok(Value::Integer(val as i64))
     }
 
     fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
-        use probability::distribution::{Categorical, Discrete};
-        let d = Categorical::new(&self.weights);
-
         let val = match val {
-            Value::Integer(v) if *v >= 0 => *v as usize,
-            _ => return err!("Discrete distribution can only eval density of a positive integer."),
+            Value::Integer(v) => *v,
+            _ => return err!("Discrete distribution can only eval density of an integer."),
         };
 
-        Ok(d.mass(val).ln())
+        if val < 0 || val as usize >= self.weights.len() {
+            return Ok(f64::NEG_INFINITY);
+        }
+
+        Ok((self.weights[val as usize] / self.weights.iter().sum::<f64>()).ln())
     }
 
     fn name(&self) -> &'static str {
         "discrete"
     }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![("weights", float_vector(&self.weights))]
+    }
+
+    fn support(&self) -> Support {
+        Support::Integer { low: 0, high: Some(self.weights.len() as i64 - 1) }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        let probs = self.probs();
+        Ok(Value::Float(probs.iter().enumerate().map(|(k, p)| k as f64 * p).sum()))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let probs = self.probs();
+        let mean: f64 = probs.iter().enumerate().map(|(k, p)| k as f64 * p).sum();
+        Ok(Value::Float(probs.iter().enumerate().map(|(k, p)| (k as f64 - mean).powi(2) * p).sum()))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let probs = self.probs();
+        let high = probs.len() as i64 - 1;
+        Ok(integer_cdf(cdf_point(x, self.name())?, 0, Some(high), |k| {
+            probs[..=k as usize].iter().sum()
+        }))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        let probs = self.probs();
+        let high = probs.len() as i64 - 1;
+        let cdf = |k: i64| probs[..=k as usize].iter().sum::<f64>();
+        Ok(Value::Integer(integer_quantile(cdf, p, 0, Some(high))))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(-self.probs().iter().map(|p| x_ln_y(*p, *p)).sum::<f64>())
+    }
 }
 
 impl std::fmt::Debug for Discrete {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}(...)", self.name())
+        fmt_distribution(self, f)
     }
 }
 
@@ -91,6 +491,13 @@ pub struct Bernoulli {
     pub param: f64,
 }
 
+impl Bernoulli {
+    pub fn new(param: f64) -> Result<Self, RuntimeError> {
+        check_param((0.0..=1.0).contains(&param), "flip", "the probability", "between 0 and 1", param)?;
+        Ok(Bernoulli { param })
+    }
+}
+
 impl Distribution for Bernoulli {
     fn sample(&self) -> Result<Value, RuntimeError> {
         use rand::prelude::*;
@@ -99,36 +506,73 @@ impl Distribution for Bernoulli {
             Ok(w) => w,
             Err(_) => return err!("Error creating `bernoulli` distribution."),
         };
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::random::rng();
         let val = rng.sample::<bool, _>(distr);
         Ok(Value::Boolean(val))
     }
 
     fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
-        use probability::distribution::{Bernoulli, Discrete};
-        let d = Bernoulli::new(self.param);
-
-        let val = val.try_get_bool("Bernoulli distribution can only eval density of a positive integer.")?;
+        let val = val.try_get_bool("Bernoulli distribution can only eval density of a boolean.")?;
 
-        Ok(d.mass(val as u8).ln())
+        Ok(if val { self.param } else { 1.0 - self.param }.ln())
     }
 
     fn name(&self) -> &'static str {
         "Bernoulli"
     }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![("p", Value::Float(self.param))]
+    }
+
+    fn support(&self) -> Support {
+        Support::Boolean
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.param))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.param * (1.0 - self.param)))
+    }
+
+    /// Orders `false` before `true`.
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let x = x.try_get_bool("Bernoulli distribution can only eval cdf of a boolean.")?;
+        Ok(if x { 1.0 } else { 1.0 - self.param })
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        Ok(Value::Boolean(p > 1.0 - self.param))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        let p = self.param;
+        Ok(-x_ln_y(p, p) - x_ln_y(1.0 - p, 1.0 - p))
+    }
 }
 
 impl std::fmt::Debug for Bernoulli {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}(...)", self.name())
+        fmt_distribution(self, f)
     }
 }
 
+/// Gamma distribution with shape `alpha` and scale `beta`.
 pub struct Gamma {
     pub alpha: f64,
     pub beta: f64,
 }
 
+impl Gamma {
+    pub fn new(alpha: f64, beta: f64) -> Result<Self, RuntimeError> {
+        check_param(alpha > 0.0 && alpha.is_finite(), "gamma", "the shape", "positive", alpha)?;
+        check_param(beta > 0.0 && beta.is_finite(), "gamma", "the scale", "positive", beta)?;
+        Ok(Gamma { alpha, beta })
+    }
+}
+
 impl Distribution for Gamma {
     fn sample(&self) -> Result<Value, RuntimeError> {
         use rand::prelude::*;
@@ -137,29 +581,68 @@ impl Distribution for Gamma {
             Ok(w) => w,
             Err(_) => return err!("Error creating `gamma` distribution."),
         };
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::random::rng();
         let val = rng.sample(distr);
         Ok(Value::Float(val))
     }
 
     fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
-        use probability::distribution::{Continuous, Gamma};
-        let d = Gamma::new(self.alpha, self.beta);
-
         let val = val.clone().try_into_numeric(
             "`gamma` can only evaluate the density of a floating point number.",
         )?;
-        Ok(d.density(val).ln())
+
+        if val < 0.0 {
+            return Ok(f64::NEG_INFINITY);
+        }
+
+        Ok(x_ln_y(self.alpha - 1.0, val) - val / self.beta - ln_gamma(self.alpha) - self.alpha * self.beta.ln())
     }
 
     fn name(&self) -> &'static str {
         "gamma"
     }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![("shape", Value::Float(self.alpha)), ("scale", Value::Float(self.beta))]
+    }
+
+    fn support(&self) -> Support {
+        Support::Real { low: 0.0, high: f64::INFINITY }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.alpha * self.beta))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.alpha * self.beta * self.beta))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let x = cdf_point(x, self.name())?;
+        if x <= 0.0 {
+            return Ok(0.0);
+        }
+        Ok(special::Gamma::inc_gamma(x / self.beta, self.alpha))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        if p <= 0.0 || p >= 1.0 {
+            return Ok(Value::Float(if p <= 0.0 { 0.0 } else { f64::INFINITY }));
+        }
+        let cdf = |x: f64| special::Gamma::inc_gamma(x, self.alpha);
+        Ok(Value::Float(self.beta * invert_cdf(cdf, p, 0.0, self.alpha)))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        let alpha = self.alpha;
+        Ok(alpha + self.beta.ln() + ln_gamma(alpha) + (1.0 - alpha) * digamma(alpha))
+    }
 }
 
 impl std::fmt::Debug for Gamma {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}(...)", self.name())
+        fmt_distribution(self, f)
     }
 }
 
@@ -167,32 +650,105 @@ pub struct Dirichlet {
     pub parameters: Vec<f64>,
 }
 
+impl Dirichlet {
+    pub fn new(parameters: Vec<f64>) -> Result<Self, RuntimeError> {
+        if parameters.len() < 2 {
+            return err!("`dirichlet` needs at least two concentration parameters.");
+        }
+        for alpha in &parameters {
+            check_param(
+                *alpha > 0.0 && alpha.is_finite(),
+                "dirichlet",
+                "each concentration parameter",
+                "positive",
+                *alpha,
+            )?;
+        }
+        Ok(Dirichlet { parameters })
+    }
+}
+
 impl Distribution for Dirichlet {
     fn sample(&self) -> Result<Value, RuntimeError> {
         use rand::prelude::*;
         use rand_distr::Dirichlet;
         let distr = match Dirichlet::new(&self.parameters) {
             Ok(w) => w,
-            Err(_) => return err!("Error creating `gamma` distribution."),
+            Err(_) => return err!("Error creating `dirichlet` distribution."),
         };
-        let mut rng = rand::thread_rng();
+        let mut rng = crate::random::rng();
         let vals = rng.sample(distr);
         Ok(Value::Vector(
             vals.into_iter().map(Value::Float).collect(),
         ))
     }
 
-    fn log_pdf(&self, _vals: &Value) -> Result<f64, RuntimeError> {
-        unimplemented!("Evaluating the density of `dirichlet` is not implemented.")
+    fn log_pdf(&self, vals: &Value) -> Result<f64, RuntimeError> {
+        let xs = vals
+            .clone()
+            .try_into_vector("`dirichlet` can only evaluate the density of a vector.")?
+            .try_into_numeric("`dirichlet` can only evaluate the density of a vector of numbers.")?;
+
+        if xs.len() != self.parameters.len() {
+            return err!(
+                "`dirichlet` can only evaluate the density of a vector of length {}, but was given one of length {}.",
+                self.parameters.len(),
+                xs.len()
+            );
+        }
+
+        // Outside the simplex, allowing for rounding error in the sum.
+        if xs.iter().any(|x| *x < 0.0) || (xs.iter().sum::<f64>() - 1.0).abs() > 1e-6 {
+            return Ok(f64::NEG_INFINITY);
+        }
+
+        let alpha_sum: f64 = self.parameters.iter().sum();
+        Ok(ln_gamma(alpha_sum)
+            + xs.iter()
+                .zip(&self.parameters)
+                .map(|(x, alpha)| x_ln_y(alpha - 1.0, *x) - ln_gamma(*alpha))
+                .sum::<f64>())
     }
 
     fn name(&self) -> &'static str {
         "dirichlet"
     }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![("alpha", float_vector(&self.parameters))]
+    }
+
+    fn support(&self) -> Support {
+        Support::Simplex(self.parameters.len())
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        let alpha_sum: f64 = self.parameters.iter().sum();
+        let mean: Vec<f64> = self.parameters.iter().map(|a| a / alpha_sum).collect();
+        Ok(float_vector(&mean))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let alpha_sum: f64 = self.parameters.iter().sum();
+        let mean: Vec<f64> = self.parameters.iter().map(|a| a / alpha_sum).collect();
+        let k = mean.len();
+        Ok(matrix_value(Matrix::from_fn(k, k, |i, j| {
+            let diagonal = if i == j { mean[i] } else { 0.0 };
+            (diagonal - mean[i] * mean[j]) / (alpha_sum + 1.0)
+        })))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        let alpha_sum: f64 = self.parameters.iter().sum();
+        let k = self.parameters.len() as f64;
+        let ln_normaliser = self.parameters.iter().map(|a| ln_gamma(*a)).sum::<f64>() - ln_gamma(alpha_sum);
+        Ok(ln_normaliser + (alpha_sum - k) * digamma(alpha_sum)
+            - self.parameters.iter().map(|a| (a - 1.0) * digamma(*a)).sum::<f64>())
+    }
 }
 
 impl std::fmt::Debug for Dirichlet {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}(...)", self.name())
+        fmt_distribution(self, f)
     }
 }
@@ -0,0 +1,739 @@
+use std::{fmt, rc::Rc};
+
+use super::{
+    cdf_point, check_param, float_vector, fmt_distribution, integer_quantile, invert_cdf,
+    matrix_value, unavailable, Distribution, Support,
+};
+use crate::{
+    matrix::Matrix,
+    types::{RuntimeError, Value},
+};
+
+fn distribution_values(components: &[Rc<dyn Distribution>]) -> Value {
+    Value::Vector(
+        components
+            .iter()
+            .cloned()
+            .map(Value::Distribution)
+            .collect(),
+    )
+}
+
+fn log_sum_exp(xs: &[f64]) -> f64 {
+    let max = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + xs.iter().map(|x| (x - max).exp()).sum::<f64>().ln()
+}
+
+/// The mean of a distribution over numbers, for combinators whose summaries are only worked out for numbers.
+fn numeric_mean(
+    dist: &dyn Distribution,
+    what: &str,
+    combinator: &str,
+) -> Result<f64, RuntimeError> {
+    match dist.mean()? {
+        Value::Float(x) => Ok(x),
+        Value::Integer(k) => Ok(k as f64),
+        _ => err!(
+            "The {} of `{}` is only available for distributions over numbers.",
+            what,
+            combinator
+        ),
+    }
+}
+
+fn numeric_variance(
+    dist: &dyn Distribution,
+    what: &str,
+    combinator: &str,
+) -> Result<f64, RuntimeError> {
+    match dist.variance()? {
+        Value::Float(x) => Ok(x),
+        Value::Integer(k) => Ok(k as f64),
+        _ => err!(
+            "The {} of `{}` is only available for distributions over numbers.",
+            what,
+            combinator
+        ),
+    }
+}
+
+/// The smallest support which covers all of `supports`, when that's one of the ranges.
+fn union_support(supports: &[Support]) -> Support {
+    let mut real = Some((f64::INFINITY, f64::NEG_INFINITY));
+    let mut integer = Some((i64::MAX, Some(i64::MIN)));
+    for support in supports {
+        match support {
+            Support::Real { low, high } => {
+                real = real.map(|(l, h)| (l.min(*low), h.max(*high)));
+                integer = None;
+            }
+            Support::Integer { low, high } => {
+                integer =
+                    integer.map(|(l, h)| (l.min(*low), h.zip(*high).map(|(h, high)| h.max(high))));
+                real = None;
+            }
+            _ => {
+                real = None;
+                integer = None;
+            }
+        }
+    }
+    match (real, integer) {
+        (Some((low, high)), _) => Support::Real { low, high },
+        (_, Some((low, high))) => Support::Integer { low, high },
+        _ if supports
+            .windows(2)
+            .all(|pair| same_shape(&pair[0], &pair[1])) =>
+        {
+            supports[0].clone()
+        }
+        _ => Support::Unknown,
+    }
+}
+
+/// Whether two supports over vectors or matrices are the same set.
+fn same_shape(a: &Support, b: &Support) -> bool {
+    match (a, b) {
+        (Support::RealVector(m), Support::RealVector(n))
+        | (Support::Simplex(m), Support::Simplex(n))
+        | (Support::PositiveDefinite(m), Support::PositiveDefinite(n))
+        | (Support::Correlation(m), Support::Correlation(n)) => m == n,
+        (Support::Boolean, Support::Boolean) => true,
+        _ => false,
+    }
+}
+
+/// A weighted mixture of distributions over the same values.
+pub struct Mixture {
+    /// Normalised to sum to 1.
+    pub weights: Vec<f64>,
+    pub components: Vec<Rc<dyn Distribution>>,
+}
+
+impl Mixture {
+    pub fn new(
+        weights: Vec<f64>,
+        components: Vec<Rc<dyn Distribution>>,
+    ) -> Result<Self, RuntimeError> {
+        if components.is_empty() {
+            return err!("`mixture` needs at least one component.");
+        }
+        if weights.len() != components.len() {
+            return err!(
+                "`mixture` needs one weight per component, but was given {} weights and {} components.",
+                weights.len(),
+                components.len()
+            );
+        }
+        for w in &weights {
+            check_param(
+                w.is_finite() && *w >= 0.0,
+                "mixture",
+                "every weight",
+                "non-negative",
+                *w,
+            )?;
+        }
+        let total: f64 = weights.iter().sum();
+        check_param(
+            total > 0.0,
+            "mixture",
+            "the weights",
+            "positive in total",
+            total,
+        )?;
+        Ok(Mixture {
+            weights: weights.iter().map(|w| w / total).collect(),
+            components,
+        })
+    }
+
+    /// The weighted sum of a summary of each component.
+    fn weighted_sum<F>(&self, summary: F) -> Result<f64, RuntimeError>
+    where
+        F: Fn(&dyn Distribution) -> Result<f64, RuntimeError>,
+    {
+        let mut total = 0.0;
+        for (w, d) in self.weights.iter().zip(&self.components) {
+            total += w * summary(d.as_ref())?;
+        }
+        Ok(total)
+    }
+
+    fn cdf_at(&self, x: f64) -> Result<f64, RuntimeError> {
+        self.weighted_sum(|d| d.cdf(&Value::Float(x)))
+    }
+
+    /// The smallest and largest component quantiles at `p`, which the quantile of the mixture lies between.
+    fn quantile_bracket(&self, p: f64) -> Result<(f64, f64), RuntimeError> {
+        let (mut low, mut high) = (f64::INFINITY, f64::NEG_INFINITY);
+        for d in &self.components {
+            let q = d
+                .quantile(p)?
+                .try_into_numeric("The quantile of a `mixture` component must be a number.")?;
+            low = low.min(q);
+            high = high.max(q);
+        }
+        Ok((low, high))
+    }
+}
+
+impl Distribution for Mixture {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::WeightedIndex;
+        let distr = match WeightedIndex::new(&self.weights) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `mixture` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        self.components[rng.sample(distr)].sample()
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let mut terms = Vec::with_capacity(self.components.len());
+        for (w, d) in self.weights.iter().zip(&self.components) {
+            if *w > 0.0 {
+                terms.push(w.ln() + d.log_pdf(val)?);
+            }
+        }
+        Ok(log_sum_exp(&terms))
+    }
+
+    fn name(&self) -> &'static str {
+        "mixture"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("weights", float_vector(&self.weights)),
+            ("components", distribution_values(&self.components)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        let supports: Vec<Support> = self.components.iter().map(|d| d.support()).collect();
+        union_support(&supports)
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(
+            self.weighted_sum(|d| numeric_mean(d, "mean", "mixture"))?,
+        ))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let mean = self.weighted_sum(|d| numeric_mean(d, "variance", "mixture"))?;
+        let second_moment = self.weighted_sum(|d| {
+            let m = numeric_mean(d, "variance", "mixture")?;
+            Ok(numeric_variance(d, "variance", "mixture")? + m * m)
+        })?;
+        Ok(Value::Float((second_moment - mean * mean).max(0.0)))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        self.cdf_at(cdf_point(x, self.name())?)
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        let (low, high) = self.quantile_bracket(p)?;
+        // Any error from the cdf shows up here, so the searches below can ignore them.
+        self.cdf_at(low)?;
+        let cdf = |x: f64| self.cdf_at(x).unwrap_or(f64::NAN);
+        match self.support() {
+            Support::Integer { .. } => Ok(Value::Integer(integer_quantile(
+                |k| cdf(k as f64),
+                p,
+                low as i64,
+                Some(high as i64),
+            ))),
+            Support::Real { .. } if p <= 0.0 => Ok(Value::Float(low)),
+            Support::Real { .. } if p >= 1.0 => Ok(Value::Float(high)),
+            Support::Real { .. } => Ok(Value::Float(invert_cdf(cdf, p, low, high))),
+            _ => unavailable("quantile", self.name()),
+        }
+    }
+}
+
+impl fmt::Debug for Mixture {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// A distribution over numbers restricted to `[low, high]`.
+pub struct Truncated {
+    pub distribution: Rc<dyn Distribution>,
+    pub low: f64,
+    pub high: f64,
+    // The probability below `low`, and between the bounds, under the original distribution.
+    below: f64,
+    mass: f64,
+}
+
+impl Truncated {
+    pub fn new(
+        distribution: Rc<dyn Distribution>,
+        low: f64,
+        high: f64,
+    ) -> Result<Self, RuntimeError> {
+        check_param(
+            !low.is_nan(),
+            "truncated",
+            "the lower bound",
+            "a number",
+            low,
+        )?;
+        check_param(
+            high >= low,
+            "truncated",
+            "the upper bound",
+            "at least the lower bound",
+            high,
+        )?;
+        let integer = match distribution.support() {
+            Support::Real { .. } => false,
+            Support::Integer { .. } => true,
+            support => {
+                return err!(
+                    "`truncated` only works on distributions over numbers, but `{}` is over {}.",
+                    distribution.name(),
+                    support
+                )
+            }
+        };
+
+        // For integers, the lower bound itself is kept, so only the mass strictly below it is removed.
+        let below = if integer {
+            distribution.cdf(&Value::Float(low.ceil() - 1.0))?
+        } else {
+            distribution.cdf(&Value::Float(low))?
+        };
+        let mass = distribution.cdf(&Value::Float(high))? - below;
+        if mass <= 0.0 {
+            return err!(
+                "`truncated` needs `{:?}` to have some probability between {} and {}.",
+                distribution,
+                low,
+                high
+            );
+        }
+        Ok(Truncated {
+            distribution,
+            low,
+            high,
+            below,
+            mass,
+        })
+    }
+
+    /// Keeps a quantile of the original distribution inside the bounds, which rounding can push it out of.
+    fn clamp(&self, val: Value) -> Value {
+        match val {
+            Value::Integer(k) => {
+                Value::Integer(k.clamp(self.low.ceil() as i64, self.high.floor() as i64))
+            }
+            Value::Float(x) => Value::Float(x.clamp(self.low, self.high)),
+            other => other,
+        }
+    }
+}
+
+impl Distribution for Truncated {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::Open01;
+        let mut rng = crate::random::rng();
+        let u: f64 = rng.sample(Open01);
+        let p = (self.below + u * self.mass).min(1.0 - f64::EPSILON);
+        Ok(self.clamp(self.distribution.quantile(p)?))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let x = val.try_get_numeric("`truncated` can only evaluate the density of a number.")?;
+        if x < self.low || x > self.high {
+            return Ok(f64::NEG_INFINITY);
+        }
+        Ok(self.distribution.log_pdf(val)? - self.mass.ln())
+    }
+
+    fn name(&self) -> &'static str {
+        "truncated"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            (
+                "distribution",
+                Value::Distribution(self.distribution.clone()),
+            ),
+            ("low", Value::Float(self.low)),
+            ("high", Value::Float(self.high)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        match self.distribution.support() {
+            Support::Integer { low, high } => {
+                let bound = Some(self.high.floor() as i64).filter(|_| self.high.is_finite());
+                Support::Integer {
+                    low: low.max(self.low.ceil() as i64),
+                    high: match (high, bound) {
+                        (Some(high), Some(bound)) => Some(high.min(bound)),
+                        (high, bound) => high.or(bound),
+                    },
+                }
+            }
+            Support::Real { low, high } => Support::Real {
+                low: low.max(self.low),
+                high: high.min(self.high),
+            },
+            support => support,
+        }
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let x = cdf_point(x, self.name())?;
+        if x < self.low {
+            return Ok(0.0);
+        }
+        let below_x = self.distribution.cdf(&Value::Float(x.min(self.high)))?;
+        Ok(((below_x - self.below) / self.mass).clamp(0.0, 1.0))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        // The ends are the bounds, rather than wherever the original quantile happens to land.
+        match self.support() {
+            Support::Integer { low, .. } if p <= 0.0 => return Ok(Value::Integer(low)),
+            Support::Real { low, .. } if p <= 0.0 => return Ok(Value::Float(low)),
+            Support::Integer {
+                high: Some(high), ..
+            } if p >= 1.0 => return Ok(Value::Integer(high)),
+            Support::Real { high, .. } if p >= 1.0 && high.is_finite() => {
+                return Ok(Value::Float(high))
+            }
+            _ => {}
+        }
+        let p = (self.below + p * self.mass).min(1.0);
+        Ok(self.clamp(self.distribution.quantile(p)?))
+    }
+}
+
+impl fmt::Debug for Truncated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// `shift + scale * x` for `x` drawn from a continuous distribution over numbers or vectors.
+pub struct ShiftScale {
+    pub distribution: Rc<dyn Distribution>,
+    pub shift: f64,
+    pub scale: f64,
+}
+
+impl ShiftScale {
+    pub fn new(
+        distribution: Rc<dyn Distribution>,
+        shift: f64,
+        scale: f64,
+    ) -> Result<Self, RuntimeError> {
+        check_param(
+            shift.is_finite(),
+            "shift-scale",
+            "the shift",
+            "finite",
+            shift,
+        )?;
+        check_param(
+            scale.is_finite() && scale > 0.0,
+            "shift-scale",
+            "the scale",
+            "positive",
+            scale,
+        )?;
+        match distribution.support() {
+            Support::Real { .. } | Support::RealVector(_) | Support::Unknown => {}
+            support => {
+                return err!(
+                    "`shift-scale` only works on continuous distributions over numbers or vectors, but `{}` is over {}.",
+                    distribution.name(),
+                    support
+                )
+            }
+        }
+        Ok(ShiftScale {
+            distribution,
+            shift,
+            scale,
+        })
+    }
+
+    fn forward(&self, val: Value) -> Result<Value, RuntimeError> {
+        match val {
+            Value::Vector(xs) => Ok(Value::Vector(
+                xs.into_iter()
+                    .map(|x| self.forward(x))
+                    .collect::<Result<_, _>>()?,
+            )),
+            x => {
+                let x = x.try_into_numeric(
+                    "`shift-scale` can only transform numbers and vectors of numbers.",
+                )?;
+                Ok(Value::Float(self.shift + self.scale * x))
+            }
+        }
+    }
+
+    /// Undoes the transformation, also giving the number of elements for the change of variables.
+    fn inverse(&self, val: &Value) -> Result<(Value, usize), RuntimeError> {
+        match val {
+            Value::Vector(ys) => {
+                let mut xs = Vec::with_capacity(ys.len());
+                for y in ys {
+                    xs.push(Value::Float(
+                        (y.try_get_numeric(
+                            "`shift-scale` can only evaluate the density of numbers.",
+                        )? - self.shift)
+                            / self.scale,
+                    ));
+                }
+                Ok((Value::Vector(xs), ys.len()))
+            }
+            y => {
+                let y = y.try_get_numeric(
+                    "`shift-scale` can only evaluate the density of a number or a vector.",
+                )?;
+                Ok((Value::Float((y - self.shift) / self.scale), 1))
+            }
+        }
+    }
+}
+
+impl Distribution for ShiftScale {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        self.forward(self.distribution.sample()?)
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let (x, n) = self.inverse(val)?;
+        Ok(self.distribution.log_pdf(&x)? - n as f64 * self.scale.ln())
+    }
+
+    fn name(&self) -> &'static str {
+        "shift-scale"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            (
+                "distribution",
+                Value::Distribution(self.distribution.clone()),
+            ),
+            ("shift", Value::Float(self.shift)),
+            ("scale", Value::Float(self.scale)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        match self.distribution.support() {
+            Support::Real { low, high } => Support::Real {
+                low: self.shift + self.scale * low,
+                high: self.shift + self.scale * high,
+            },
+            support => support,
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        self.forward(self.distribution.mean()?)
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let factor = self.scale * self.scale;
+        match self.distribution.variance()? {
+            Value::Matrix(m) => Ok(matrix_value(m.map(|x| factor * x))),
+            v => Ok(Value::Float(
+                factor
+                    * v.try_into_numeric(
+                        "The variance of `shift-scale` must be a number or a matrix.",
+                    )?,
+            )),
+        }
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let y = cdf_point(x, self.name())?;
+        self.distribution
+            .cdf(&Value::Float((y - self.shift) / self.scale))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        self.forward(self.distribution.quantile(p)?)
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        let n = match self.distribution.support() {
+            Support::RealVector(n) => n,
+            _ => 1,
+        };
+        Ok(self.distribution.entropy()? + n as f64 * self.scale.ln())
+    }
+}
+
+impl fmt::Debug for ShiftScale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// Independent draws from each of the components, as a vector.
+pub struct Product {
+    pub components: Vec<Rc<dyn Distribution>>,
+}
+
+impl Product {
+    pub fn new(components: Vec<Rc<dyn Distribution>>) -> Result<Self, RuntimeError> {
+        if components.is_empty() {
+            return err!("`product` needs at least one component.");
+        }
+        Ok(Product { components })
+    }
+}
+
+impl Distribution for Product {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        let samples = self.components.iter().map(|d| d.sample());
+        Ok(Value::Vector(samples.collect::<Result<_, _>>()?))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let xs = val.clone().try_into_vector(&format!(
+            "`{}` can only evaluate the density of a vector.",
+            self.name()
+        ))?;
+        if xs.len() != self.components.len() {
+            return err!(
+                "`{}` is over vectors of length {}, but was given one of length {}.",
+                self.name(),
+                self.components.len(),
+                xs.len()
+            );
+        }
+        let mut total = 0.0;
+        for (d, x) in self.components.iter().zip(&xs) {
+            total += d.log_pdf(x)?;
+        }
+        Ok(total)
+    }
+
+    fn name(&self) -> &'static str {
+        "product"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![("components", distribution_values(&self.components))]
+    }
+
+    fn support(&self) -> Support {
+        Support::Independent(self.components.iter().map(|d| d.support()).collect())
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        let means = self.components.iter().map(|d| d.mean());
+        Ok(Value::Vector(means.collect::<Result<_, _>>()?))
+    }
+
+    /// The covariance matrix, which is diagonal since the elements are independent.
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let mut variances = Vec::with_capacity(self.components.len());
+        for d in &self.components {
+            variances.push(numeric_variance(d.as_ref(), "variance", self.name())?);
+        }
+        let n = variances.len();
+        Ok(matrix_value(Matrix::from_fn(n, n, |i, j| {
+            if i == j {
+                variances[i]
+            } else {
+                0.0
+            }
+        })))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        self.components.iter().map(|d| d.entropy()).sum()
+    }
+}
+
+impl fmt::Debug for Product {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// `n` independent draws from one distribution, as a vector. The same as a `product` of `n` copies.
+pub struct Iid {
+    pub distribution: Rc<dyn Distribution>,
+    pub n: usize,
+    copies: Product,
+}
+
+impl Iid {
+    pub fn new(distribution: Rc<dyn Distribution>, n: usize) -> Result<Self, RuntimeError> {
+        check_param(n > 0, "iid", "the number of draws", "positive", n as f64)?;
+        Ok(Iid {
+            copies: Product::new(vec![distribution.clone(); n])?,
+            distribution,
+            n,
+        })
+    }
+}
+
+impl Distribution for Iid {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        self.copies.sample()
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        self.copies.log_pdf(val)
+    }
+
+    fn name(&self) -> &'static str {
+        "iid"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            (
+                "distribution",
+                Value::Distribution(self.distribution.clone()),
+            ),
+            ("n", Value::Integer(self.n as i64)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        self.copies.support()
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        self.copies.mean()
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        self.copies.variance()
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(self.n as f64 * self.distribution.entropy()?)
+    }
+}
+
+impl fmt::Debug for Iid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
@@ -0,0 +1,773 @@
+use std::f64::consts::{E, LN_2, PI, SQRT_2, TAU};
+
+use super::{
+    cdf_point, check_param, digamma, fmt_distribution, invert_cdf, ln_beta, ln_gamma,
+    std_normal_cdf, std_normal_quantile, undefined, x_ln_y, Distribution, Support,
+};
+use crate::types::{RuntimeError, Value};
+
+fn outcome(val: &Value, name: &str) -> Result<f64, RuntimeError> {
+    val.try_get_numeric(&format!(
+        "`{}` can only evaluate the density of a number.",
+        name
+    ))
+}
+
+pub struct Beta {
+    pub alpha: f64,
+    pub beta: f64,
+}
+
+impl Beta {
+    pub fn new(alpha: f64, beta: f64) -> Result<Self, RuntimeError> {
+        check_param(alpha > 0.0 && alpha.is_finite(), "beta", "alpha", "positive", alpha)?;
+        check_param(beta > 0.0 && beta.is_finite(), "beta", "beta", "positive", beta)?;
+        Ok(Beta { alpha, beta })
+    }
+}
+
+impl Distribution for Beta {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::Beta;
+        let distr = match Beta::new(self.alpha, self.beta) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `beta` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        Ok(Value::Float(rng.sample(distr)))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let x = outcome(val, self.name())?;
+        if !(0.0..=1.0).contains(&x) {
+            return Ok(f64::NEG_INFINITY);
+        }
+        Ok(
+            x_ln_y(self.alpha - 1.0, x) + x_ln_y(self.beta - 1.0, 1.0 - x)
+                - ln_beta(self.alpha, self.beta),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "beta"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("alpha", Value::Float(self.alpha)),
+            ("beta", Value::Float(self.beta)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::Real {
+            low: 0.0,
+            high: 1.0,
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.alpha / (self.alpha + self.beta)))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let total = self.alpha + self.beta;
+        Ok(Value::Float(
+            self.alpha * self.beta / (total * total * (total + 1.0)),
+        ))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let x = cdf_point(x, self.name())?.clamp(0.0, 1.0);
+        let ln_beta = ln_beta(self.alpha, self.beta);
+        Ok(special::Beta::inc_beta(x, self.alpha, self.beta, ln_beta))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        let ln_beta = ln_beta(self.alpha, self.beta);
+        Ok(Value::Float(special::Beta::inv_inc_beta(
+            p, self.alpha, self.beta, ln_beta,
+        )))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        let (a, b) = (self.alpha, self.beta);
+        Ok(
+            ln_beta(a, b) - (a - 1.0) * digamma(a) - (b - 1.0) * digamma(b)
+                + (a + b - 2.0) * digamma(a + b),
+        )
+    }
+}
+
+impl std::fmt::Debug for Beta {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+pub struct UniformContinuous {
+    pub low: f64,
+    pub high: f64,
+}
+
+impl UniformContinuous {
+    pub fn new(low: f64, high: f64) -> Result<Self, RuntimeError> {
+        check_param(
+            low.is_finite(),
+            "uniform-continuous",
+            "the lower bound",
+            "finite",
+            low,
+        )?;
+        check_param(
+            high.is_finite() && high > low,
+            "uniform-continuous",
+            "the upper bound",
+            "finite and greater than the lower bound",
+            high,
+        )?;
+        Ok(UniformContinuous { low, high })
+    }
+}
+
+impl Distribution for UniformContinuous {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        let mut rng = crate::random::rng();
+        Ok(Value::Float(rng.gen_range(self.low..self.high)))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let x = outcome(val, self.name())?;
+        if x < self.low || x > self.high {
+            return Ok(f64::NEG_INFINITY);
+        }
+        Ok(-(self.high - self.low).ln())
+    }
+
+    fn name(&self) -> &'static str {
+        "uniform-continuous"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("low", Value::Float(self.low)),
+            ("high", Value::Float(self.high)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::Real {
+            low: self.low,
+            high: self.high,
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float((self.low + self.high) / 2.0))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float((self.high - self.low).powi(2) / 12.0))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let x = cdf_point(x, self.name())?;
+        Ok(((x - self.low) / (self.high - self.low)).clamp(0.0, 1.0))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.low + p * (self.high - self.low)))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok((self.high - self.low).ln())
+    }
+}
+
+impl std::fmt::Debug for UniformContinuous {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+pub struct Exponential {
+    pub rate: f64,
+}
+
+impl Exponential {
+    pub fn new(rate: f64) -> Result<Self, RuntimeError> {
+        check_param(
+            rate > 0.0 && rate.is_finite(),
+            "exponential",
+            "the rate",
+            "positive",
+            rate,
+        )?;
+        Ok(Exponential { rate })
+    }
+}
+
+impl Distribution for Exponential {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::Exp;
+        let distr = match Exp::new(self.rate) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `exponential` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        Ok(Value::Float(rng.sample(distr)))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let x = outcome(val, self.name())?;
+        if x < 0.0 {
+            return Ok(f64::NEG_INFINITY);
+        }
+        Ok(self.rate.ln() - self.rate * x)
+    }
+
+    fn name(&self) -> &'static str {
+        "exponential"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![("rate", Value::Float(self.rate))]
+    }
+
+    fn support(&self) -> Support {
+        Support::Real {
+            low: 0.0,
+            high: f64::INFINITY,
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(1.0 / self.rate))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(1.0 / (self.rate * self.rate)))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let x = cdf_point(x, self.name())?;
+        Ok(if x <= 0.0 {
+            0.0
+        } else {
+            -(-self.rate * x).exp_m1()
+        })
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(-(-p).ln_1p() / self.rate))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(1.0 - self.rate.ln())
+    }
+}
+
+impl std::fmt::Debug for Exponential {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// Student's t distribution with `nu` degrees of freedom, shifted by `loc` and scaled by `scale`.
+pub struct StudentT {
+    pub nu: f64,
+    pub loc: f64,
+    pub scale: f64,
+}
+
+impl StudentT {
+    pub fn new(nu: f64, loc: f64, scale: f64) -> Result<Self, RuntimeError> {
+        check_param(
+            nu > 0.0 && nu.is_finite(),
+            "student-t",
+            "the degrees of freedom",
+            "positive",
+            nu,
+        )?;
+        check_param(loc.is_finite(), "student-t", "the location", "finite", loc)?;
+        check_param(
+            scale > 0.0 && scale.is_finite(),
+            "student-t",
+            "the scale",
+            "positive",
+            scale,
+        )?;
+        Ok(StudentT { nu, loc, scale })
+    }
+
+    /// The cdf of the unshifted, unscaled distribution, in terms of the regularised incomplete beta function. Near
+    /// the centre this uses the complementary form, which stays accurate there.
+    fn standard_cdf(&self, z: f64) -> f64 {
+        use special::Beta;
+        let nu = self.nu;
+        let (a, b) = (nu / 2.0, 0.5);
+        if z * z < nu {
+            let centre = 0.5 * (z * z / (nu + z * z)).inc_beta(b, a, ln_beta(b, a));
+            0.5 + z.signum() * centre
+        } else {
+            let tail = 0.5 * (nu / (nu + z * z)).inc_beta(a, b, ln_beta(a, b));
+            if z > 0.0 {
+                1.0 - tail
+            } else {
+                tail
+            }
+        }
+    }
+}
+
+impl Distribution for StudentT {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::StudentT;
+        let distr = match StudentT::new(self.nu) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `student-t` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        Ok(Value::Float(self.loc + self.scale * rng.sample(distr)))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let z = (outcome(val, self.name())? - self.loc) / self.scale;
+        let nu = self.nu;
+        Ok(ln_gamma((nu + 1.0) / 2.0)
+            - ln_gamma(nu / 2.0)
+            - 0.5 * (nu * PI).ln()
+            - self.scale.ln()
+            - (nu + 1.0) / 2.0 * (z * z / nu).ln_1p())
+    }
+
+    fn name(&self) -> &'static str {
+        "student-t"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("nu", Value::Float(self.nu)),
+            ("loc", Value::Float(self.loc)),
+            ("scale", Value::Float(self.scale)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::Real {
+            low: f64::NEG_INFINITY,
+            high: f64::INFINITY,
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        if self.nu <= 1.0 {
+            return undefined("mean", self.name(), " when nu is at most 1");
+        }
+        Ok(Value::Float(self.loc))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        if self.nu <= 1.0 {
+            return undefined("variance", self.name(), " when nu is at most 1");
+        }
+        let scale_squared = self.scale * self.scale;
+        Ok(Value::Float(if self.nu <= 2.0 {
+            f64::INFINITY
+        } else {
+            scale_squared * self.nu / (self.nu - 2.0)
+        }))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let z = (cdf_point(x, self.name())? - self.loc) / self.scale;
+        Ok(self.standard_cdf(z))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        let z = if p <= 0.0 {
+            f64::NEG_INFINITY
+        } else if p >= 1.0 {
+            f64::INFINITY
+        } else {
+            invert_cdf(|z| self.standard_cdf(z), p, -1.0, 1.0)
+        };
+        Ok(Value::Float(self.loc + self.scale * z))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        let nu = self.nu;
+        Ok(
+            (nu + 1.0) / 2.0 * (digamma((nu + 1.0) / 2.0) - digamma(nu / 2.0))
+                + 0.5 * nu.ln()
+                + ln_beta(nu / 2.0, 0.5)
+                + self.scale.ln(),
+        )
+    }
+}
+
+impl std::fmt::Debug for StudentT {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+pub struct Laplace {
+    pub loc: f64,
+    pub scale: f64,
+}
+
+impl Laplace {
+    pub fn new(loc: f64, scale: f64) -> Result<Self, RuntimeError> {
+        check_param(loc.is_finite(), "laplace", "the location", "finite", loc)?;
+        check_param(
+            scale > 0.0 && scale.is_finite(),
+            "laplace",
+            "the scale",
+            "positive",
+            scale,
+        )?;
+        Ok(Laplace { loc, scale })
+    }
+}
+
+impl Distribution for Laplace {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        // Inverse CDF of a uniform draw on (-1/2, 1/2).
+        let mut rng = crate::random::rng();
+        let u: f64 = rng.gen::<f64>() - 0.5;
+        let x = self.loc - self.scale * u.signum() * (1.0 - 2.0 * u.abs()).ln();
+        Ok(Value::Float(x))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let x = outcome(val, self.name())?;
+        Ok(-(2.0 * self.scale).ln() - (x - self.loc).abs() / self.scale)
+    }
+
+    fn name(&self) -> &'static str {
+        "laplace"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("loc", Value::Float(self.loc)),
+            ("scale", Value::Float(self.scale)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::Real {
+            low: f64::NEG_INFINITY,
+            high: f64::INFINITY,
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.loc))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(2.0 * self.scale * self.scale))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let z = (cdf_point(x, self.name())? - self.loc) / self.scale;
+        Ok(if z < 0.0 {
+            0.5 * z.exp()
+        } else {
+            1.0 - 0.5 * (-z).exp()
+        })
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        let u = p - 0.5;
+        Ok(Value::Float(
+            self.loc - self.scale * u.signum() * (1.0 - 2.0 * u.abs()).ln(),
+        ))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(1.0 + (2.0 * self.scale).ln())
+    }
+}
+
+impl std::fmt::Debug for Laplace {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+pub struct Cauchy {
+    pub loc: f64,
+    pub scale: f64,
+}
+
+impl Cauchy {
+    pub fn new(loc: f64, scale: f64) -> Result<Self, RuntimeError> {
+        check_param(loc.is_finite(), "cauchy", "the location", "finite", loc)?;
+        check_param(
+            scale > 0.0 && scale.is_finite(),
+            "cauchy",
+            "the scale",
+            "positive",
+            scale,
+        )?;
+        Ok(Cauchy { loc, scale })
+    }
+}
+
+impl Distribution for Cauchy {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::Cauchy;
+        let distr = match Cauchy::new(self.loc, self.scale) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `cauchy` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        Ok(Value::Float(rng.sample(distr)))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let z = (outcome(val, self.name())? - self.loc) / self.scale;
+        Ok(-(PI * self.scale).ln() - (z * z).ln_1p())
+    }
+
+    fn name(&self) -> &'static str {
+        "cauchy"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("loc", Value::Float(self.loc)),
+            ("scale", Value::Float(self.scale)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::Real {
+            low: f64::NEG_INFINITY,
+            high: f64::INFINITY,
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        undefined("mean", self.name(), "")
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        undefined("variance", self.name(), "")
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let z = (cdf_point(x, self.name())? - self.loc) / self.scale;
+        Ok(0.5 + z.atan() / PI)
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(if p <= 0.0 {
+            f64::NEG_INFINITY
+        } else if p >= 1.0 {
+            f64::INFINITY
+        } else {
+            self.loc + self.scale * (PI * (p - 0.5)).tan()
+        }))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok((4.0 * PI * self.scale).ln())
+    }
+}
+
+impl std::fmt::Debug for Cauchy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// The distribution of `exp(x)` where `x` is normal with mean `mu` and standard deviation `sigma`.
+pub struct LogNormal {
+    pub mu: f64,
+    pub sigma: f64,
+}
+
+impl LogNormal {
+    pub fn new(mu: f64, sigma: f64) -> Result<Self, RuntimeError> {
+        check_param(mu.is_finite(), "log-normal", "mu", "finite", mu)?;
+        check_param(
+            sigma > 0.0 && sigma.is_finite(),
+            "log-normal",
+            "sigma",
+            "positive",
+            sigma,
+        )?;
+        Ok(LogNormal { mu, sigma })
+    }
+}
+
+impl Distribution for LogNormal {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::LogNormal;
+        let distr = match LogNormal::new(self.mu, self.sigma) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `log-normal` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        Ok(Value::Float(rng.sample(distr)))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let x = outcome(val, self.name())?;
+        if x <= 0.0 {
+            return Ok(f64::NEG_INFINITY);
+        }
+        let z = (x.ln() - self.mu) / self.sigma;
+        Ok(-(x * self.sigma * TAU.sqrt()).ln() - z * z / 2.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "log-normal"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("mu", Value::Float(self.mu)),
+            ("sigma", Value::Float(self.sigma)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::Real {
+            low: 0.0,
+            high: f64::INFINITY,
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(
+            (self.mu + self.sigma * self.sigma / 2.0).exp(),
+        ))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let sigma_squared = self.sigma * self.sigma;
+        Ok(Value::Float(
+            sigma_squared.exp_m1() * (2.0 * self.mu + sigma_squared).exp(),
+        ))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let x = cdf_point(x, self.name())?;
+        if x <= 0.0 {
+            return Ok(0.0);
+        }
+        Ok(std_normal_cdf((x.ln() - self.mu) / self.sigma))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(
+            (self.mu + self.sigma * std_normal_quantile(p)).exp(),
+        ))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(self.mu + 0.5 * (TAU * E * self.sigma * self.sigma).ln())
+    }
+}
+
+impl std::fmt::Debug for LogNormal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// The absolute value of a normal with mean 0 and standard deviation `sigma`.
+pub struct HalfNormal {
+    pub sigma: f64,
+}
+
+impl HalfNormal {
+    pub fn new(sigma: f64) -> Result<Self, RuntimeError> {
+        check_param(
+            sigma > 0.0 && sigma.is_finite(),
+            "half-normal",
+            "sigma",
+            "positive",
+            sigma,
+        )?;
+        Ok(HalfNormal { sigma })
+    }
+}
+
+impl Distribution for HalfNormal {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::StandardNormal;
+        let mut rng = crate::random::rng();
+        let z: f64 = rng.sample(StandardNormal);
+        Ok(Value::Float((self.sigma * z).abs()))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let x = outcome(val, self.name())?;
+        if x < 0.0 {
+            return Ok(f64::NEG_INFINITY);
+        }
+        let z = x / self.sigma;
+        Ok(LN_2 - (self.sigma * TAU.sqrt()).ln() - z * z / 2.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "half-normal"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![("sigma", Value::Float(self.sigma))]
+    }
+
+    fn support(&self) -> Support {
+        Support::Real {
+            low: 0.0,
+            high: f64::INFINITY,
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.sigma * (2.0 / PI).sqrt()))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.sigma * self.sigma * (1.0 - 2.0 / PI)))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let x = cdf_point(x, self.name())?;
+        if x <= 0.0 {
+            return Ok(0.0);
+        }
+        Ok(special::Error::error(x / (self.sigma * SQRT_2)))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(
+            self.sigma * std_normal_quantile((1.0 + p) / 2.0),
+        ))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(0.5 * (PI * self.sigma * self.sigma / 2.0).ln() + 0.5)
+    }
+}
+
+impl std::fmt::Debug for HalfNormal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
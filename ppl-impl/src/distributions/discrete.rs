@@ -0,0 +1,669 @@
+use super::{
+    cdf_point, check_param, float_vector, fmt_distribution, integer_cdf, integer_entropy,
+    integer_quantile, ln_beta, ln_gamma, unbounded_quantile, x_ln_y, Distribution, Support,
+};
+use crate::types::{RuntimeError, Value};
+
+fn outcome(val: &Value, name: &str) -> Result<i64, RuntimeError> {
+    val.try_get_integer(&format!(
+        "`{}` can only evaluate the density of an integer.",
+        name
+    ))
+}
+
+fn ln_factorial(k: i64) -> f64 {
+    ln_gamma(k as f64 + 1.0)
+}
+
+/// Whether two values are the same outcome, for distributions over arbitrary values.
+pub(super) fn same_outcome(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Integer(a), Value::Integer(b)) => a == b,
+        (Value::Float(_), _) | (_, Value::Float(_)) => {
+            match (a.try_get_numeric(""), b.try_get_numeric("")) {
+                (Ok(a), Ok(b)) => a == b,
+                _ => false,
+            }
+        }
+        (Value::Boolean(a), Value::Boolean(b)) => a == b,
+        (Value::Vector(a), Value::Vector(b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same_outcome(a, b))
+        }
+        (Value::Matrix(a), Value::Matrix(b)) => a == b,
+        (Value::Matrix(m), v @ Value::Vector(_)) | (v @ Value::Vector(_), Value::Matrix(m)) => {
+            same_outcome(&Value::Vector(m.rows_as_values()), v)
+        }
+        (Value::Null, Value::Null) => true,
+        _ => false,
+    }
+}
+
+pub struct Poisson {
+    pub rate: f64,
+}
+
+impl Poisson {
+    pub fn new(rate: f64) -> Result<Self, RuntimeError> {
+        check_param(
+            rate > 0.0 && rate.is_finite(),
+            "poisson",
+            "the rate",
+            "positive",
+            rate,
+        )?;
+        Ok(Poisson { rate })
+    }
+
+    fn log_pmf(&self, k: i64) -> f64 {
+        if k < 0 {
+            return f64::NEG_INFINITY;
+        }
+        k as f64 * self.rate.ln() - self.rate - ln_factorial(k)
+    }
+
+    fn integer_cdf(&self, k: i64) -> f64 {
+        1.0 - special::Gamma::inc_gamma(self.rate, k as f64 + 1.0)
+    }
+}
+
+impl Distribution for Poisson {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::Poisson;
+        let distr = match Poisson::new(self.rate) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `poisson` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        let val: f64 = rng.sample(distr);
+        Ok(Value::Integer(val as i64))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        Ok(self.log_pmf(outcome(val, self.name())?))
+    }
+
+    fn name(&self) -> &'static str {
+        "poisson"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![("rate", Value::Float(self.rate))]
+    }
+
+    fn support(&self) -> Support {
+        Support::Integer { low: 0, high: None }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.rate))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.rate))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        Ok(integer_cdf(cdf_point(x, self.name())?, 0, None, |k| {
+            self.integer_cdf(k)
+        }))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        unbounded_quantile(p, self.name())?;
+        Ok(Value::Integer(integer_quantile(
+            |k| self.integer_cdf(k),
+            p,
+            0,
+            None,
+        )))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(integer_entropy(|k| self.log_pmf(k), 0, None))
+    }
+}
+
+impl std::fmt::Debug for Poisson {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// Uniform over the integers from `low` up to but not including `high`.
+pub struct UniformDiscrete {
+    pub low: i64,
+    pub high: i64,
+}
+
+impl UniformDiscrete {
+    pub fn new(low: i64, high: i64) -> Result<Self, RuntimeError> {
+        if high <= low {
+            return err!(
+                "`uniform-discrete` needs the upper bound to be greater than the lower bound, but was given {} and {}.",
+                low,
+                high
+            );
+        }
+        if high.checked_sub(low).is_none() {
+            return err!(
+                "`uniform-discrete` needs the bounds to be at most {} apart, but was given {} and {}.",
+                i64::MAX,
+                low,
+                high
+            );
+        }
+        Ok(UniformDiscrete { low, high })
+    }
+}
+
+impl Distribution for UniformDiscrete {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        let mut rng = crate::random::rng();
+        Ok(Value::Integer(rng.gen_range(self.low..self.high)))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let k = outcome(val, self.name())?;
+        if k < self.low || k >= self.high {
+            return Ok(f64::NEG_INFINITY);
+        }
+        Ok(-((self.high - self.low) as f64).ln())
+    }
+
+    fn name(&self) -> &'static str {
+        "uniform-discrete"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("low", Value::Integer(self.low)),
+            ("high", Value::Integer(self.high)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::Integer {
+            low: self.low,
+            high: Some(self.high - 1),
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float((self.low as f64 + (self.high - 1) as f64) / 2.0))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let n = (self.high - self.low) as f64;
+        Ok(Value::Float((n * n - 1.0) / 12.0))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let n = (self.high - self.low) as f64;
+        let x = cdf_point(x, self.name())?;
+        Ok(integer_cdf(x, self.low, Some(self.high - 1), |k| {
+            (k as i128 - self.low as i128 + 1) as f64 / n
+        }))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        let n = (self.high - self.low) as f64;
+        let cdf = |k: i64| (k as i128 - self.low as i128 + 1) as f64 / n;
+        Ok(Value::Integer(integer_quantile(
+            cdf,
+            p,
+            self.low,
+            Some(self.high - 1),
+        )))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(((self.high - self.low) as f64).ln())
+    }
+}
+
+impl std::fmt::Debug for UniformDiscrete {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// The number of successes in `n` trials which each succeed with probability `p`.
+pub struct Binomial {
+    pub n: u64,
+    pub p: f64,
+}
+
+impl Binomial {
+    pub fn new(n: u64, p: f64) -> Result<Self, RuntimeError> {
+        check_param(
+            (0.0..=1.0).contains(&p),
+            "binomial",
+            "p",
+            "between 0 and 1",
+            p,
+        )?;
+        Ok(Binomial { n, p })
+    }
+
+    fn log_pmf(&self, k: i64) -> f64 {
+        let n = self.n as i64;
+        if k < 0 || k > n {
+            return f64::NEG_INFINITY;
+        }
+        ln_factorial(n) - ln_factorial(k) - ln_factorial(n - k)
+            + x_ln_y(k as f64, self.p)
+            + x_ln_y((n - k) as f64, 1.0 - self.p)
+    }
+
+    /// Only valid for `k` below `n`.
+    fn integer_cdf(&self, k: i64) -> f64 {
+        let (a, b) = ((self.n as i64 - k) as f64, k as f64 + 1.0);
+        special::Beta::inc_beta(1.0 - self.p, a, b, ln_beta(a, b))
+    }
+}
+
+impl Distribution for Binomial {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::Binomial;
+        let distr = match Binomial::new(self.n, self.p) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `binomial` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        Ok(Value::Integer(rng.sample(distr) as i64))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        Ok(self.log_pmf(outcome(val, self.name())?))
+    }
+
+    fn name(&self) -> &'static str {
+        "binomial"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("n", Value::Integer(self.n as i64)),
+            ("p", Value::Float(self.p)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::Integer {
+            low: 0,
+            high: Some(self.n as i64),
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.n as f64 * self.p))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.n as f64 * self.p * (1.0 - self.p)))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let x = cdf_point(x, self.name())?;
+        Ok(integer_cdf(x, 0, Some(self.n as i64), |k| {
+            self.integer_cdf(k)
+        }))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        let n = self.n as i64;
+        let cdf = |k: i64| if k >= n { 1.0 } else { self.integer_cdf(k) };
+        Ok(Value::Integer(integer_quantile(cdf, p, 0, Some(n))))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(integer_entropy(|k| self.log_pmf(k), 0, Some(self.n as i64)))
+    }
+}
+
+impl std::fmt::Debug for Binomial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// The number of failures before the first success, where each trial succeeds with probability `p`.
+pub struct Geometric {
+    pub p: f64,
+}
+
+impl Geometric {
+    pub fn new(p: f64) -> Result<Self, RuntimeError> {
+        check_param(
+            p > 0.0 && p <= 1.0,
+            "geometric",
+            "p",
+            "greater than 0 and at most 1",
+            p,
+        )?;
+        Ok(Geometric { p })
+    }
+}
+
+impl Distribution for Geometric {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::Geometric;
+        let distr = match Geometric::new(self.p) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `geometric` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        Ok(Value::Integer(rng.sample(distr) as i64))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let k = outcome(val, self.name())?;
+        if k < 0 {
+            return Ok(f64::NEG_INFINITY);
+        }
+        Ok(x_ln_y(k as f64, 1.0 - self.p) + self.p.ln())
+    }
+
+    fn name(&self) -> &'static str {
+        "geometric"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![("p", Value::Float(self.p))]
+    }
+
+    fn support(&self) -> Support {
+        Support::Integer { low: 0, high: None }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float((1.0 - self.p) / self.p))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float((1.0 - self.p) / (self.p * self.p)))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let x = cdf_point(x, self.name())?;
+        Ok(integer_cdf(x, 0, None, |k| {
+            1.0 - (1.0 - self.p).powf(k as f64 + 1.0)
+        }))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        unbounded_quantile(p, self.name())?;
+        let cdf = |k: i64| 1.0 - (1.0 - self.p).powf(k as f64 + 1.0);
+        Ok(Value::Integer(integer_quantile(cdf, p, 0, None)))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        let p = self.p;
+        Ok((-x_ln_y(1.0 - p, 1.0 - p) - x_ln_y(p, p)) / p)
+    }
+}
+
+impl std::fmt::Debug for Geometric {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// The number of failures before `r` successes, where each trial succeeds with probability `p`. `r` need not be
+/// an integer.
+pub struct NegativeBinomial {
+    pub r: f64,
+    pub p: f64,
+}
+
+impl NegativeBinomial {
+    pub fn new(r: f64, p: f64) -> Result<Self, RuntimeError> {
+        check_param(
+            r > 0.0 && r.is_finite(),
+            "negative-binomial",
+            "r",
+            "positive",
+            r,
+        )?;
+        check_param(
+            p > 0.0 && p <= 1.0,
+            "negative-binomial",
+            "p",
+            "greater than 0 and at most 1",
+            p,
+        )?;
+        Ok(NegativeBinomial { r, p })
+    }
+
+    fn log_pmf(&self, k: i64) -> f64 {
+        if k < 0 {
+            return f64::NEG_INFINITY;
+        }
+        ln_gamma(k as f64 + self.r) - ln_factorial(k) - ln_gamma(self.r)
+            + self.r * self.p.ln()
+            + x_ln_y(k as f64, 1.0 - self.p)
+    }
+
+    fn integer_cdf(&self, k: i64) -> f64 {
+        let b = k as f64 + 1.0;
+        special::Beta::inc_beta(self.p, self.r, b, ln_beta(self.r, b))
+    }
+}
+
+impl Distribution for NegativeBinomial {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::{Gamma, Poisson};
+        if self.p == 1.0 {
+            return Ok(Value::Integer(0));
+        }
+        // A Poisson whose rate is drawn from a gamma distribution.
+        let gamma = match Gamma::new(self.r, (1.0 - self.p) / self.p) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `negative-binomial` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        let rate: f64 = rng.sample(gamma);
+        if rate <= 0.0 {
+            return Ok(Value::Integer(0));
+        }
+        let poisson = match Poisson::new(rate) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `negative-binomial` distribution."),
+        };
+        let val: f64 = rng.sample(poisson);
+        Ok(Value::Integer(val as i64))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        Ok(self.log_pmf(outcome(val, self.name())?))
+    }
+
+    fn name(&self) -> &'static str {
+        "negative-binomial"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![("r", Value::Float(self.r)), ("p", Value::Float(self.p))]
+    }
+
+    fn support(&self) -> Support {
+        Support::Integer { low: 0, high: None }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.r * (1.0 - self.p) / self.p))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Float(self.r * (1.0 - self.p) / (self.p * self.p)))
+    }
+
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        Ok(integer_cdf(cdf_point(x, self.name())?, 0, None, |k| {
+            self.integer_cdf(k)
+        }))
+    }
+
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        unbounded_quantile(p, self.name())?;
+        Ok(Value::Integer(integer_quantile(
+            |k| self.integer_cdf(k),
+            p,
+            0,
+            None,
+        )))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(integer_entropy(|k| self.log_pmf(k), 0, None))
+    }
+}
+
+impl std::fmt::Debug for NegativeBinomial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// A distribution over arbitrary values, each with its own probability. The probabilities are normalised when the
+/// distribution is created.
+pub struct Categorical {
+    pub values: Vec<Value>,
+    pub probs: Vec<f64>,
+}
+
+impl Categorical {
+    pub fn new(values: Vec<Value>, weights: Vec<f64>) -> Result<Self, RuntimeError> {
+        if values.is_empty() {
+            return err!("`categorical` needs at least one value.");
+        }
+        if values.len() != weights.len() {
+            return err!(
+                "`categorical` needs one probability per value, but was given {} values and {} probabilities.",
+                values.len(),
+                weights.len()
+            );
+        }
+        for w in &weights {
+            check_param(
+                *w >= 0.0 && w.is_finite(),
+                "categorical",
+                "each probability",
+                "non-negative",
+                *w,
+            )?;
+        }
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return err!("`categorical` needs at least one non-zero probability.");
+        }
+        let probs = weights.into_iter().map(|w| w / total).collect();
+        Ok(Categorical { values, probs })
+    }
+
+    /// Each distinct value with its total probability, since the same value may be listed more than once.
+    fn outcomes(&self) -> Vec<(&Value, f64)> {
+        let mut outcomes: Vec<(&Value, f64)> = Vec::new();
+        for (v, p) in self.values.iter().zip(&self.probs) {
+            match outcomes.iter_mut().find(|(seen, _)| same_outcome(seen, v)) {
+                Some((_, mass)) => *mass += p,
+                None => outcomes.push((v, *p)),
+            }
+        }
+        outcomes
+    }
+
+    fn numeric_values(&self, what: &str) -> Result<Vec<f64>, RuntimeError> {
+        self.values
+            .iter()
+            .map(|v| {
+                v.try_get_numeric(&format!(
+                    "The {} of `categorical` is only available when its values are numbers.",
+                    what
+                ))
+            })
+            .collect()
+    }
+}
+
+impl Distribution for Categorical {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::WeightedIndex;
+        let distr = match WeightedIndex::new(&self.probs) {
+            Ok(w) => w,
+            Err(_) => return err!("Error creating `categorical` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        Ok(self.values[rng.sample::<usize, _>(distr)].clone())
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        // The same value may be listed more than once.
+        let mass: f64 = self
+            .values
+            .iter()
+            .zip(&self.probs)
+            .filter(|(v, _)| same_outcome(v, val))
+            .map(|(_, p)| p)
+            .sum();
+        Ok(mass.ln())
+    }
+
+    fn name(&self) -> &'static str {
+        "categorical"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("values", Value::Vector(self.values.clone())),
+            ("probs", float_vector(&self.probs)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::Values(
+            self.outcomes()
+                .into_iter()
+                .map(|(v, _)| v.clone())
+                .collect(),
+        )
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        let xs = self.numeric_values("mean")?;
+        Ok(Value::Float(
+            xs.iter().zip(&self.probs).map(|(x, p)| x * p).sum(),
+        ))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let xs = self.numeric_values("variance")?;
+        let mean: f64 = xs.iter().zip(&self.probs).map(|(x, p)| x * p).sum();
+        Ok(Value::Float(
+            xs.iter()
+                .zip(&self.probs)
+                .map(|(x, p)| (x - mean).powi(2) * p)
+                .sum(),
+        ))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(-self
+            .outcomes()
+            .iter()
+            .map(|(_, p)| x_ln_y(*p, *p))
+            .sum::<f64>())
+    }
+}
+
+impl std::fmt::Debug for Categorical {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
@@ -0,0 +1,229 @@
+use std::{
+    f64::consts::{PI, SQRT_2},
+    fmt,
+};
+
+use super::{
+    cdf_point, discrete::same_outcome, float_vector, fmt_distribution, unavailable, x_ln_y,
+    Distribution, Support,
+};
+use crate::types::{RuntimeError, Value};
+
+/// Weighted samples, such as the results of inference. Samples of floats are smoothed with a Gaussian kernel to
+/// give a density; anything else has the mass of the samples equal to it.
+pub struct Empirical {
+    pub values: Vec<Value>,
+    /// Normalised to sum to 1.
+    pub weights: Vec<f64>,
+    // The kernel's standard deviation, when the samples are floats.
+    bandwidth: Option<f64>,
+}
+
+impl Empirical {
+    /// `log_weights` may be minus infinity, but not all of them.
+    pub fn new(values: Vec<Value>, log_weights: Vec<f64>) -> Result<Self, RuntimeError> {
+        if values.is_empty() {
+            return err!("`empirical` needs at least one sample.");
+        }
+        if values.len() != log_weights.len() {
+            return err!(
+                "`empirical` needs one weight per sample, but was given {} weights and {} samples.",
+                log_weights.len(),
+                values.len()
+            );
+        }
+        let max = log_weights
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        if max == f64::NEG_INFINITY || max.is_nan() {
+            return err!("`empirical` needs some sample to have a positive weight.");
+        }
+        let weights: Vec<f64> = log_weights.iter().map(|w| (w - max).exp()).collect();
+        let total: f64 = weights.iter().sum();
+        let weights: Vec<f64> = weights.iter().map(|w| w / total).collect();
+
+        let bandwidth = if values.iter().all(|v| matches!(v, Value::Float(_))) {
+            let xs: Vec<f64> = values
+                .iter()
+                .map(|v| v.try_get_numeric("").unwrap())
+                .collect();
+            silverman_bandwidth(&xs, &weights)
+        } else {
+            None
+        };
+        Ok(Empirical {
+            values,
+            weights,
+            bandwidth,
+        })
+    }
+
+    fn numbers(&self, what: &str) -> Result<Vec<f64>, RuntimeError> {
+        self.values
+            .iter()
+            .map(|v| {
+                v.try_get_numeric(&format!(
+                    "The {} of `empirical` is only available for samples of numbers.",
+                    what
+                ))
+            })
+            .collect()
+    }
+}
+
+/// Silverman's rule of thumb, using the effective sample size of the weights. `None` when the samples are all the
+/// same, so there's nothing to smooth.
+fn silverman_bandwidth(xs: &[f64], weights: &[f64]) -> Option<f64> {
+    let mean: f64 = xs.iter().zip(weights).map(|(x, w)| w * x).sum();
+    let variance: f64 = xs
+        .iter()
+        .zip(weights)
+        .map(|(x, w)| w * (x - mean).powi(2))
+        .sum();
+    let n_effective = 1.0 / weights.iter().map(|w| w * w).sum::<f64>();
+    let bandwidth = 1.06 * variance.sqrt() * n_effective.powf(-0.2);
+    Some(bandwidth).filter(|h| *h > 0.0 && h.is_finite())
+}
+
+impl Distribution for Empirical {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::WeightedIndex;
+        let distr = match WeightedIndex::new(&self.weights) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `empirical` distribution."),
+        };
+        let mut rng = crate::random::rng();
+        Ok(self.values[rng.sample(distr)].clone())
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let h = match self.bandwidth {
+            Some(h) => h,
+            None => {
+                let mass: f64 = self
+                    .values
+                    .iter()
+                    .zip(&self.weights)
+                    .filter(|(v, _)| same_outcome(v, val))
+                    .map(|(_, w)| w)
+                    .sum();
+                return Ok(mass.ln());
+            }
+        };
+
+        let x = val.try_get_numeric("`empirical` can only evaluate the density of a number.")?;
+        let density: f64 = self
+            .values
+            .iter()
+            .zip(&self.weights)
+            .map(|(v, w)| {
+                let z = (x - v.try_get_numeric("").unwrap()) / h;
+                w * (-0.5 * z * z).exp()
+            })
+            .sum();
+        Ok(density.ln() - (h * SQRT_2 * PI.sqrt()).ln())
+    }
+
+    fn name(&self) -> &'static str {
+        "empirical"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("values", Value::Vector(self.values.clone())),
+            ("weights", float_vector(&self.weights)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        match self.bandwidth {
+            Some(_) => Support::Real {
+                low: f64::NEG_INFINITY,
+                high: f64::INFINITY,
+            },
+            None => {
+                let mut values: Vec<Value> = Vec::new();
+                for (v, w) in self.values.iter().zip(&self.weights) {
+                    if *w > 0.0 && !values.iter().any(|u| same_outcome(u, v)) {
+                        values.push(v.clone());
+                    }
+                }
+                Support::Values(values)
+            }
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        let xs = self.numbers("mean")?;
+        Ok(Value::Float(
+            xs.iter().zip(&self.weights).map(|(x, w)| w * x).sum(),
+        ))
+    }
+
+    /// The variance of the samples, not counting the kernel.
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let xs = self.numbers("variance")?;
+        let mean: f64 = xs.iter().zip(&self.weights).map(|(x, w)| w * x).sum();
+        Ok(Value::Float(
+            xs.iter()
+                .zip(&self.weights)
+                .map(|(x, w)| w * (x - mean).powi(2))
+                .sum(),
+        ))
+    }
+
+    /// The weighted proportion of samples at or below `x`, not counting the kernel.
+    fn cdf(&self, x: &Value) -> Result<f64, RuntimeError> {
+        let x = cdf_point(x, self.name())?;
+        let xs = self.numbers("cdf")?;
+        Ok(xs
+            .iter()
+            .zip(&self.weights)
+            .filter(|(v, _)| **v <= x)
+            .map(|(_, w)| w)
+            .sum::<f64>()
+            .min(1.0))
+    }
+
+    /// The smallest sample whose cdf is at least `p`.
+    fn quantile(&self, p: f64) -> Result<Value, RuntimeError> {
+        let xs = self.numbers("quantile")?;
+        let mut order: Vec<usize> = (0..xs.len()).collect();
+        order.sort_by(|a, b| {
+            xs[*a]
+                .partial_cmp(&xs[*b])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        let mut cumulative = 0.0;
+        for i in &order {
+            cumulative += self.weights[*i];
+            if cumulative >= p - 1e-12 && self.weights[*i] > 0.0 {
+                return Ok(self.values[*i].clone());
+            }
+        }
+        Ok(self.values[*order.last().unwrap()].clone())
+    }
+
+    /// Only for samples which aren't smoothed, since the kernel density has no closed form entropy.
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        if self.bandwidth.is_some() {
+            return unavailable("entropy", self.name());
+        }
+        let mut entropy = 0.0;
+        if let Support::Values(values) = self.support() {
+            for v in &values {
+                let p = self.log_pdf(v)?.exp();
+                entropy -= x_ln_y(p, p);
+            }
+        }
+        Ok(entropy)
+    }
+}
+
+impl fmt::Debug for Empirical {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
@@ -0,0 +1,725 @@
+use std::{
+    f64::consts::{LN_2, PI, TAU},
+    rc::Rc,
+};
+
+use super::{
+    check_param, digamma, float_vector, fmt_distribution, ln_beta, ln_gamma, matrix_value,
+    undefined, x_ln_y, Distribution, Support,
+};
+use crate::{
+    matrix::Matrix,
+    types::{RuntimeError, Value, ValueImpls},
+};
+
+fn vector_outcome(val: &Value, name: &str) -> Result<Vec<f64>, RuntimeError> {
+    val.clone()
+        .try_into_vector(&format!(
+            "`{}` can only evaluate the density of a vector.",
+            name
+        ))?
+        .try_into_numeric(&format!(
+            "`{}` can only evaluate the density of a vector of numbers.",
+            name
+        ))
+}
+
+fn matrix_outcome(val: &Value, name: &str, dim: usize) -> Result<Rc<Matrix>, RuntimeError> {
+    let m = Matrix::try_from_value(val, &format!("The value given to `{}`", name))?;
+    if m.n_rows() != dim || m.n_cols() != dim {
+        return err!(
+            "`{}` can only evaluate the density of a [{} x {}] matrix, but was given a [{} x {}] matrix.",
+            name,
+            dim,
+            dim,
+            m.n_rows(),
+            m.n_cols()
+        );
+    }
+    Ok(m)
+}
+
+fn check_len(name: &str, expected: usize, found: usize) -> Result<(), RuntimeError> {
+    if expected != found {
+        return err!(
+            "`{}` can only evaluate the density of a vector of length {}, but was given one of length {}.",
+            name,
+            expected,
+            found
+        );
+    }
+    Ok(())
+}
+
+/// The Cholesky factor of a matrix parameter, which must be symmetric and positive definite.
+fn scale_cholesky(scale: &Matrix, dist: &str, param: &str) -> Result<Matrix, RuntimeError> {
+    match scale.cholesky() {
+        Some(l) => Ok(l),
+        None => err!(
+            "`{}` needs {} to be a symmetric positive definite matrix.",
+            dist,
+            param
+        ),
+    }
+}
+
+/// The log of the multivariate gamma function of dimension `p`.
+fn ln_multivariate_gamma(a: f64, p: usize) -> f64 {
+    let p_f = p as f64;
+    p_f * (p_f - 1.0) / 4.0 * PI.ln() + (0..p).map(|j| ln_gamma(a - j as f64 / 2.0)).sum::<f64>()
+}
+
+/// The expected log determinant of a Wishart matrix, given the log determinant of its scale.
+fn wishart_expected_ln_det(df: f64, p: usize, ln_det_scale: f64) -> f64 {
+    (0..p).map(|j| digamma((df - j as f64) / 2.0)).sum::<f64>() + p as f64 * LN_2 + ln_det_scale
+}
+
+fn wishart_entropy(df: f64, p: usize, ln_det_scale: f64) -> f64 {
+    let p_f = p as f64;
+    df / 2.0 * ln_det_scale + df * p_f / 2.0 * LN_2 + ln_multivariate_gamma(df / 2.0, p)
+        - (df - p_f - 1.0) / 2.0 * wishart_expected_ln_det(df, p, ln_det_scale)
+        + df * p_f / 2.0
+}
+
+fn standard_normals(n: usize) -> Vec<f64> {
+    use rand::prelude::*;
+    use rand_distr::StandardNormal;
+    let mut rng = crate::random::rng();
+    (0..n).map(|_| rng.sample(StandardNormal)).collect()
+}
+
+/// Multivariate normal with full covariance.
+pub struct MultivariateNormal {
+    pub mean: Vec<f64>,
+    pub covariance: Matrix,
+    /// The Cholesky factor of the covariance matrix.
+    pub cholesky: Matrix,
+}
+
+impl MultivariateNormal {
+    pub fn new(mean: Vec<f64>, covariance: Matrix) -> Result<Self, RuntimeError> {
+        if covariance.n_rows() != mean.len() || covariance.n_cols() != mean.len() {
+            return err!(
+                "`mvn` needs a [{} x {}] covariance matrix for a mean of length {}, but was given a [{} x {}] matrix.",
+                mean.len(),
+                mean.len(),
+                mean.len(),
+                covariance.n_rows(),
+                covariance.n_cols()
+            );
+        }
+        let cholesky = scale_cholesky(&covariance, "mvn", "the covariance")?;
+        Ok(MultivariateNormal {
+            mean,
+            covariance,
+            cholesky,
+        })
+    }
+}
+
+impl Distribution for MultivariateNormal {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        let z = standard_normals(self.mean.len());
+        let x = self.cholesky.mul_vec(&z);
+        Ok(Value::Vector(
+            x.iter()
+                .zip(&self.mean)
+                .map(|(x, mu)| Value::Float(x + mu))
+                .collect(),
+        ))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let x = vector_outcome(val, self.name())?;
+        check_len(self.name(), self.mean.len(), x.len())?;
+        let diff: Vec<f64> = x.iter().zip(&self.mean).map(|(x, mu)| x - mu).collect();
+        let z = self.cholesky.solve_lower(&diff);
+        let k = self.mean.len() as f64;
+        Ok(-0.5
+            * (k * TAU.ln()
+                + self.cholesky.cholesky_log_determinant()
+                + z.iter().map(|z| z * z).sum::<f64>()))
+    }
+
+    fn name(&self) -> &'static str {
+        "mvn"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("mean", float_vector(&self.mean)),
+            ("covariance", matrix_value(self.covariance.clone())),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::RealVector(self.mean.len())
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(float_vector(&self.mean))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        Ok(matrix_value(self.covariance.clone()))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        let k = self.mean.len() as f64;
+        Ok(0.5 * (k * (1.0 + TAU.ln()) + self.cholesky.cholesky_log_determinant()))
+    }
+}
+
+impl std::fmt::Debug for MultivariateNormal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// Multivariate normal with independent components, given by their standard deviations.
+pub struct DiagonalNormal {
+    pub mean: Vec<f64>,
+    pub sigmas: Vec<f64>,
+}
+
+impl DiagonalNormal {
+    pub fn new(mean: Vec<f64>, sigmas: Vec<f64>) -> Result<Self, RuntimeError> {
+        if mean.len() != sigmas.len() {
+            return err!(
+                "`mvn-diag` needs one standard deviation per mean, but was given {} means and {} standard deviations.",
+                mean.len(),
+                sigmas.len()
+            );
+        }
+        for sigma in &sigmas {
+            check_param(
+                *sigma > 0.0 && sigma.is_finite(),
+                "mvn-diag",
+                "each standard deviation",
+                "positive",
+                *sigma,
+            )?;
+        }
+        Ok(DiagonalNormal { mean, sigmas })
+    }
+}
+
+impl Distribution for DiagonalNormal {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        let z = standard_normals(self.mean.len());
+        Ok(Value::Vector(
+            z.iter()
+                .zip(self.mean.iter().zip(&self.sigmas))
+                .map(|(z, (mu, sigma))| Value::Float(mu + sigma * z))
+                .collect(),
+        ))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let x = vector_outcome(val, self.name())?;
+        check_len(self.name(), self.mean.len(), x.len())?;
+        Ok(x.iter()
+            .zip(self.mean.iter().zip(&self.sigmas))
+            .map(|(x, (mu, sigma))| {
+                let z = (x - mu) / sigma;
+                -(sigma * TAU.sqrt()).ln() - z * z / 2.0
+            })
+            .sum())
+    }
+
+    fn name(&self) -> &'static str {
+        "mvn-diag"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("mean", float_vector(&self.mean)),
+            ("sigmas", float_vector(&self.sigmas)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::RealVector(self.mean.len())
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(float_vector(&self.mean))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let k = self.sigmas.len();
+        Ok(matrix_value(Matrix::from_fn(k, k, |i, j| {
+            if i == j {
+                self.sigmas[i] * self.sigmas[i]
+            } else {
+                0.0
+            }
+        })))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        Ok(self
+            .sigmas
+            .iter()
+            .map(|sigma| 0.5 * (1.0 + TAU.ln()) + sigma.ln())
+            .sum())
+    }
+}
+
+impl std::fmt::Debug for DiagonalNormal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// Samples a Wishart matrix using the Bartlett decomposition, given the Cholesky factor of the scale matrix.
+fn sample_wishart(df: f64, scale_cholesky: &Matrix) -> Result<Matrix, RuntimeError> {
+    use rand::prelude::*;
+    use rand_distr::{ChiSquared, StandardNormal};
+    let p = scale_cholesky.n_rows();
+    let mut rng = crate::random::rng();
+    let mut a = vec![0f64; p * p];
+    for i in 0..p {
+        let chi_squared = match ChiSquared::new(df - i as f64) {
+            Ok(d) => d,
+            Err(_) => return err!("Error creating `wishart` distribution."),
+        };
+        a[i * p + i] = rng.sample(chi_squared).sqrt();
+        for j in 0..i {
+            a[i * p + j] = rng.sample(StandardNormal);
+        }
+    }
+    let la = scale_cholesky.mul(&Matrix::new(p, p, a)).unwrap();
+    Ok(la.mul(&la.transpose()).unwrap())
+}
+
+fn check_wishart_df(df: f64, dim: usize, name: &str) -> Result<(), RuntimeError> {
+    check_param(
+        df > dim as f64 - 1.0 && df.is_finite(),
+        name,
+        "the degrees of freedom",
+        &format!("greater than {}", dim as f64 - 1.0),
+        df,
+    )
+}
+
+/// Wishart distribution over positive definite matrices, with `df` degrees of freedom and the given scale matrix.
+pub struct Wishart {
+    pub df: f64,
+    pub scale: Matrix,
+    scale_cholesky: Matrix,
+}
+
+impl Wishart {
+    pub fn new(df: f64, scale: Matrix) -> Result<Self, RuntimeError> {
+        let scale_cholesky = scale_cholesky(&scale, "wishart", "the scale")?;
+        check_wishart_df(df, scale.n_rows(), "wishart")?;
+        Ok(Wishart {
+            df,
+            scale,
+            scale_cholesky,
+        })
+    }
+}
+
+impl Distribution for Wishart {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        Ok(Value::Matrix(Rc::new(sample_wishart(
+            self.df,
+            &self.scale_cholesky,
+        )?)))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let p = self.scale.n_rows();
+        let x = matrix_outcome(val, self.name(), p)?;
+        let x_cholesky = match x.cholesky() {
+            Some(l) => l,
+            None => return Ok(f64::NEG_INFINITY),
+        };
+        let scale_inverse = self.scale.inverse().unwrap();
+        let (df, p_f) = (self.df, p as f64);
+        Ok(
+            (df - p_f - 1.0) / 2.0 * x_cholesky.cholesky_log_determinant()
+                - scale_inverse.mul(&x).unwrap().trace() / 2.0
+                - df * p_f / 2.0 * LN_2
+                - df / 2.0 * self.scale_cholesky.cholesky_log_determinant()
+                - ln_multivariate_gamma(df / 2.0, p),
+        )
+    }
+
+    fn name(&self) -> &'static str {
+        "wishart"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("df", Value::Float(self.df)),
+            ("scale", matrix_value(self.scale.clone())),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::PositiveDefinite(self.scale.n_rows())
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(matrix_value(self.scale.map(|x| self.df * x)))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let v = &self.scale;
+        let p = v.n_rows();
+        Ok(matrix_value(Matrix::from_fn(p, p, |i, j| {
+            self.df * (v.get(i, j).powi(2) + v.get(i, i) * v.get(j, j))
+        })))
+    }
+
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        let ln_det_scale = self.scale_cholesky.cholesky_log_determinant();
+        Ok(wishart_entropy(self.df, self.scale.n_rows(), ln_det_scale))
+    }
+}
+
+impl std::fmt::Debug for Wishart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// The distribution of the inverse of a Wishart matrix, with `df` degrees of freedom and the given scale matrix.
+pub struct InverseWishart {
+    pub df: f64,
+    pub scale: Matrix,
+    scale_cholesky: Matrix,
+    inverse_scale_cholesky: Matrix,
+}
+
+impl InverseWishart {
+    pub fn new(df: f64, scale: Matrix) -> Result<Self, RuntimeError> {
+        let scale_cholesky = scale_cholesky(&scale, "inverse-wishart", "the scale")?;
+        check_wishart_df(df, scale.n_rows(), "inverse-wishart")?;
+        let inverse_scale_cholesky = match scale.inverse().and_then(|inv| inv.cholesky()) {
+            Some(l) => l,
+            None => {
+                return err!("`inverse-wishart` was given a scale matrix which can't be inverted.")
+            }
+        };
+        Ok(InverseWishart {
+            df,
+            scale,
+            scale_cholesky,
+            inverse_scale_cholesky,
+        })
+    }
+}
+
+impl Distribution for InverseWishart {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        let w = sample_wishart(self.df, &self.inverse_scale_cholesky)?;
+        match w.inverse() {
+            Some(x) => Ok(Value::Matrix(Rc::new(x))),
+            None => err!("`inverse-wishart` sampled a singular matrix."),
+        }
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let p = self.scale.n_rows();
+        let x = matrix_outcome(val, self.name(), p)?;
+        let (x_cholesky, x_inverse) = match (x.cholesky(), x.inverse()) {
+            (Some(l), Some(inv)) => (l, inv),
+            _ => return Ok(f64::NEG_INFINITY),
+        };
+        let (df, p_f) = (self.df, p as f64);
+        Ok(df / 2.0 * self.scale_cholesky.cholesky_log_determinant()
+            - df * p_f / 2.0 * LN_2
+            - ln_multivariate_gamma(df / 2.0, p)
+            - (df + p_f + 1.0) / 2.0 * x_cholesky.cholesky_log_determinant()
+            - self.scale.mul(&x_inverse).unwrap().trace() / 2.0)
+    }
+
+    fn name(&self) -> &'static str {
+        "inverse-wishart"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("df", Value::Float(self.df)),
+            ("scale", matrix_value(self.scale.clone())),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::PositiveDefinite(self.scale.n_rows())
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        let p = self.scale.n_rows() as f64;
+        if self.df <= p + 1.0 {
+            return undefined(
+                "mean",
+                self.name(),
+                " unless the degrees of freedom exceed the dimension plus 1",
+            );
+        }
+        Ok(matrix_value(self.scale.map(|x| x / (self.df - p - 1.0))))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let psi = &self.scale;
+        let p = psi.n_rows();
+        let (df, p_f) = (self.df, p as f64);
+        if df <= p_f + 3.0 {
+            return undefined(
+                "variance",
+                self.name(),
+                " unless the degrees of freedom exceed the dimension plus 3",
+            );
+        }
+        let denominator = (df - p_f) * (df - p_f - 1.0).powi(2) * (df - p_f - 3.0);
+        Ok(matrix_value(Matrix::from_fn(p, p, |i, j| {
+            ((df - p_f + 1.0) * psi.get(i, j).powi(2)
+                + (df - p_f - 1.0) * psi.get(i, i) * psi.get(j, j))
+                / denominator
+        })))
+    }
+
+    /// The entropy of the Wishart matrix being inverted, adjusted by the Jacobian of inversion.
+    fn entropy(&self) -> Result<f64, RuntimeError> {
+        let p = self.scale.n_rows();
+        let ln_det_inverse_scale = -self.scale_cholesky.cholesky_log_determinant();
+        Ok(wishart_entropy(self.df, p, ln_det_inverse_scale)
+            - (p as f64 + 1.0) * wishart_expected_ln_det(self.df, p, ln_det_inverse_scale))
+    }
+}
+
+impl std::fmt::Debug for InverseWishart {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// The LKJ distribution over `dim` by `dim` correlation matrices, with density proportional to `det(R)^(eta - 1)`.
+pub struct Lkj {
+    pub dim: usize,
+    pub eta: f64,
+}
+
+impl Lkj {
+    pub fn new(dim: usize, eta: f64) -> Result<Self, RuntimeError> {
+        if dim == 0 {
+            return err!("`lkj` needs a dimension of at least 1.");
+        }
+        check_param(eta > 0.0 && eta.is_finite(), "lkj", "eta", "positive", eta)?;
+        Ok(Lkj { dim, eta })
+    }
+
+    /// The log of the normalising constant, from Lewandowski, Kurowicka and Joe (2009).
+    fn log_normaliser(&self) -> f64 {
+        let d = self.dim as f64;
+        (1..self.dim)
+            .map(|k| {
+                let k = k as f64;
+                let beta = self.eta + (d - k - 1.0) / 2.0;
+                (2.0 * self.eta - 2.0 + d - k) * (d - k) * LN_2 + (d - k) * ln_beta(beta, beta)
+            })
+            .sum()
+    }
+}
+
+impl Distribution for Lkj {
+    /// Uses the vine method: partial correlations are drawn from scaled beta distributions, then converted to
+    /// correlations.
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::Beta;
+        let d = self.dim;
+        let mut rng = crate::random::rng();
+        let mut partial = vec![0f64; d * d];
+        let mut corr = Matrix::identity(d).data().to_vec();
+        let mut beta = self.eta + (d as f64 - 1.0) / 2.0;
+        for k in 0..d.saturating_sub(1) {
+            beta -= 0.5;
+            let distr = match Beta::new(beta, beta) {
+                Ok(d) => d,
+                Err(_) => return err!("Error creating `lkj` distribution."),
+            };
+            for i in k + 1..d {
+                partial[k * d + i] = 2.0 * rng.sample(distr) - 1.0;
+                let mut p = partial[k * d + i];
+                for l in (0..k).rev() {
+                    p = p
+                        * ((1.0 - partial[l * d + i].powi(2)) * (1.0 - partial[l * d + k].powi(2)))
+                            .sqrt()
+                        + partial[l * d + i] * partial[l * d + k];
+                }
+                corr[k * d + i] = p;
+                corr[i * d + k] = p;
+            }
+        }
+        Ok(Value::Matrix(Rc::new(Matrix::new(d, d, corr))))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let r = matrix_outcome(val, self.name(), self.dim)?;
+        let unit_diagonal = (0..self.dim).all(|i| (r.get(i, i) - 1.0).abs() < 1e-9);
+        let cholesky = match r.cholesky() {
+            Some(l) if unit_diagonal => l,
+            _ => return Ok(f64::NEG_INFINITY),
+        };
+        Ok((self.eta - 1.0) * cholesky.cholesky_log_determinant() - self.log_normaliser())
+    }
+
+    fn name(&self) -> &'static str {
+        "lkj"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("dim", Value::Integer(self.dim as i64)),
+            ("eta", Value::Float(self.eta)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::Correlation(self.dim)
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        Ok(matrix_value(Matrix::identity(self.dim)))
+    }
+
+    /// Each off-diagonal element is a beta variable scaled to (-1, 1).
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let beta = self.eta - 1.0 + self.dim as f64 / 2.0;
+        Ok(matrix_value(Matrix::from_fn(self.dim, self.dim, |i, j| {
+            if i == j {
+                0.0
+            } else {
+                1.0 / (2.0 * beta + 1.0)
+            }
+        })))
+    }
+}
+
+impl std::fmt::Debug for Lkj {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
+
+/// The counts of each outcome in `n` independent draws from a categorical distribution.
+pub struct Multinomial {
+    pub n: u64,
+    pub probs: Vec<f64>,
+}
+
+impl Multinomial {
+    pub fn new(n: u64, weights: Vec<f64>) -> Result<Self, RuntimeError> {
+        if weights.is_empty() {
+            return err!("`multinomial` needs at least one probability.");
+        }
+        for w in &weights {
+            check_param(
+                *w >= 0.0 && w.is_finite(),
+                "multinomial",
+                "each probability",
+                "non-negative",
+                *w,
+            )?;
+        }
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 {
+            return err!("`multinomial` needs at least one non-zero probability.");
+        }
+        let probs = weights.into_iter().map(|w| w / total).collect();
+        Ok(Multinomial { n, probs })
+    }
+}
+
+impl Distribution for Multinomial {
+    /// Draws each count from a binomial, conditioned on the counts before it.
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        use rand::prelude::*;
+        use rand_distr::Binomial;
+        let mut rng = crate::random::rng();
+        let mut remaining_n = self.n;
+        let mut remaining_p = 1.0;
+        let mut counts = Vec::with_capacity(self.probs.len());
+        for p in &self.probs {
+            let count = if remaining_n == 0 || remaining_p <= 0.0 {
+                0
+            } else {
+                let distr = match Binomial::new(remaining_n, (p / remaining_p).min(1.0)) {
+                    Ok(d) => d,
+                    Err(_) => return err!("Error creating `multinomial` distribution."),
+                };
+                rng.sample(distr)
+            };
+            remaining_n -= count;
+            remaining_p -= p;
+            counts.push(Value::Integer(count as i64));
+        }
+        Ok(Value::Vector(counts))
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let counts = val
+            .clone()
+            .try_into_vector("`multinomial` can only evaluate the density of a vector.")?
+            .into_iter()
+            .map(|v| {
+                v.try_into_integer(
+                    "`multinomial` can only evaluate the density of a vector of integers.",
+                )
+            })
+            .collect::<Result<Vec<i64>, RuntimeError>>()?;
+        check_len(self.name(), self.probs.len(), counts.len())?;
+        if counts.iter().any(|k| *k < 0) || counts.iter().sum::<i64>() != self.n as i64 {
+            return Ok(f64::NEG_INFINITY);
+        }
+        Ok(ln_gamma(self.n as f64 + 1.0)
+            + counts
+                .iter()
+                .zip(&self.probs)
+                .map(|(k, p)| x_ln_y(*k as f64, *p) - ln_gamma(*k as f64 + 1.0))
+                .sum::<f64>())
+    }
+
+    fn name(&self) -> &'static str {
+        "multinomial"
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        vec![
+            ("n", Value::Integer(self.n as i64)),
+            ("probs", float_vector(&self.probs)),
+        ]
+    }
+
+    fn support(&self) -> Support {
+        Support::Counts {
+            len: self.probs.len(),
+            total: self.n,
+        }
+    }
+
+    fn mean(&self) -> Result<Value, RuntimeError> {
+        let mean: Vec<f64> = self.probs.iter().map(|p| self.n as f64 * p).collect();
+        Ok(float_vector(&mean))
+    }
+
+    fn variance(&self) -> Result<Value, RuntimeError> {
+        let (n, k) = (self.n as f64, self.probs.len());
+        Ok(matrix_value(Matrix::from_fn(k, k, |i, j| {
+            let diagonal = if i == j { self.probs[i] } else { 0.0 };
+            n * (diagonal - self.probs[i] * self.probs[j])
+        })))
+    }
+}
+
+impl std::fmt::Debug for Multinomial {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
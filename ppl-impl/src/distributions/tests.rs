@@ -0,0 +1,1022 @@
+//! Statistical checks that every distribution's `sample` agrees with its `log_pdf`, that each density normalises,
+//! and that values outside the support have a log density of `-inf`. Means, variances, cdfs, quantiles and entropies
+//! are checked against numerical integration of the density.
+//!
+//! Sampling tests use a significance level of `SIGNIFICANCE`, so a correct distribution fails them very rarely, and
+//! seed the random number generator with `SEED` first, so that a failure can be reproduced.
+
+use std::rc::Rc;
+
+use special::Gamma as _;
+
+use super::*;
+use crate::matrix::Matrix;
+
+const SEED: u64 = 42;
+const N_SAMPLES: usize = 4000;
+const SIGNIFICANCE: f64 = 1e-4;
+const NORMALISATION_TOLERANCE: f64 = 1e-3;
+/// Relative tolerance for summaries computed by numerical integration.
+const SUMMARY_TOLERANCE: f64 = 1e-2;
+
+fn float(x: f64) -> Value {
+    Value::Float(x)
+}
+
+fn int(k: i64) -> Value {
+    Value::Integer(k)
+}
+
+fn vector(xs: &[f64]) -> Value {
+    Value::Vector(xs.iter().copied().map(Value::Float).collect())
+}
+
+fn matrix(rows: usize, cols: usize, data: &[f64]) -> Value {
+    Value::Matrix(Rc::new(Matrix::new(rows, cols, data.to_vec())))
+}
+
+fn numeric(val: Value) -> f64 {
+    val.try_get_numeric("expected a number").unwrap()
+}
+
+fn log_density(dist: &dyn Distribution, val: &Value) -> f64 {
+    let log_p = dist.log_pdf(val).unwrap();
+    assert!(
+        !log_p.is_nan(),
+        "{:?} gave a NaN log density for {:?}",
+        dist,
+        val
+    );
+    log_p
+}
+
+/// The CDF of a density on `[lo, hi]`, by the trapezoid rule over `n` intervals.
+struct NumericCdf {
+    xs: Vec<f64>,
+    cdf: Vec<f64>,
+}
+
+impl NumericCdf {
+    fn new<F: Fn(f64) -> f64>(density: F, lo: f64, hi: f64, n: usize) -> Self {
+        let step = (hi - lo) / n as f64;
+        let xs: Vec<f64> = (0..=n).map(|i| lo + i as f64 * step).collect();
+        let ps: Vec<f64> = xs.iter().map(|x| density(*x)).collect();
+        let mut cdf = vec![0.0; n + 1];
+        for i in 1..=n {
+            // An infinite density at an endpoint still has finite mass nearby; drop it rather than poison the sum.
+            let (a, b) = (ps[i - 1], ps[i]);
+            let (a, b) = (
+                if a.is_finite() { a } else { b },
+                if b.is_finite() { b } else { a },
+            );
+            cdf[i] = cdf[i - 1] + (a + b) / 2.0 * step;
+        }
+        NumericCdf { xs, cdf }
+    }
+
+    fn total(&self) -> f64 {
+        *self.cdf.last().unwrap()
+    }
+
+    fn at(&self, x: f64) -> f64 {
+        let i = self.xs.partition_point(|g| *g <= x);
+        if i == 0 {
+            return 0.0;
+        }
+        if i == self.xs.len() {
+            return self.total();
+        }
+        let t = (x - self.xs[i - 1]) / (self.xs[i] - self.xs[i - 1]);
+        self.cdf[i - 1] + t * (self.cdf[i] - self.cdf[i - 1])
+    }
+}
+
+fn assert_close(dist: &dyn Distribution, what: &str, found: f64, expected: f64, tolerance: f64) {
+    assert!(
+        (found - expected).abs() <= tolerance * expected.abs().max(1.0),
+        "{:?}: {} is {}, but should be about {}",
+        dist,
+        what,
+        found,
+        expected
+    );
+}
+
+/// `-p ln p` for a log probability, which is 0 where there's no mass.
+fn entropy_term(log_p: f64) -> f64 {
+    if log_p == f64::NEG_INFINITY {
+        0.0
+    } else {
+        -log_p.exp() * log_p
+    }
+}
+
+/// Kolmogorov-Smirnov test of `samples` against `cdf`.
+fn ks_test<F: Fn(f64) -> f64>(what: &str, mut samples: Vec<f64>, cdf: F) {
+    samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let n = samples.len() as f64;
+    let mut statistic = 0f64;
+    for (i, x) in samples.iter().enumerate() {
+        let f = cdf(*x);
+        statistic = statistic
+            .max((f - i as f64 / n).abs())
+            .max(((i + 1) as f64 / n - f).abs());
+    }
+    let critical = (-(SIGNIFICANCE / 2.0).ln() / 2.0).sqrt() / n.sqrt();
+    assert!(
+        statistic < critical,
+        "{}: KS statistic {} is above the critical value {}",
+        what,
+        statistic,
+        critical
+    );
+}
+
+/// Checks a distribution over a single number, with essentially all of its mass in `[lo, hi]`.
+fn check_continuous(dist: &dyn Distribution, lo: f64, hi: f64) {
+    check_continuous_by(dist, lo, hi, float, numeric);
+
+    let density = |x: f64| log_density(dist, &float(x)).exp();
+    let cdf = NumericCdf::new(density, lo, hi, 200_000);
+    if let Ok(mean) = dist.mean() {
+        let expected = NumericCdf::new(|x| x * density(x), lo, hi, 200_000).total();
+        assert_close(dist, "the mean", numeric(mean), expected, SUMMARY_TOLERANCE);
+        if let Ok(variance) = dist.variance() {
+            let squared = NumericCdf::new(|x| (x - expected).powi(2) * density(x), lo, hi, 200_000);
+            assert_close(
+                dist,
+                "the variance",
+                numeric(variance),
+                squared.total(),
+                SUMMARY_TOLERANCE,
+            );
+        }
+    }
+    for i in 1..10 {
+        let x = lo + (hi - lo) * i as f64 / 10.0;
+        assert_close(
+            dist,
+            "the cdf",
+            dist.cdf(&float(x)).unwrap(),
+            cdf.at(x),
+            NORMALISATION_TOLERANCE,
+        );
+    }
+    for p in &[0.01, 0.1, 0.5, 0.9, 0.99] {
+        let x = numeric(dist.quantile(*p).unwrap());
+        assert_close(
+            dist,
+            "the cdf of the quantile",
+            dist.cdf(&float(x)).unwrap(),
+            *p,
+            1e-9,
+        );
+    }
+}
+
+/// Checks a one-dimensional family of outcomes, `to_value(x)` for `x` in `[lo, hi]`. `from_sample` recovers `x`.
+fn check_continuous_by<F, G>(dist: &dyn Distribution, lo: f64, hi: f64, to_value: F, from_sample: G)
+where
+    F: Fn(f64) -> Value,
+    G: Fn(Value) -> f64,
+{
+    let cdf = NumericCdf::new(|x| log_density(dist, &to_value(x)).exp(), lo, hi, 200_000);
+    assert!(
+        (cdf.total() - 1.0).abs() < NORMALISATION_TOLERANCE,
+        "{:?} has total mass {}",
+        dist,
+        cdf.total()
+    );
+
+    if let Ok(entropy) = dist.entropy() {
+        let expected = NumericCdf::new(
+            |x| entropy_term(log_density(dist, &to_value(x))),
+            lo,
+            hi,
+            200_000,
+        );
+        assert_close(
+            dist,
+            "the entropy",
+            entropy,
+            expected.total(),
+            SUMMARY_TOLERANCE,
+        );
+    }
+
+    let samples = (0..N_SAMPLES)
+        .map(|_| from_sample(dist.sample().unwrap()))
+        .collect();
+    ks_test(&format!("{:?}", dist), samples, |x| cdf.at(x));
+}
+
+/// Chi-square goodness of fit p-value.
+fn chi_square_p_value(statistic: f64, degrees_of_freedom: usize) -> f64 {
+    1.0 - (statistic / 2.0).inc_gamma(degrees_of_freedom as f64 / 2.0)
+}
+
+/// Checks a distribution whose support is, or is almost all covered by, `outcomes`.
+fn check_discrete(dist: &dyn Distribution, outcomes: &[Value]) {
+    let probs: Vec<f64> = outcomes
+        .iter()
+        .map(|v| log_density(dist, v).exp())
+        .collect();
+    let total: f64 = probs.iter().sum();
+    assert!(
+        (total - 1.0).abs() < NORMALISATION_TOLERANCE,
+        "{:?} has total mass {}",
+        dist,
+        total
+    );
+
+    if outcomes.iter().all(|v| matches!(v, Value::Integer(_))) {
+        check_integer_summaries(dist, outcomes, &probs);
+    }
+
+    let keys: Vec<String> = outcomes.iter().map(|v| format!("{:?}", v)).collect();
+    let mut counts = vec![0usize; outcomes.len()];
+    let mut other = 0usize;
+    for _ in 0..N_SAMPLES {
+        let key = format!("{:?}", dist.sample().unwrap());
+        match keys.iter().position(|k| *k == key) {
+            Some(i) => counts[i] += 1,
+            None => other += 1,
+        }
+    }
+
+    // Pool outcomes which are too unlikely for the chi-square approximation to hold.
+    let n = N_SAMPLES as f64;
+    let (mut statistic, mut bins) = (0.0, 0);
+    let (mut pooled_expected, mut pooled_count) = ((1.0 - total).max(0.0) * n, other as f64);
+    for (p, count) in probs.iter().zip(&counts) {
+        let expected = p * n;
+        if expected < 5.0 {
+            pooled_expected += expected;
+            pooled_count += *count as f64;
+        } else {
+            statistic += (*count as f64 - expected).powi(2) / expected;
+            bins += 1;
+        }
+    }
+    if pooled_expected >= 5.0 {
+        statistic += (pooled_count - pooled_expected).powi(2) / pooled_expected;
+        bins += 1;
+    } else {
+        assert!(
+            pooled_count <= 5.0 + 10.0 * pooled_expected,
+            "{:?} sampled {} unlikely outcomes, but only {} were expected",
+            dist,
+            pooled_count,
+            pooled_expected
+        );
+    }
+
+    if bins < 2 {
+        // A distribution with a single likely outcome has nothing to compare.
+        return;
+    }
+
+    let p_value = chi_square_p_value(statistic, bins - 1);
+    assert!(
+        p_value > SIGNIFICANCE,
+        "{:?}: chi-square statistic {} over {} bins has p-value {}",
+        dist,
+        statistic,
+        bins,
+        p_value
+    );
+}
+
+/// Checks the summaries of a distribution over the integers in `outcomes`, which are in increasing order, by summing
+/// `probs`.
+fn check_integer_summaries(dist: &dyn Distribution, outcomes: &[Value], probs: &[f64]) {
+    let ks: Vec<f64> = outcomes.iter().map(|v| numeric(v.clone())).collect();
+    let mean: f64 = ks.iter().zip(probs).map(|(k, p)| k * p).sum();
+    let variance: f64 = ks
+        .iter()
+        .zip(probs)
+        .map(|(k, p)| (k - mean).powi(2) * p)
+        .sum();
+    let entropy: f64 = probs.iter().map(|p| entropy_term(p.ln())).sum();
+    assert_close(dist, "the mean", numeric(dist.mean().unwrap()), mean, 1e-6);
+    assert_close(
+        dist,
+        "the variance",
+        numeric(dist.variance().unwrap()),
+        variance,
+        1e-6,
+    );
+    assert_close(dist, "the entropy", dist.entropy().unwrap(), entropy, 1e-6);
+
+    let mut cumulative = 0.0;
+    for (k, p) in ks.iter().zip(probs) {
+        cumulative += p;
+        assert_close(
+            dist,
+            "the cdf",
+            dist.cdf(&float(*k)).unwrap(),
+            cumulative,
+            1e-9,
+        );
+        assert_close(
+            dist,
+            "the cdf between integers",
+            dist.cdf(&float(k + 0.5)).unwrap(),
+            cumulative,
+            1e-9,
+        );
+    }
+    for p in &[0.01, 0.1, 0.5, 0.9, 0.99] {
+        let k = numeric(dist.quantile(*p).unwrap());
+        assert!(
+            dist.cdf(&float(k)).unwrap() >= p - 1e-12 && dist.cdf(&float(k - 1.0)).unwrap() < *p,
+            "{:?}: {} is not the {} quantile",
+            dist,
+            k,
+            p
+        );
+    }
+}
+
+/// The elements of a vector or matrix value, in row-major order.
+fn elements(val: Value) -> Vec<f64> {
+    match val {
+        Value::Matrix(m) => m.data().to_vec(),
+        v => v.try_into_vector("").unwrap().try_into_numeric("").unwrap(),
+    }
+}
+
+/// Checks the mean and variance of each element of a distribution over vectors or matrices against sample moments.
+fn check_sample_moments(dist: &dyn Distribution) {
+    let mean = elements(dist.mean().unwrap());
+    let variance = elements(dist.variance().unwrap());
+    // Distributions over vectors give a covariance matrix, of which only the diagonal is compared.
+    let variance: Vec<f64> = if variance.len() == mean.len() {
+        variance
+    } else {
+        (0..mean.len())
+            .map(|i| variance[i * mean.len() + i])
+            .collect()
+    };
+
+    let samples: Vec<Vec<f64>> = (0..N_SAMPLES)
+        .map(|_| elements(dist.sample().unwrap()))
+        .collect();
+    let n = N_SAMPLES as f64;
+    for i in 0..mean.len() {
+        let xs: Vec<f64> = samples.iter().map(|s| s[i]).collect();
+        let sample_mean = xs.iter().sum::<f64>() / n;
+        let sample_variance = xs.iter().map(|x| (x - sample_mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let fourth_moment = xs.iter().map(|x| (x - sample_mean).powi(4)).sum::<f64>() / n;
+        let mean_error = (sample_variance / n).sqrt();
+        let variance_error = ((fourth_moment - sample_variance.powi(2)).max(0.0) / n).sqrt();
+        assert!(
+            (sample_mean - mean[i]).abs() <= 5.0 * mean_error + 1e-9,
+            "{:?}: element {} has mean {}, but sampled {}",
+            dist,
+            i,
+            mean[i],
+            sample_mean
+        );
+        assert!(
+            (sample_variance - variance[i]).abs() <= 5.0 * variance_error + 1e-9,
+            "{:?}: element {} has variance {}, but sampled {}",
+            dist,
+            i,
+            variance[i],
+            sample_variance
+        );
+    }
+}
+
+fn assert_out_of_support(dist: &dyn Distribution, vals: &[Value]) {
+    for val in vals {
+        assert_eq!(
+            dist.log_pdf(val).unwrap(),
+            f64::NEG_INFINITY,
+            "{:?} should have no density at {:?}",
+            dist,
+            val
+        );
+    }
+}
+
+fn integers(range: std::ops::Range<i64>) -> Vec<Value> {
+    range.map(int).collect()
+}
+
+#[test]
+fn normal() {
+    crate::random::seed(SEED);
+    check_continuous(&Normal::new(1.0, 2.0).unwrap(), -20.0, 22.0);
+    check_continuous(&Normal::new(0.0, 0.1).unwrap(), -2.0, 2.0);
+    assert_eq!(
+        format!("{:?}", Normal::new(1.0, 2.0).unwrap()),
+        "normal(mu=1.0, sigma=2.0)"
+    );
+    assert!(Normal::new(0.0, -1.0).is_err());
+}
+
+#[test]
+fn gamma() {
+    crate::random::seed(SEED);
+    let d = Gamma::new(2.5, 1.5).unwrap();
+    check_continuous(&d, 0.0, 80.0);
+    check_continuous(&Gamma::new(200.0, 0.1).unwrap(), 0.0, 60.0);
+    assert_out_of_support(&d, &[float(-1.0)]);
+    assert!(Gamma::new(0.0, 1.0).is_err());
+}
+
+#[test]
+fn beta() {
+    crate::random::seed(SEED);
+    let d = Beta::new(2.0, 3.0).unwrap();
+    check_continuous(&d, 0.0, 1.0);
+    assert_out_of_support(&d, &[float(-0.1), float(1.1)]);
+    assert!(Beta::new(1.0, -1.0).is_err());
+    assert!(Beta::new(1.0, f64::INFINITY).is_err());
+}
+
+#[test]
+fn uniform_continuous() {
+    crate::random::seed(SEED);
+    let d = UniformContinuous::new(-1.0, 3.0).unwrap();
+    check_continuous(&d, -1.0, 3.0);
+    assert_out_of_support(&d, &[float(-2.0), float(4.0)]);
+    assert!(UniformContinuous::new(1.0, 1.0).is_err());
+}
+
+#[test]
+fn exponential() {
+    crate::random::seed(SEED);
+    let d = Exponential::new(2.0).unwrap();
+    check_continuous(&d, 0.0, 20.0);
+    assert_out_of_support(&d, &[float(-1.0)]);
+    assert!(Exponential::new(0.0).is_err());
+}
+
+#[test]
+fn student_t() {
+    crate::random::seed(SEED);
+    check_continuous(&StudentT::new(3.0, 1.0, 2.0).unwrap(), -2000.0, 2000.0);
+    assert!(StudentT::new(-3.0, 0.0, 1.0).is_err());
+    assert!(StudentT::new(f64::INFINITY, 0.0, 1.0).is_err());
+}
+
+#[test]
+fn laplace() {
+    crate::random::seed(SEED);
+    check_continuous(&Laplace::new(1.0, 2.0).unwrap(), -60.0, 62.0);
+    assert!(Laplace::new(0.0, 0.0).is_err());
+}
+
+#[test]
+fn cauchy() {
+    crate::random::seed(SEED);
+    check_continuous(&Cauchy::new(0.0, 1.0).unwrap(), -10_000.0, 10_000.0);
+    assert!(Cauchy::new(0.0, -1.0).is_err());
+}
+
+#[test]
+fn log_normal() {
+    crate::random::seed(SEED);
+    let d = LogNormal::new(0.0, 0.5).unwrap();
+    check_continuous(&d, 0.0, 50.0);
+    assert_out_of_support(&d, &[float(-1.0), float(0.0)]);
+    assert!(LogNormal::new(0.0, 0.0).is_err());
+}
+
+#[test]
+fn half_normal() {
+    crate::random::seed(SEED);
+    let d = HalfNormal::new(2.0).unwrap();
+    check_continuous(&d, 0.0, 20.0);
+    assert_out_of_support(&d, &[float(-1.0)]);
+    assert!(HalfNormal::new(-2.0).is_err());
+}
+
+#[test]
+fn discrete() {
+    crate::random::seed(SEED);
+    let d = Discrete::new(vec![1.0, 2.0, 3.0]).unwrap();
+    check_discrete(&d, &integers(0..3));
+    assert_out_of_support(&d, &[int(-1), int(3)]);
+    assert!(Discrete::new(vec![0.0, 0.0]).is_err());
+}
+
+#[test]
+fn bernoulli() {
+    crate::random::seed(SEED);
+    let d = Bernoulli::new(0.3).unwrap();
+    check_discrete(&d, &[Value::Boolean(true), Value::Boolean(false)]);
+    assert!((d.cdf(&Value::Boolean(false)).unwrap() - 0.7).abs() < 1e-12);
+    assert!(!d.quantile(0.7).unwrap().try_get_bool("").unwrap());
+    assert!(d.quantile(0.71).unwrap().try_get_bool("").unwrap());
+    assert!((d.entropy().unwrap() + 0.3f64.ln() * 0.3 + 0.7f64.ln() * 0.7).abs() < 1e-12);
+    assert!(Bernoulli::new(1.5).is_err());
+}
+
+#[test]
+fn poisson() {
+    crate::random::seed(SEED);
+    let d = Poisson::new(3.5).unwrap();
+    check_discrete(&d, &integers(0..60));
+    assert_out_of_support(&d, &[int(-1)]);
+    assert!(Poisson::new(-1.0).is_err());
+}
+
+#[test]
+fn uniform_discrete() {
+    crate::random::seed(SEED);
+    let d = UniformDiscrete::new(-2, 3).unwrap();
+    check_discrete(&d, &integers(-2..3));
+    assert_out_of_support(&d, &[int(-3), int(3)]);
+    assert!(UniformDiscrete::new(3, 3).is_err());
+}
+
+#[test]
+fn binomial() {
+    crate::random::seed(SEED);
+    let d = Binomial::new(10, 0.3).unwrap();
+    check_discrete(&d, &integers(0..11));
+    assert_out_of_support(&d, &[int(-1), int(11)]);
+    check_discrete(&Binomial::new(5, 1.0).unwrap(), &integers(0..6));
+    assert!(Binomial::new(10, 1.1).is_err());
+}
+
+#[test]
+fn geometric() {
+    crate::random::seed(SEED);
+    let d = Geometric::new(0.3).unwrap();
+    check_discrete(&d, &integers(0..200));
+    assert_out_of_support(&d, &[int(-1)]);
+    assert!(Geometric::new(0.0).is_err());
+}
+
+#[test]
+fn negative_binomial() {
+    crate::random::seed(SEED);
+    let d = NegativeBinomial::new(2.5, 0.4).unwrap();
+    check_discrete(&d, &integers(0..300));
+    assert_out_of_support(&d, &[int(-1)]);
+    assert!(NegativeBinomial::new(0.0, 0.5).is_err());
+}
+
+#[test]
+fn categorical() {
+    crate::random::seed(SEED);
+    let values = vec![vector(&[1.0, 2.0]), int(7), Value::Boolean(true)];
+    let d = Categorical::new(values.clone(), vec![1.0, 2.0, 3.0]).unwrap();
+    check_discrete(&d, &values);
+    assert_out_of_support(&d, &[int(8), Value::Boolean(false), vector(&[1.0])]);
+    assert!(d.mean().is_err());
+
+    // Listing a value twice is the same as listing it once with the combined probability.
+    let d = Categorical::new(vec![int(1), int(2), int(1)], vec![1.0, 2.0, 1.0]).unwrap();
+    let once = Discrete::new(vec![0.0, 2.0, 2.0]).unwrap();
+    assert!((d.entropy().unwrap() - once.entropy().unwrap()).abs() < 1e-12);
+    assert!((numeric(d.mean().unwrap()) - 1.5).abs() < 1e-12);
+    assert!(Categorical::new(values, vec![1.0]).is_err());
+}
+
+#[test]
+fn dirichlet() {
+    crate::random::seed(SEED);
+    // With two components, the first is beta distributed, and the density of the pair is the density of the first.
+    let d = Dirichlet::new(vec![2.0, 3.0]).unwrap();
+    check_continuous_by(
+        &d,
+        0.0,
+        1.0,
+        |x| vector(&[x, 1.0 - x]),
+        |v| numeric(v.try_into_vector("").unwrap().remove(0)),
+    );
+    assert_out_of_support(&d, &[vector(&[0.5, 0.6]), vector(&[-0.1, 1.1])]);
+    assert!(Dirichlet::new(vec![1.0, 0.0]).is_err());
+    check_sample_moments(&Dirichlet::new(vec![0.5, 2.0, 4.0]).unwrap());
+}
+
+#[test]
+fn multivariate_normal() {
+    crate::random::seed(SEED);
+    let cov = Matrix::new(2, 2, vec![2.0, 0.8, 0.8, 1.0]);
+    let d = MultivariateNormal::new(vec![1.0, -1.0], cov).unwrap();
+
+    let cdf = NumericCdf::new(
+        |x| {
+            NumericCdf::new(
+                |y| log_density(&d, &vector(&[x, y])).exp(),
+                -12.0,
+                10.0,
+                2000,
+            )
+            .total()
+        },
+        -14.0,
+        16.0,
+        2000,
+    );
+    assert!((cdf.total() - 1.0).abs() < NORMALISATION_TOLERANCE);
+
+    // Any projection of a multivariate normal is normal.
+    let samples: Vec<Vec<f64>> = (0..N_SAMPLES)
+        .map(|_| {
+            d.sample()
+                .unwrap()
+                .try_into_vector("")
+                .unwrap()
+                .try_into_numeric("")
+                .unwrap()
+        })
+        .collect();
+    for (w, mean, var) in [
+        ([1.0, 0.0], 1.0, 2.0f64),
+        ([0.0, 1.0], -1.0, 1.0),
+        ([1.0, 1.0], 0.0, 4.6),
+    ] {
+        let projected = NumericCdf::new(
+            |x| log_density(&Normal::new(mean, var.sqrt()).unwrap(), &float(x)).exp(),
+            -20.0,
+            20.0,
+            200_000,
+        );
+        let xs = samples.iter().map(|s| w[0] * s[0] + w[1] * s[1]).collect();
+        ks_test("mvn projection", xs, |x| projected.at(x));
+    }
+
+    check_sample_moments(&d);
+    let entropy = NumericCdf::new(
+        |x| {
+            NumericCdf::new(
+                |y| entropy_term(log_density(&d, &vector(&[x, y]))),
+                -12.0,
+                10.0,
+                2000,
+            )
+            .total()
+        },
+        -14.0,
+        16.0,
+        2000,
+    );
+    assert_close(
+        &d,
+        "the entropy",
+        d.entropy().unwrap(),
+        entropy.total(),
+        SUMMARY_TOLERANCE,
+    );
+
+    assert!(
+        MultivariateNormal::new(vec![0.0, 0.0], Matrix::new(2, 2, vec![1.0, 2.0, 2.0, 1.0]))
+            .is_err()
+    );
+}
+
+#[test]
+fn diagonal_normal() {
+    crate::random::seed(SEED);
+    let d = DiagonalNormal::new(vec![1.0, -1.0], vec![2.0, 0.5]).unwrap();
+    let full = MultivariateNormal::new(
+        vec![1.0, -1.0],
+        Matrix::new(2, 2, vec![4.0, 0.0, 0.0, 0.25]),
+    )
+    .unwrap();
+    for x in &[vector(&[0.0, 0.0]), vector(&[3.0, -2.0])] {
+        assert!((log_density(&d, x) - log_density(&full, x)).abs() < 1e-9);
+    }
+    assert!((d.entropy().unwrap() - full.entropy().unwrap()).abs() < 1e-9);
+    check_sample_moments(&d);
+
+    for (i, (mean, sigma)) in [(1.0, 2.0), (-1.0, 0.5)].iter().enumerate() {
+        let marginal = NumericCdf::new(
+            |x| log_density(&Normal::new(*mean, *sigma).unwrap(), &float(x)).exp(),
+            -20.0,
+            20.0,
+            200_000,
+        );
+        let xs = (0..N_SAMPLES)
+            .map(|_| numeric(d.sample().unwrap().try_into_vector("").unwrap().remove(i)))
+            .collect();
+        ks_test("mvn-diag marginal", xs, |x| marginal.at(x));
+    }
+}
+
+fn element(val: Value, i: usize, j: usize) -> f64 {
+    Matrix::try_from_value(&val, "sample").unwrap().get(i, j)
+}
+
+#[test]
+fn wishart() {
+    crate::random::seed(SEED);
+    let d = Wishart::new(3.0, Matrix::new(1, 1, vec![2.0])).unwrap();
+    check_continuous_by(&d, 0.0, 200.0, |x| matrix(1, 1, &[x]), |v| element(v, 0, 0));
+    assert_out_of_support(&d, &[matrix(1, 1, &[-1.0])]);
+
+    // The diagonal elements of a Wishart matrix are themselves Wishart with the same degrees of freedom.
+    let d = Wishart::new(4.0, Matrix::new(2, 2, vec![1.0, 0.5, 0.5, 2.0])).unwrap();
+    let marginal = Wishart::new(4.0, Matrix::new(1, 1, vec![2.0])).unwrap();
+    let cdf = NumericCdf::new(
+        |x| log_density(&marginal, &matrix(1, 1, &[x])).exp(),
+        0.0,
+        200.0,
+        200_000,
+    );
+    let xs = (0..N_SAMPLES)
+        .map(|_| element(d.sample().unwrap(), 1, 1))
+        .collect();
+    ks_test("wishart marginal", xs, |x| cdf.at(x));
+    assert_out_of_support(&d, &[matrix(2, 2, &[1.0, 2.0, 2.0, 1.0])]);
+    check_sample_moments(&d);
+
+    assert!(Wishart::new(0.5, Matrix::new(2, 2, vec![1.0, 0.0, 0.0, 1.0])).is_err());
+}
+
+#[test]
+fn inverse_wishart() {
+    crate::random::seed(SEED);
+    let d = InverseWishart::new(5.0, Matrix::new(1, 1, vec![2.0])).unwrap();
+    check_continuous_by(
+        &d,
+        0.0,
+        1000.0,
+        |x| matrix(1, 1, &[x]),
+        |v| element(v, 0, 0),
+    );
+    assert_out_of_support(&d, &[matrix(1, 1, &[-1.0])]);
+
+    // The diagonal elements of a p by p inverse Wishart matrix are inverse Wishart, with p - 1 fewer degrees of freedom.
+    let d = InverseWishart::new(6.0, Matrix::new(2, 2, vec![1.0, 0.5, 0.5, 2.0])).unwrap();
+    let marginal = InverseWishart::new(5.0, Matrix::new(1, 1, vec![1.0])).unwrap();
+    let cdf = NumericCdf::new(
+        |x| log_density(&marginal, &matrix(1, 1, &[x])).exp(),
+        0.0,
+        1000.0,
+        500_000,
+    );
+    let xs = (0..N_SAMPLES)
+        .map(|_| element(d.sample().unwrap(), 0, 0))
+        .collect();
+    ks_test("inverse-wishart marginal", xs, |x| cdf.at(x));
+
+    // Enough degrees of freedom for the sample variance to settle.
+    check_sample_moments(
+        &InverseWishart::new(14.0, Matrix::new(2, 2, vec![1.0, 0.5, 0.5, 2.0])).unwrap(),
+    );
+}
+
+#[test]
+fn lkj() {
+    crate::random::seed(SEED);
+    let d = Lkj::new(2, 2.0).unwrap();
+    check_continuous_by(
+        &d,
+        -1.0,
+        1.0,
+        |r| matrix(2, 2, &[1.0, r, r, 1.0]),
+        |v| element(v, 0, 1),
+    );
+    assert_out_of_support(
+        &d,
+        &[
+            matrix(2, 2, &[1.0, 1.5, 1.5, 1.0]),
+            matrix(2, 2, &[2.0, 0.0, 0.0, 2.0]),
+        ],
+    );
+
+    // Each correlation of a d by d LKJ matrix is 2 * Beta(b, b) - 1, where b = eta - 1 + d / 2.
+    let d = Lkj::new(4, 1.5).unwrap();
+    let b = Beta::new(2.5, 2.5).unwrap();
+    let cdf = NumericCdf::new(
+        |r| log_density(&b, &float((r + 1.0) / 2.0)).exp() / 2.0,
+        -1.0,
+        1.0,
+        200_000,
+    );
+    let xs = (0..N_SAMPLES)
+        .map(|_| element(d.sample().unwrap(), 1, 3))
+        .collect();
+    ks_test("lkj marginal", xs, |x| cdf.at(x));
+    check_sample_moments(&d);
+
+    // With eta = 1, the 3 by 3 correlation matrices are uniform over a set with volume pi^2 / 2.
+    let d = Lkj::new(3, 1.0).unwrap();
+    let log_p = log_density(
+        &d,
+        &matrix(3, 3, &[1.0, 0.2, 0.1, 0.2, 1.0, 0.3, 0.1, 0.3, 1.0]),
+    );
+    assert!((log_p + (std::f64::consts::PI.powi(2) / 2.0).ln()).abs() < 1e-9);
+}
+
+#[test]
+fn multinomial() {
+    crate::random::seed(SEED);
+    let d = Multinomial::new(4, vec![0.2, 0.3, 0.5]).unwrap();
+    let mut outcomes = Vec::new();
+    for i in 0..=4 {
+        for j in 0..=4 - i {
+            outcomes.push(Value::Vector(vec![int(i), int(j), int(4 - i - j)]));
+        }
+    }
+    check_discrete(&d, &outcomes);
+    let counts = |ks: &[i64]| Value::Vector(ks.iter().copied().map(int).collect());
+    assert_out_of_support(&d, &[counts(&[1, 1, 1]), counts(&[-1, 2, 3])]);
+    assert!(Multinomial::new(4, vec![-0.2, 1.2]).is_err());
+    check_sample_moments(&d);
+}
+
+fn shared_normal(mu: f64, sigma: f64) -> Rc<dyn Distribution> {
+    Rc::new(Normal::new(mu, sigma).unwrap())
+}
+
+#[test]
+fn mixture() {
+    crate::random::seed(SEED);
+    let d = Mixture::new(
+        vec![1.0, 3.0],
+        vec![shared_normal(-2.0, 1.0), shared_normal(3.0, 0.5)],
+    )
+    .unwrap();
+    check_continuous(&d, -15.0, 15.0);
+    assert_eq!(d.weights, vec![0.25, 0.75]);
+
+    let d = Mixture::new(
+        vec![0.5, 0.5],
+        vec![
+            Rc::new(Poisson::new(1.0).unwrap()),
+            Rc::new(Poisson::new(8.0).unwrap()),
+        ],
+    )
+    .unwrap();
+    let probs: Vec<f64> = integers(0..40)
+        .iter()
+        .map(|k| log_density(&d, k).exp())
+        .collect();
+    assert_close(&d, "the total mass", probs.iter().sum(), 1.0, 1e-9);
+    assert_close(&d, "the mean", numeric(d.mean().unwrap()), 4.5, 1e-12);
+    let k = numeric(d.quantile(0.5).unwrap());
+    assert!(d.cdf(&float(k)).unwrap() >= 0.5 && d.cdf(&float(k - 1.0)).unwrap() < 0.5);
+
+    assert!(Mixture::new(
+        vec![1.0],
+        vec![shared_normal(0.0, 1.0), shared_normal(1.0, 1.0)]
+    )
+    .is_err());
+    assert!(Mixture::new(
+        vec![-1.0, 2.0],
+        vec![shared_normal(0.0, 1.0), shared_normal(1.0, 1.0)]
+    )
+    .is_err());
+}
+
+#[test]
+fn truncated() {
+    crate::random::seed(SEED);
+    let d = Truncated::new(shared_normal(1.0, 2.0), -1.0, 2.5).unwrap();
+    check_continuous(&d, -1.0, 2.5);
+    assert_out_of_support(&d, &[float(-1.5), float(3.0)]);
+
+    let d = Truncated::new(Rc::new(Exponential::new(1.0).unwrap()), 2.0, f64::INFINITY).unwrap();
+    check_continuous(&d, 2.0, 40.0);
+
+    // The bounds are inclusive for distributions over integers.
+    let poisson = Poisson::new(3.0).unwrap();
+    let d = Truncated::new(Rc::new(Poisson::new(3.0).unwrap()), 2.0, 5.0).unwrap();
+    let mass: f64 = integers(2..6)
+        .iter()
+        .map(|k| log_density(&poisson, k).exp())
+        .sum();
+    for k in integers(2..6) {
+        assert_close(
+            &d,
+            "the density",
+            log_density(&d, &k),
+            log_density(&poisson, &k) - mass.ln(),
+            1e-9,
+        );
+    }
+    assert_out_of_support(&d, &[int(1), int(6)]);
+    for _ in 0..N_SAMPLES {
+        let k = d.sample().unwrap().try_into_integer("").unwrap();
+        assert!((2..=5).contains(&k), "{:?} sampled {}", d, k);
+    }
+    assert_eq!(numeric(d.quantile(0.0).unwrap()), 2.0);
+    assert_eq!(numeric(d.quantile(1.0).unwrap()), 5.0);
+
+    assert!(Truncated::new(shared_normal(0.0, 1.0), 1.0, -1.0).is_err());
+    assert!(Truncated::new(Rc::new(Dirichlet::new(vec![1.0, 1.0]).unwrap()), 0.0, 1.0).is_err());
+}
+
+#[test]
+fn shift_scale() {
+    crate::random::seed(SEED);
+    let d = ShiftScale::new(Rc::new(Gamma::new(2.0, 1.5).unwrap()), 1.0, 3.0).unwrap();
+    check_continuous(&d, 1.0, 150.0);
+    assert_out_of_support(&d, &[float(0.5)]);
+
+    // Shifting and scaling a standard normal gives another normal.
+    let d = ShiftScale::new(shared_normal(0.0, 1.0), 2.0, 3.0).unwrap();
+    let expected = Normal::new(2.0, 3.0).unwrap();
+    assert_close(
+        &d,
+        "the density",
+        log_density(&d, &float(0.7)),
+        log_density(&expected, &float(0.7)),
+        1e-12,
+    );
+
+    let mvn = DiagonalNormal::new(vec![0.0, 1.0], vec![1.0, 2.0]).unwrap();
+    let d = ShiftScale::new(Rc::new(mvn), 1.0, 2.0).unwrap();
+    let expected = DiagonalNormal::new(vec![1.0, 3.0], vec![2.0, 4.0]).unwrap();
+    assert_close(
+        &d,
+        "the density",
+        log_density(&d, &vector(&[0.5, 2.0])),
+        log_density(&expected, &vector(&[0.5, 2.0])),
+        1e-12,
+    );
+    check_sample_moments(&d);
+
+    assert!(ShiftScale::new(Rc::new(Poisson::new(1.0).unwrap()), 0.0, 2.0).is_err());
+    assert!(ShiftScale::new(shared_normal(0.0, 1.0), 0.0, -1.0).is_err());
+}
+
+#[test]
+fn product_and_iid() {
+    crate::random::seed(SEED);
+    let gamma = Gamma::new(3.0, 2.0).unwrap();
+    let d = Product::new(vec![
+        shared_normal(1.0, 2.0),
+        Rc::new(Gamma::new(3.0, 2.0).unwrap()),
+    ])
+    .unwrap();
+    let x = vector(&[0.5, 4.0]);
+    assert_close(
+        &d,
+        "the density",
+        log_density(&d, &x),
+        log_density(&Normal::new(1.0, 2.0).unwrap(), &float(0.5))
+            + log_density(&gamma, &float(4.0)),
+        1e-12,
+    );
+    assert_out_of_support(&d, &[vector(&[0.5, -1.0])]);
+    assert!(d.log_pdf(&vector(&[0.5])).is_err());
+    check_sample_moments(&d);
+
+    let d = Iid::new(Rc::new(Gamma::new(3.0, 2.0).unwrap()), 3).unwrap();
+    let x = vector(&[0.5, 4.0, 2.0]);
+    let expected: f64 = [0.5, 4.0, 2.0]
+        .iter()
+        .map(|x| log_density(&gamma, &float(*x)))
+        .sum();
+    assert_close(&d, "the density", log_density(&d, &x), expected, 1e-12);
+    assert_close(
+        &d,
+        "the entropy",
+        d.entropy().unwrap(),
+        3.0 * gamma.entropy().unwrap(),
+        1e-12,
+    );
+    check_sample_moments(&d);
+    assert_eq!(
+        format!("{:?}", d),
+        "iid(distribution=gamma(shape=3.0, scale=2.0), n=3)"
+    );
+}
+
+#[test]
+fn empirical() {
+    crate::random::seed(SEED);
+    // Log weights needn't be normalised, and minus infinity drops a sample.
+    let values = vec![int(1), int(2), int(1), int(5)];
+    let log_weights = [0.0, 1.0_f64.ln(), 2.0_f64.ln(), f64::NEG_INFINITY];
+    let d = Empirical::new(values, log_weights.iter().map(|w| w + 10.0).collect()).unwrap();
+    check_discrete(&d, &[int(1), int(2)]);
+    assert_close(
+        &d,
+        "the density",
+        log_density(&d, &int(1)),
+        0.75_f64.ln(),
+        1e-12,
+    );
+    assert_out_of_support(&d, &[int(5), int(3)]);
+    assert_close(&d, "the mean", numeric(d.mean().unwrap()), 1.25, 1e-12);
+    assert_close(&d, "the cdf", d.cdf(&float(1.5)).unwrap(), 0.75, 1e-12);
+    assert_eq!(numeric(d.quantile(0.8).unwrap()), 2.0);
+
+    // Floats are smoothed into a density.
+    let xs = [0.3, -1.2, 0.8, 2.5, 0.0, 1.1];
+    let d = Empirical::new(xs.iter().map(|x| float(*x)).collect(), vec![0.0; 6]).unwrap();
+    let density = NumericCdf::new(|x| log_density(&d, &float(x)).exp(), -20.0, 20.0, 200_000);
+    assert_close(
+        &d,
+        "the total mass",
+        density.total(),
+        1.0,
+        NORMALISATION_TOLERANCE,
+    );
+    assert!(log_density(&d, &float(0.5)) > log_density(&d, &float(5.0)));
+
+    assert!(Empirical::new(vec![], vec![]).is_err());
+    assert!(Empirical::new(vec![int(1)], vec![f64::NEG_INFINITY]).is_err());
+}
@@ -0,0 +1,93 @@
+use std::{fmt, rc::Rc};
+
+use super::{fmt_distribution, Distribution, Support};
+use crate::{
+    inference::prior_only::PriorOnly,
+    interpreter::{Binding, Definitions, DistributionDef, Interpreter},
+    types::{RuntimeError, Value},
+};
+
+/// A distribution declared with `defdist`, applied to its parameters.
+///
+/// The sampler and log density are evaluated by their own interpreter, so `sample` inside the sampler draws
+/// from the prior whichever inference algorithm the program runs with. That algorithm only ever sees the
+/// distribution as a whole, just like a built-in one.
+pub struct UserDistribution {
+    definition: Rc<DistributionDef>,
+    args: Vec<Value>,
+    definitions: Rc<Definitions>,
+}
+
+impl UserDistribution {
+    pub fn new(
+        definition: Rc<DistributionDef>,
+        args: Vec<Value>,
+        definitions: Rc<Definitions>,
+    ) -> Self {
+        Self {
+            definition,
+            args,
+            definitions,
+        }
+    }
+
+    fn bindings(&self) -> Vec<Binding> {
+        self.definition
+            .parameters
+            .iter()
+            .zip(&self.args)
+            .map(|(ident, val)| Binding {
+                ident: ident.to_string(),
+                val: val.clone(),
+            })
+            .collect()
+    }
+}
+
+impl Distribution for UserDistribution {
+    fn sample(&self) -> Result<Value, RuntimeError> {
+        let mut alg = PriorOnly::new();
+        let mut interpreter = Interpreter::with_definitions(&mut alg, self.definitions.clone());
+        interpreter.scope = self.bindings();
+        interpreter.eval(&self.definition.sampler)
+    }
+
+    fn log_pdf(&self, val: &Value) -> Result<f64, RuntimeError> {
+        let mut alg = PriorOnly::new();
+        let mut interpreter = Interpreter::with_definitions(&mut alg, self.definitions.clone());
+        interpreter.scope = self.bindings();
+        interpreter.scope.push(Binding {
+            ident: self.definition.value.0.clone(),
+            val: val.clone(),
+        });
+        interpreter
+            .eval(&self.definition.log_density)?
+            .try_into_numeric(&format!(
+                "The log density of `{}` must evaluate to a number.",
+                self.definition.name
+            ))
+    }
+
+    fn name(&self) -> &'static str {
+        self.definition.name
+    }
+
+    fn params(&self) -> Vec<(&'static str, Value)> {
+        self.definition
+            .parameters
+            .iter()
+            .copied()
+            .zip(self.args.iter().cloned())
+            .collect()
+    }
+
+    fn support(&self) -> Support {
+        Support::Unknown
+    }
+}
+
+impl fmt::Debug for UserDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt_distribution(self, f)
+    }
+}
@@ -1,6 +1,10 @@
-use std::{convert::TryFrom, rc::Rc, usize};
+use std::{convert::TryFrom, rc::Rc};
 
-use crate::{EvalResult, distributions::{Bernoulli, Dirichlet, Discrete, Gamma, Normal}, inference::InferenceAlg, interpreter::{Binding, Interpreter}, types::{RuntimeError, Value, ValueImpls, ValueType}};
+use crate::{EvalResult, distributions::{Bernoulli, Dirichlet, Discrete, Gamma, Normal}, inference::InferenceAlg, interpreter::{Binding, Interpreter}, matrix::Matrix, types::{RuntimeError, Value, ValueImpls, ValueType}};
+
+mod distributions;
+mod linalg;
+mod math;
 
 enum ComparisonType {
     Less,
@@ -18,8 +22,8 @@ fn assert_all_numeric_type(fn_name: &str, vals: &[Value]) -> Result<ValueType, R
         let this_el_type = val.get_type();
 
         // float is contagious. if we ever see a float in a numeric operator the result is a float.
-        if all_t == ValueType::Integer && this_el_type == ValueType::Integer {
-            all_t = ValueType::Integer;
+        if this_el_type == ValueType::Integer {
+            // keeps whatever type we've seen so far.
         } else if this_el_type == ValueType::Float {
             all_t = ValueType::Float;
         } else {
@@ -34,6 +38,231 @@ fn assert_all_numeric_type(fn_name: &str, vals: &[Value]) -> Result<ValueType, R
     Ok(all_t)
 }
 
+/// The type a built-in expects for one of its arguments. Used by the static checker.
+#[derive(Clone, Copy, Debug)]
+pub enum Param {
+    Any,
+    Numeric,
+    /// A number, or a vector or matrix of numbers to operate on element-wise.
+    Elementwise,
+    Integer,
+    Boolean,
+    Vector,
+    Distribution,
+}
+
+/// The type a built-in returns. Used by the static checker.
+#[derive(Clone, Copy, Debug)]
+pub enum Returns {
+    /// Integer if every argument is an integer, otherwise float, or a vector if any argument is one.
+    /// Mirrors `assert_all_numeric_type`.
+    NumericPromotion,
+    Type(ValueType),
+    Unknown,
+}
+
+pub struct Signature {
+    pub name: &'static str,
+    pub min_args: usize,
+    /// `None` for variadic functions.
+    pub max_args: Option<usize>,
+    /// Expected argument types. Arguments past the end of this list take the type of the last entry.
+    pub params: &'static [Param],
+    pub returns: Returns,
+}
+
+const fn sig(
+    name: &'static str,
+    min_args: usize,
+    max_args: Option<usize>,
+    params: &'static [Param],
+    returns: Returns,
+) -> Signature {
+    Signature {
+        name,
+        min_args,
+        max_args,
+        params,
+        returns,
+    }
+}
+
+const BOOLEAN: Returns = Returns::Type(ValueType::Boolean);
+const INTEGER: Returns = Returns::Type(ValueType::Integer);
+const FLOAT: Returns = Returns::Type(ValueType::Float);
+const VECTOR: Returns = Returns::Type(ValueType::Vector);
+const DISTRIBUTION: Returns = Returns::Type(ValueType::Distribution);
+
+/// Signatures of everything handled by `dispatch_function`. Keep in sync when adding built-ins.
+pub const BUILTINS: &[Signature] = &[
+    sig("+", 2, None, &[Param::Elementwise], Returns::NumericPromotion),
+    sig("*", 2, None, &[Param::Elementwise], Returns::NumericPromotion),
+    sig("-", 1, Some(2), &[Param::Elementwise], Returns::NumericPromotion),
+    sig("/", 2, Some(2), &[Param::Numeric], Returns::NumericPromotion),
+    sig("<", 2, Some(2), &[Param::Any], BOOLEAN),
+    sig("<=", 2, Some(2), &[Param::Any], BOOLEAN),
+    sig("<>", 2, Some(2), &[Param::Any], BOOLEAN),
+    sig("=", 2, Some(2), &[Param::Any], BOOLEAN),
+    sig(">=", 2, Some(2), &[Param::Any], BOOLEAN),
+    sig(">", 2, Some(2), &[Param::Any], BOOLEAN),
+    sig("and", 2, Some(2), &[Param::Boolean], BOOLEAN),
+    sig("or", 2, Some(2), &[Param::Boolean], BOOLEAN),
+    sig("vector", 0, None, &[Param::Any], VECTOR),
+    sig("get", 2, Some(2), &[Param::Vector, Param::Integer], Returns::Unknown),
+    sig("first", 1, Some(1), &[Param::Vector], Returns::Unknown),
+    sig("second", 1, Some(1), &[Param::Vector], Returns::Unknown),
+    sig("last", 1, Some(1), &[Param::Vector], Returns::Unknown),
+    sig("rest", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("append", 2, Some(2), &[Param::Vector, Param::Any], VECTOR),
+    sig("mat-transpose", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("mat-repmat", 3, Some(3), &[Param::Vector, Param::Integer, Param::Integer], VECTOR),
+    sig("mat-mul", 2, Some(2), &[Param::Vector, Param::Vector], VECTOR),
+    sig("mat-add", 2, Some(2), &[Param::Vector, Param::Vector], VECTOR),
+    sig("mat-sub", 2, Some(2), &[Param::Vector, Param::Vector], VECTOR),
+    sig("mat-emul", 2, Some(2), &[Param::Vector, Param::Vector], VECTOR),
+    sig("mat-div", 2, Some(2), &[Param::Vector, Param::Vector], VECTOR),
+    sig("mat-inverse", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("mat-det", 1, Some(1), &[Param::Vector], FLOAT),
+    sig("cholesky", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("mat-tanh", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("mat-exp", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("mat-log", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("mat-sqrt", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("mat-abs", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("mat-sin", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("mat-cos", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("mat-sigmoid", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("mat-relu", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("log", 1, Some(1), &[Param::Numeric], FLOAT),
+    sig("exp", 1, Some(1), &[Param::Numeric], FLOAT),
+    sig("sqrt", 1, Some(1), &[Param::Numeric], FLOAT),
+    sig("pow", 2, Some(2), &[Param::Numeric], Returns::NumericPromotion),
+    sig("abs", 1, Some(1), &[Param::Numeric], Returns::NumericPromotion),
+    sig("sin", 1, Some(1), &[Param::Numeric], FLOAT),
+    sig("cos", 1, Some(1), &[Param::Numeric], FLOAT),
+    sig("sigmoid", 1, Some(1), &[Param::Numeric], FLOAT),
+    sig("lgamma", 1, Some(1), &[Param::Numeric], FLOAT),
+    sig("floor", 1, Some(1), &[Param::Numeric], INTEGER),
+    sig("round", 1, Some(1), &[Param::Numeric], INTEGER),
+    sig("relu", 1, Some(1), &[Param::Numeric], Returns::NumericPromotion),
+    sig("min", 1, None, &[Param::Elementwise], Returns::Unknown),
+    sig("max", 1, None, &[Param::Elementwise], Returns::Unknown),
+    sig("sum", 1, Some(1), &[Param::Vector], Returns::Unknown),
+    sig("mean", 1, Some(1), &[Param::Any], Returns::Unknown),
+    sig("logsumexp", 1, Some(1), &[Param::Vector], FLOAT),
+    sig("softmax", 1, Some(1), &[Param::Vector], VECTOR),
+    sig("dot", 2, Some(2), &[Param::Vector, Param::Vector], Returns::Unknown),
+    sig("range", 1, Some(2), &[Param::Integer], VECTOR),
+    sig("repeat", 2, Some(2), &[Param::Any, Param::Integer], VECTOR),
+    sig("length", 1, Some(1), &[Param::Vector], INTEGER),
+    sig("concat", 0, None, &[Param::Vector], VECTOR),
+    sig("bernoulli", 1, Some(1), &[Param::Numeric], DISTRIBUTION),
+    sig("flip", 1, Some(1), &[Param::Numeric], DISTRIBUTION),
+    sig("discrete", 1, Some(1), &[Param::Vector], DISTRIBUTION),
+    sig("normal", 2, Some(2), &[Param::Numeric, Param::Numeric], DISTRIBUTION),
+    sig("gamma", 2, Some(2), &[Param::Numeric, Param::Numeric], DISTRIBUTION),
+    sig("dirichlet", 1, Some(1), &[Param::Vector], DISTRIBUTION),
+    sig("beta", 2, Some(2), &[Param::Numeric, Param::Numeric], DISTRIBUTION),
+    sig("uniform-continuous", 2, Some(2), &[Param::Numeric, Param::Numeric], DISTRIBUTION),
+    sig("exponential", 1, Some(1), &[Param::Numeric], DISTRIBUTION),
+    sig("student-t", 1, Some(3), &[Param::Numeric], DISTRIBUTION),
+    sig("laplace", 2, Some(2), &[Param::Numeric, Param::Numeric], DISTRIBUTION),
+    sig("cauchy", 2, Some(2), &[Param::Numeric, Param::Numeric], DISTRIBUTION),
+    sig("log-normal", 2, Some(2), &[Param::Numeric, Param::Numeric], DISTRIBUTION),
+    sig("half-normal", 1, Some(1), &[Param::Numeric], DISTRIBUTION),
+    sig("poisson", 1, Some(1), &[Param::Numeric], DISTRIBUTION),
+    sig("uniform-discrete", 2, Some(2), &[Param::Integer, Param::Integer], DISTRIBUTION),
+    sig("binomial", 2, Some(2), &[Param::Integer, Param::Numeric], DISTRIBUTION),
+    sig("geometric", 1, Some(1), &[Param::Numeric], DISTRIBUTION),
+    sig("negative-binomial", 2, Some(2), &[Param::Numeric, Param::Numeric], DISTRIBUTION),
+    sig("categorical", 2, Some(2), &[Param::Vector, Param::Vector], DISTRIBUTION),
+    sig("mvn", 2, Some(2), &[Param::Vector, Param::Vector], DISTRIBUTION),
+    sig("mvn-diag", 2, Some(2), &[Param::Vector, Param::Vector], DISTRIBUTION),
+    sig("wishart", 2, Some(2), &[Param::Numeric, Param::Vector], DISTRIBUTION),
+    sig("inverse-wishart", 2, Some(2), &[Param::Numeric, Param::Vector], DISTRIBUTION),
+    sig("lkj", 2, Some(2), &[Param::Integer, Param::Numeric], DISTRIBUTION),
+    sig("multinomial", 2, Some(2), &[Param::Integer, Param::Vector], DISTRIBUTION),
+    sig("mixture", 2, Some(2), &[Param::Vector, Param::Vector], DISTRIBUTION),
+    sig("truncated", 3, Some(3), &[Param::Distribution, Param::Numeric, Param::Numeric], DISTRIBUTION),
+    sig("shift-scale", 3, Some(3), &[Param::Distribution, Param::Numeric, Param::Numeric], DISTRIBUTION),
+    sig("product", 1, Some(1), &[Param::Vector], DISTRIBUTION),
+    sig("iid", 2, Some(2), &[Param::Distribution, Param::Integer], DISTRIBUTION),
+    sig("variance", 1, Some(1), &[Param::Distribution], Returns::Unknown),
+    sig("cdf", 2, Some(2), &[Param::Distribution, Param::Any], FLOAT),
+    sig("quantile", 2, Some(2), &[Param::Distribution, Param::Numeric], Returns::Unknown),
+    sig("entropy", 1, Some(1), &[Param::Distribution], FLOAT),
+    sig("support", 1, Some(1), &[Param::Distribution], VECTOR),
+    sig("params", 1, Some(1), &[Param::Distribution], VECTOR),
+];
+
+pub fn builtin_signature(name: &str) -> Option<&'static Signature> {
+    BUILTINS.iter().find(|s| s.name == name)
+}
+
+fn is_vector_or_matrix(val: &Value) -> bool {
+    matches!(val, Value::Vector(_) | Value::Matrix(_))
+}
+
+/// Applies `f` element-wise across any mix of numbers, vectors and matrices, broadcasting dimensions of size 1.
+/// A vector of numbers acts as a single row. The result is a matrix if any argument was a matrix.
+fn elementwise<F: Fn(f64, f64) -> f64>(fn_name: &str, vals: Vec<Value>, f: F) -> EvalResult {
+    let mut any_matrix = false;
+    let mut operands = Vec::with_capacity(vals.len());
+    for (i, val) in vals.iter().enumerate() {
+        let what = format!("Argument {} to `{}`", i + 1, fn_name);
+        let operand = match val {
+            Value::Float(_) | Value::Integer(_) => {
+                Rc::new(Matrix::new(1, 1, vec![val.try_get_numeric("")?]))
+            }
+            Value::Vector(els) if !els.iter().any(is_vector_or_matrix) => {
+                let row = els
+                    .iter()
+                    .map(|v| v.try_get_numeric(&format!("{} contains non-numeric values.", what)))
+                    .collect::<Result<Vec<f64>, RuntimeError>>()?;
+                Rc::new(Matrix::new(1, row.len(), row))
+            }
+            Value::Vector(_) | Value::Matrix(_) => {
+                any_matrix = true;
+                Matrix::try_from_value(val, &what)?
+            }
+            _ => return err!("{} must be a number, vector or matrix.", what),
+        };
+        operands.push(operand);
+    }
+
+    let mut operands = operands.into_iter();
+    let mut result = operands.next().unwrap();
+    for operand in operands {
+        result = match result.broadcast(&operand, &f) {
+            Some(m) => Rc::new(m),
+            None => {
+                return err!(
+                    "`{}` can't broadcast a [{} x {}] and a [{} x {}] together.",
+                    fn_name,
+                    result.n_rows(),
+                    result.n_cols(),
+                    operand.n_rows(),
+                    operand.n_cols()
+                )
+            }
+        };
+    }
+
+    Ok(if any_matrix {
+        Value::Matrix(result)
+    } else {
+        Value::Vector(result.data().iter().copied().map(Value::Float).collect())
+    })
+}
+
+// Matrices behave like a vector of their rows.
+fn matrix_row(m: &Matrix, index: i64) -> EvalResult {
+    if index < 0 || index as usize >= m.n_rows() {
+        return err!("Index out of bounds.");
+    }
+    Ok(m.row_value(index as usize))
+}
+
 impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
     pub fn dispatch_function(
         &mut self,
@@ -70,11 +299,47 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
             "mat-mul" => return self.matrix_multiply(vals),
             "mat-add" => return self.matrix_addition(vals),
 
+            "mat-sub" => return self.matrix_binary(name, vals, |a, b| a - b),
+            "mat-emul" => return self.matrix_binary(name, vals, |a, b| a * b),
+            "mat-div" => return self.matrix_binary(name, vals, |a, b| a / b),
+            "mat-inverse" => return self.matrix_inverse(vals),
+            "mat-det" => return self.matrix_determinant(vals),
+            "cholesky" => return self.cholesky(vals),
+
             "mat-tanh" => return self.matrix_tanh(vals),
+            "mat-exp" => return self.matrix_map(name, vals, f64::exp),
+            "mat-log" => return self.matrix_map(name, vals, f64::ln),
+            "mat-sqrt" => return self.matrix_map(name, vals, f64::sqrt),
+            "mat-abs" => return self.matrix_map(name, vals, f64::abs),
+            "mat-sin" => return self.matrix_map(name, vals, f64::sin),
+            "mat-cos" => return self.matrix_map(name, vals, f64::cos),
+            "mat-sigmoid" => return self.matrix_map(name, vals, math::sigmoid),
+            "mat-relu" => return self.matrix_map(name, vals, math::relu),
 
             "log" => return self.log(vals),
             "exp" => return self.exp(vals),
             "sqrt" => return self.sqrt(vals),
+            "pow" => return self.pow(vals),
+            "abs" => return self.abs(vals),
+            "sin" => return self.float_function(name, vals, f64::sin),
+            "cos" => return self.float_function(name, vals, f64::cos),
+            "sigmoid" => return self.float_function(name, vals, math::sigmoid),
+            "lgamma" => return self.lgamma(vals),
+            "floor" => return self.round_to_integer(name, vals, f64::floor),
+            "round" => return self.round_to_integer(name, vals, f64::round),
+            "relu" => return self.relu(vals),
+            "min" => return self.min(vals),
+            "max" => return self.max(vals),
+
+            "sum" => return self.sum(vals),
+            "mean" => return self.mean(vals),
+            "logsumexp" => return self.logsumexp(vals),
+            "softmax" => return self.softmax(vals),
+            "dot" => return self.dot(vals),
+            "range" => return self.range(vals),
+            "repeat" => return self.repeat(vals),
+            "length" => return self.length(vals),
+            "concat" => return self.concat(vals),
 
             "bernoulli" => return self.flip(vals),
             "flip" => return self.flip(vals),
@@ -83,8 +348,40 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
             "normal" => return self.normal(vals),
             "gamma" => return self.gamma(vals),
             "dirichlet" => return self.dirichlet(vals),
-            // "beta" => return self.beta(vals),
-            // "poisson" => return self.poisson(vals),
+            "beta" => return self.beta(vals),
+            "uniform-continuous" => return self.uniform_continuous(vals),
+            "exponential" => return self.exponential(vals),
+            "student-t" => return self.student_t(vals),
+            "laplace" => return self.laplace(vals),
+            "cauchy" => return self.cauchy(vals),
+            "log-normal" => return self.log_normal(vals),
+            "half-normal" => return self.half_normal(vals),
+
+            "poisson" => return self.poisson(vals),
+            "uniform-discrete" => return self.uniform_discrete(vals),
+            "binomial" => return self.binomial(vals),
+            "geometric" => return self.geometric(vals),
+            "negative-binomial" => return self.negative_binomial(vals),
+            "categorical" => return self.categorical(vals),
+
+            "mvn" => return self.multivariate_normal(vals),
+            "mvn-diag" => return self.diagonal_normal(vals),
+            "wishart" => return self.wishart(vals),
+            "inverse-wishart" => return self.inverse_wishart(vals),
+            "lkj" => return self.lkj(vals),
+            "multinomial" => return self.multinomial(vals),
+            "mixture" => return self.mixture(vals),
+            "truncated" => return self.truncated(vals),
+            "shift-scale" => return self.shift_scale(vals),
+            "product" => return self.product(vals),
+            "iid" => return self.iid(vals),
+
+            "variance" => return self.variance(vals),
+            "cdf" => return self.cdf(vals),
+            "quantile" => return self.quantile(vals),
+            "entropy" => return self.entropy(vals),
+            "support" => return self.support(vals),
+            "params" => return self.params(vals),
             _ => {}
         }
 
@@ -92,8 +389,8 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
         // easy for FOPPL, no scope needed
         // look up function name in self.functions
 
-        if self.functions.contains_key(name) {
-            let function = self.functions.get(name).unwrap().clone();
+        if self.definitions.functions.contains_key(name) {
+            let function = self.definitions.functions.get(name).unwrap().clone();
             let old_scope_count = self.scope.len();
 
             if vals.len() != function.parameters.len() {
@@ -105,7 +402,7 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
                 );
             }
 
-            for (ident, val) in function.parameters.iter().zip(vals.into_iter()) {
+            for (ident, val) in function.parameters.iter().zip(vals) {
                 let binding = Binding {
                     ident: ident.0.clone(),
                     val,
@@ -118,6 +415,11 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
 
             return Ok(val);
         }
+
+        if let Some(definition) = self.definitions.distributions.get(name) {
+            let definition = definition.clone();
+            return self.user_distribution(definition, vals);
+        }
         err!("Could not find function `{}`", name)
     }
 
@@ -126,6 +428,10 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
             return err!("Multiply must have at least 2 arguments.");
         }
 
+        if vals.iter().any(is_vector_or_matrix) {
+            return elementwise("+", vals, |a, b| a + b);
+        }
+
         let mut sum_int = 0i64;
         let mut sum_float = 0f64;
         let mut all_int = true;
@@ -155,6 +461,10 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
             return err!("Multiply must have at least 2 arguments.");
         }
 
+        if vals.iter().any(is_vector_or_matrix) {
+            return elementwise("*", vals, |a, b| a * b);
+        }
+
         let mut product_int = 1i64;
         let mut product_float = 1f64;
         let mut all_int = true;
@@ -180,6 +490,14 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
     }
 
     fn subtraction_or_negation(&mut self, vals: Vec<Value>) -> EvalResult {
+        if vals.iter().any(is_vector_or_matrix) {
+            if vals.len() == 1 {
+                return elementwise("-", vec![Value::Integer(0), vals[0].clone()], |a, b| a - b);
+            } else if vals.len() == 2 {
+                return elementwise("-", vals, |a, b| a - b);
+            }
+        }
+
         if vals.len() == 1 {
             // negation
             assert_all_numeric_type("negation", &vals)?;
@@ -230,16 +548,17 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
             return err!("`get` must have 2 arguments.");
         }
 
-        let list = match &vals[0] {
-            Value::Vector(v) => v,
-            _ => return err!("First argument to `get` must be a vector."),
-        };
-
         let index = match &vals[1] {
             Value::Integer(v) => *v,
             _ => return err!("Second argument to `get` must be an integer."),
         };
 
+        let list = match &vals[0] {
+            Value::Vector(v) => v,
+            Value::Matrix(m) => return matrix_row(m, index),
+            _ => return err!("First argument to `get` must be a vector."),
+        };
+
         if index as usize >= list.len() {
             return err!("Index out of bounds.");
         }
@@ -254,6 +573,7 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
 
         let list = match &vals[0] {
             Value::Vector(v) => v,
+            Value::Matrix(m) => return matrix_row(m, 0),
             _ => return err!("Argument to `first` must be a vector."),
         };
 
@@ -271,6 +591,7 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
 
         let list = match &vals[0] {
             Value::Vector(v) => v,
+            Value::Matrix(m) => return matrix_row(m, 1),
             _ => return err!("Argument to `second` must be a vector."),
         };
 
@@ -288,6 +609,7 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
 
         let list = match &vals[0] {
             Value::Vector(v) => v,
+            Value::Matrix(m) => return matrix_row(m, m.n_rows() as i64 - 1),
             _ => return err!("Argument to `last` must be a vector."),
         };
 
@@ -305,6 +627,8 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
 
         let list = match &vals[0] {
             Value::Vector(v) => v,
+            Value::Matrix(m) if m.n_rows() > 1 => return Ok(Value::Matrix(Rc::new(m.rows_from(1)))),
+            Value::Matrix(_) => return Ok(Value::Vector(Vec::new())),
             _ => return err!("Argument to 'rest' must be a vector."),
         };
 
@@ -316,53 +640,10 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
     }
 
     fn matrix_transpose(&mut self, vals: Vec<Value>) -> EvalResult {
-        if vals.len() != 1 {
-            return err!("`mat-transpose` must have exactly 1 argument.");
-        }
-
-        let list = match &vals[0] {
-            Value::Vector(v) => v,
-            _ => return err!("Argument to `mat-transpose` must be a vector of vectors."),
-        };
-
-        if list.is_empty() {
-            return err!("The vector given to `mat-transpose` must have at least one element.");
-        }
-
-        let first_el = match &list[0] {
-            Value::Vector(v) => v,
-            _ => return err!("Argument to `mat-transpose` must be a vector of vectors."),
-        };
-
-        if first_el.is_empty() {
-            return err!(
-                "All sub-vectors given to `mat-transpose` must have at least one element."
-            );
-        }
-
-        let unwrapped = list
-            .iter()
-            .map(|v| match v {
-                Value::Vector(v) => {
-                    if v.len() != first_el.len() {
-                        return err!("`mat-transpose` needs a 2D, rectangular vector-of-vectors.");
-                    }
-                    Ok(v.clone())
-                }
-                _ => return err!("`mat-transpose` needs a 2D, rectangular vector-of-vectors."),
-            })
-            .collect::<Result<Vec<Vec<Value>>, RuntimeError>>()?;
-
-        let mut transposed = Vec::with_capacity(first_el.len());
-        for i in 0..first_el.len() {
-            let mut row = Vec::with_capacity(list.len());
-            for j in 0..list.len() {
-                row.push(unwrapped[j][i].clone());
-            }
-            transposed.push(Value::Vector(row));
-        }
+        let mat = vals.try_into_one("`mat-transpose` must have exactly 1 argument.")?;
+        let mat = Matrix::try_from_value(&mat, "The argument to `mat-transpose`")?;
 
-        Ok(Value::Vector(transposed))
+        Ok(Value::Matrix(Rc::new(mat.transpose())))
     }
 
     fn matrix_repeat(&mut self, mut vals: Vec<Value>) -> EvalResult {
@@ -376,339 +657,44 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
             vals.pop().unwrap(),
         );
 
-        let mat1 = match v0 {
-            Value::Vector(v) => v,
-            _ => return err!("Argument 1 to `mat-repmat` must be a vector of vectors."),
-        };
-
-        if mat1.is_empty() {
-            return err!("Argument 1 to `mat-repmat` must have at least one element.");
-        }
-
-        let mat1_first_el_len = match &mat1[0] {
-            Value::Vector(v) => v.len(),
-            _ => return err!("Argument 1 to `mat-repmat` must be a vector of vectors."),
-        };
-
-        if mat1_first_el_len < 1 {
-            return err!("All sub-vectors given to `mat-repmat` must have at least one element.");
-        }
-
-        let unwrapped_mat1 = mat1
-            .into_iter()
-            .map(|v| match v {
-                Value::Vector(v) => {
-                    if v.len() != mat1_first_el_len {
-                        return err!("Argument 1 to `mat-repmat` had uneven length rows.");
-                    }
-                    Ok(v)
-                }
-                _ => return err!("First arg to `mat-add` had non-vector elements."),
-            })
-            .collect::<Result<Vec<Vec<Value>>, RuntimeError>>()?;
+        let mat1 = Matrix::try_from_value(&v0, "Argument 1 to `mat-repmat`")?;
 
         let n_rows_mul = match v1 {
             Value::Integer(i) => match usize::try_from(i) {
-                Ok(u) => u,
-                Err(_) => return err!("First arg to `mat-repmat` must be a positive integer."),
+                Ok(u) if u > 0 => u,
+                _ => return err!("First arg to `mat-repmat` must be a positive integer."),
             },
             _ => return err!("First arg to `mat-repmat` must be an integer."),
         };
 
         let n_cols_mul: usize = match v2 {
             Value::Integer(i) => match usize::try_from(i) {
-                Ok(u) => u,
-                Err(_) => return err!("Second arg to `mat-repmat` must be a positive integer."),
+                Ok(u) if u > 0 => u,
+                _ => return err!("Second arg to `mat-repmat` must be a positive integer."),
             },
             _ => return err!("Second arg to `mat-repmat` must be an integer."),
         };
 
-        let n_rows = unwrapped_mat1.len();
-        let n_cols = mat1_first_el_len;
-
-        let mut matrix = Vec::with_capacity(n_rows * n_rows_mul);
-        for i in 0..(n_rows * n_rows_mul) {
-            let i = i % n_rows;
-            let mut row = Vec::with_capacity(n_cols * n_cols_mul);
-            for j in 0..n_cols * n_cols_mul {
-                let j = j % n_cols;
-                row.push(unwrapped_mat1[i][j].clone());
-            }
-            matrix.push(Value::Vector(row));
-        }
-
-        Ok(Value::Vector(matrix))
+        Ok(Value::Matrix(Rc::new(mat1.repeat(n_rows_mul, n_cols_mul))))
     }
 
     fn matrix_multiply(&mut self, vals: Vec<Value>) -> EvalResult {
-        if vals.len() != 2 {
-            return err!("`mat-mul` must have exactly 2 arguments.");
-        }
-
-        let mat1 = match &vals[0] {
-            Value::Vector(v) => v,
-            _ => return err!("Argument 1 to `mat-mul` must be a vector of vectors."),
-        };
-
-        let mat2 = match &vals[1] {
-            Value::Vector(v) => v,
-            _ => return err!("Argument 2 to `mat-mul` must be a vector of vectors."),
-        };
-
-        if mat1.is_empty() {
-            return err!("The vector given to `mat-mul` must have at least one element.");
-        }
-
-        let mat1_first_el = match &mat1[0] {
-            Value::Vector(v) => v,
-            _ => return err!("Argument to `mat-mul` must be a vector of vectors."),
-        };
+        let (mat1, mat2) = vals.try_into_two("`mat-mul` must have exactly 2 arguments.")?;
+        let mat1 = Matrix::try_from_value(&mat1, "Argument 1 to `mat-mul`")?;
+        let mat2 = Matrix::try_from_value(&mat2, "Argument 2 to `mat-mul`")?;
 
-        if mat1_first_el.is_empty() {
-            return err!("All sub-vectors given to `mat-mul` must have at least one element.");
+        match mat1.mul(&mat2) {
+            Some(product) => Ok(Value::Matrix(Rc::new(product))),
+            None => err!("`mat-mul` needs matrices with matching inner dimensions."),
         }
-
-        let mat2_first_el = match &mat2[0] {
-            Value::Vector(v) => v,
-            _ => return err!("Argument to `mat-mul` must be a vector of vectors."),
-        };
-
-        if mat2_first_el.is_empty() {
-            return err!("All sub-vectors given to `mat-mul` must have at least one element.");
-        }
-
-        let unwrapped_mat1 = mat1
-            .iter()
-            .map(|v| match v {
-                Value::Vector(v) => {
-                    if v.len() != mat1_first_el.len() {
-                        return err!("First arg to `mat-mul` had uneven length rows.");
-                    }
-                    assert_all_numeric_type("mat-multiply", v)?;
-                    let v = v
-                        .iter()
-                        .map(|v| match v {
-                            Value::Float(f) => *f,
-                            Value::Integer(i) => *i as f64,
-                            _ => unreachable!(),
-                        })
-                        .collect::<Vec<f64>>();
-                    Ok(v)
-                }
-                _ => return err!("First arg to `mat-mul` had non-vector elements."),
-            })
-            .collect::<Result<Vec<Vec<f64>>, RuntimeError>>()?;
-
-        let unwrapped_mat2 = mat2
-            .iter()
-            .map(|v| match v {
-                Value::Vector(v) => {
-                    if v.len() != mat2_first_el.len() {
-                        return err!("Second arg to `mat-mul` had uneven length rows.");
-                    }
-                    assert_all_numeric_type("mat-multiply", v)?;
-                    let v = v
-                        .iter()
-                        .map(|v| match v {
-                            Value::Float(f) => *f,
-                            Value::Integer(i) => *i as f64,
-                            _ => unreachable!(),
-                        })
-                        .collect::<Vec<f64>>();
-                    Ok(v)
-                }
-                _ => return err!("Second arg to `mat-mul` had non-vector elements."),
-            })
-            .collect::<Result<Vec<Vec<f64>>, RuntimeError>>()?;
-
-        let mat1_nrows = unwrapped_mat1.len();
-        let mat1_ncols = mat1_first_el.len();
-        let mat2_nrows = unwrapped_mat2.len();
-        let mat2_ncols = mat2_first_el.len();
-
-        if mat1_ncols != mat2_nrows {
-            return err!("`mat-mul` needs matrices with matching inner dimensions.");
-        }
-
-        let shared_dim = mat1_ncols;
-
-        let mut product = Vec::with_capacity(mat1_nrows);
-        for i in 0..mat1_nrows {
-            let mut row = Vec::with_capacity(mat2_ncols);
-            for j in 0..mat2_ncols {
-                let mut sum = 0f64;
-                for k in 0..shared_dim {
-                    let v1 = unwrapped_mat1[i][k];
-                    let v2 = unwrapped_mat2[k][j];
-
-                    sum += v1 * v2;
-                }
-                row.push(Value::Float(sum));
-            }
-            product.push(Value::Vector(row));
-        }
-
-        Ok(Value::Vector(product))
     }
 
     fn matrix_addition(&mut self, vals: Vec<Value>) -> EvalResult {
-        if vals.len() != 2 {
-            return err!("`mat-add` must have exactly 2 arguments.");
-        }
-
-        let mat1 = match &vals[0] {
-            Value::Vector(v) => v,
-            _ => return err!("Argument 1 to `mat-add` must be a vector of vectors."),
-        };
-
-        let mat2 = match &vals[1] {
-            Value::Vector(v) => v,
-            _ => return err!("Argument 2 to `mat-add` must be a vector of vectors."),
-        };
-
-        if mat1.is_empty() {
-            return err!("The vector given to `mat-add` must have at least one element.");
-        }
-
-        let mat1_first_el = match &mat1[0] {
-            Value::Vector(v) => v,
-            _ => return err!("Argument to `mat-add` must be a vector of vectors."),
-        };
-
-        if mat1_first_el.is_empty() {
-            return err!("All sub-vectors given to `mat-add` must have at least one element.");
-        }
-
-        let mat2_first_el = match &mat2[0] {
-            Value::Vector(v) => v,
-            _ => return err!("Argument to `mat-add` must be a vector of vectors."),
-        };
-
-        if mat2_first_el.is_empty() {
-            return err!("All sub-vectors given to `mat-add` must have at least one element.");
-        }
-
-        let unwrapped_mat1 = mat1
-            .iter()
-            .map(|v| match v {
-                Value::Vector(v) => {
-                    if v.len() != mat1_first_el.len() {
-                        return err!("First arg to `mat-add` had uneven length rows.");
-                    }
-                    let v = v
-                        .iter()
-                        .map(|v| match v {
-                            Value::Float(f) => Ok(*f),
-                            Value::Integer(i) => Ok(*i as f64),
-                            _ => err!("Elements of matrix 1 in `mat-add` were not numeric."),
-                        })
-                        .collect::<Result<Vec<f64>, RuntimeError>>()?;
-                    Ok(v)
-                }
-                _ => return err!("First arg to `mat-add` had non-vector elements."),
-            })
-            .collect::<Result<Vec<Vec<f64>>, RuntimeError>>()?;
-
-        let unwrapped_mat2 = mat2
-            .iter()
-            .map(|v| match v {
-                Value::Vector(v) => {
-                    if v.len() != mat2_first_el.len() {
-                        return err!("Second arg to `mat-add` had uneven length rows.");
-                    }
-                    let v = v
-                        .iter()
-                        .map(|v| match v {
-                            Value::Float(f) => Ok(*f),
-                            Value::Integer(i) => Ok(*i as f64),
-                            _ => err!("Elements of matrix 2 in `mat-add` were not numeric."),
-                        })
-                        .collect::<Result<Vec<f64>, RuntimeError>>()?;
-                    Ok(v)
-                }
-                _ => return err!("Second arg to `mat-add` had non-vector elements."),
-            })
-            .collect::<Result<Vec<Vec<f64>>, RuntimeError>>()?;
-
-        let mat1_nrows = unwrapped_mat1.len();
-        let mat1_ncols = mat1_first_el.len();
-        let mat2_nrows = unwrapped_mat2.len();
-        let mat2_ncols = mat2_first_el.len();
-
-        let compatible_dimensions =
-            (mat1_nrows == 1 || mat2_nrows == 1 || mat1_nrows == mat2_nrows)
-                && (mat1_ncols == 1 || mat2_ncols == 1 || mat1_ncols == mat2_ncols)
-                && ((mat1_ncols <= mat2_ncols && mat1_nrows <= mat2_nrows)
-                    || (mat2_ncols <= mat1_ncols && mat2_nrows <= mat1_nrows));
-
-        if !compatible_dimensions {
-            return err!("arguments to `mat-add` have incompatible dimensions.");
-        }
-
-        let n_rows = mat1_nrows.max(mat2_nrows);
-        let n_cols = mat1_ncols.max(mat2_ncols);
-
-        let mut sum_mat = Vec::with_capacity(n_rows);
-        for i in 0..n_rows {
-            let mut row = Vec::with_capacity(n_cols);
-            let m1i = if mat1_nrows > 1 { i } else { 0 };
-            let m2i = if mat2_nrows > 1 { i } else { 0 };
-            for j in 0..n_cols {
-                let m1j = if mat1_ncols > 1 { j } else { 0 };
-                let m2j = if mat2_ncols > 1 { j } else { 0 };
-                row.push(Value::Float(
-                    unwrapped_mat1[m1i][m1j] + unwrapped_mat2[m2i][m2j],
-                ));
-            }
-            sum_mat.push(Value::Vector(row));
-        }
-
-        Ok(Value::Vector(sum_mat))
+        self.matrix_binary("mat-add", vals, |a, b| a + b)
     }
 
     fn matrix_tanh(&mut self, vals: Vec<Value>) -> EvalResult {
-        let mat1 = vals
-            .try_into_one("`mat-tanh` must have exactly 1 argument.")?
-            .try_into_vector("Argument to `mat-tanh` must be a vector.")?;
-
-        if mat1.is_empty() {
-            return err!("The vector given to `mat-add` must have at least one element.");
-        }
-
-        let n_rows = mat1.len();
-
-        let n_cols = match &mat1[0] {
-            Value::Vector(v) => v.len(),
-            _ => return err!("Argument to `mat-add` must be a vector of vectors."),
-        };
-
-        if n_cols < 1 {
-            return err!("All sub-vectors given to `mat-add` must have at least one element.");
-        }
-
-        let unwrapped_mat1 = mat1
-            .into_iter()
-            .map(|v| match v {
-                Value::Vector(v) => {
-                    if v.len() != n_cols {
-                        return err!("First arg to `mat-add` had uneven length rows.");
-                    }
-                    v.try_into_numeric("First arg to `mat-add` contains non-numeric values")
-                }
-                _ => return err!("First arg to `mat-add` had non-vector elements."),
-            })
-            .collect::<Result<Vec<Vec<f64>>, RuntimeError>>()?;
-
-        let mut tanh_mat = Vec::with_capacity(n_rows);
-        for old_row in unwrapped_mat1 {
-            let mut row = Vec::with_capacity(n_cols);
-            for old_val in old_row {
-                row.push(Value::Float(old_val.tanh()));
-            }
-            tanh_mat.push(Value::Vector(row));
-        }
-
-        Ok(Value::Vector(tanh_mat))
+        self.matrix_map("mat-tanh", vals, f64::tanh)
     }
 
     fn append(&mut self, mut vals: Vec<Value>) -> EvalResult {
@@ -720,6 +706,7 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
 
         let mut vec = match vals.pop().unwrap() {
             Value::Vector(v) => v,
+            Value::Matrix(m) => m.rows_as_values(),
             _ => return err!("First argument to `append` must be a vector."),
         };
 
@@ -825,16 +812,22 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
                 Ok(Value::Boolean(compare(comparison_type, *a, *b)))
             }
             (Value::Vector(_a), Value::Vector(_b)) => {
-                unimplemented!("Vector comparison not implemented.")
+                err!("Vectors can't be compared.")
             }
-            _ => unimplemented!("Comparison for this type combination not implemented."),
+            // Matrices are equal if they have the same shape and entries, but have no order.
+            (Value::Matrix(a), Value::Matrix(b)) => match comparison_type {
+                ComparisonType::Equal => Ok(Value::Boolean(a == b)),
+                ComparisonType::NotEqual => Ok(Value::Boolean(a != b)),
+                _ => err!("Matrices can only be compared with `=` and `<>`."),
+            },
+            (a, b) => err!("Can't compare {} with {}.", a, b),
         }
     }
 
     fn normal(&mut self, vals: Vec<Value>) -> EvalResult {
         let (mu, sigma) =
             vals.try_into_two_numeric("`normal` expects exactly two numeric arguments.")?;
-        let distribution = Value::Distribution(Rc::new(Normal { mu, sigma }));
+        let distribution = Value::Distribution(Rc::new(Normal::new(mu, sigma)?));
         Ok(distribution)
     }
 
@@ -844,21 +837,21 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
             .try_into_one(message)?
             .try_into_vector(message)?
             .try_into_numeric(message)?;
-        let distribution = Value::Distribution(Rc::new(Discrete { weights }));
+        let distribution = Value::Distribution(Rc::new(Discrete::new(weights)?));
         Ok(distribution)
     }
 
     fn flip(&mut self, weights: Vec<Value>) -> EvalResult {
         let param = weights
             .try_into_one_numeric("`flip` expects a single numeric argument.")?;
-        let distribution = Value::Distribution(Rc::new(Bernoulli { param }));
+        let distribution = Value::Distribution(Rc::new(Bernoulli::new(param)?));
         Ok(distribution)
     }
 
     fn gamma(&mut self, vals: Vec<Value>) -> EvalResult {
         let (alpha, beta) =
             vals.try_into_two_numeric("`gamma` expects exactly two numeric arguments.")?;
-        let distribution = Value::Distribution(Rc::new(Gamma { alpha, beta }));
+        let distribution = Value::Distribution(Rc::new(Gamma::new(alpha, beta)?));
         Ok(distribution)
     }
 
@@ -868,7 +861,7 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
             .try_into_one(message)?
             .try_into_vector(message)?
             .try_into_numeric(message)?;
-        let distribution = Value::Distribution(Rc::new(Dirichlet { parameters }));
+        let distribution = Value::Distribution(Rc::new(Dirichlet::new(parameters)?));
         Ok(distribution)
     }
 }
@@ -0,0 +1,371 @@
+use std::{convert::TryFrom, rc::Rc};
+
+use crate::{
+    distributions::{
+        Beta, Binomial, Categorical, Cauchy, DiagonalNormal, Distribution, Exponential, Geometric,
+        HalfNormal, Iid, InverseWishart, Laplace, Lkj, LogNormal, Mixture, Multinomial,
+        MultivariateNormal, NegativeBinomial, Poisson, Product, ShiftScale, StudentT, Truncated,
+        UniformContinuous, UniformDiscrete, UserDistribution, Wishart,
+    },
+    inference::InferenceAlg,
+    interpreter::{DistributionDef, Interpreter},
+    matrix::Matrix,
+    types::{RuntimeError, Value, ValueImpls},
+    EvalResult,
+};
+
+fn one_distribution(name: &str, vals: Vec<Value>) -> Result<Rc<dyn Distribution>, RuntimeError> {
+    match vals.try_into_one(&format!("`{}` expects exactly one argument.", name))? {
+        Value::Distribution(d) => Ok(d),
+        other => err!(
+            "`{}` expects a distribution, but was given {}.",
+            name,
+            other.get_type()
+        ),
+    }
+}
+
+/// A distribution followed by one more argument, as taken by `cdf` and `quantile`.
+fn distribution_and_value(
+    name: &str,
+    vals: Vec<Value>,
+) -> Result<(Rc<dyn Distribution>, Value), RuntimeError> {
+    let (d, val) = vals.try_into_two(&format!("`{}` expects exactly two arguments.", name))?;
+    match d {
+        Value::Distribution(d) => Ok((d, val)),
+        other => err!(
+            "First argument to `{}` must be a distribution, but was given {}.",
+            name,
+            other.get_type()
+        ),
+    }
+}
+
+/// A vector of distributions, as taken by `mixture` and `product`.
+fn distribution_vector(name: &str, val: Value) -> Result<Vec<Rc<dyn Distribution>>, RuntimeError> {
+    let message = format!("`{}` expects a vector of distributions.", name);
+    val.try_into_vector(&message)?
+        .into_iter()
+        .map(|v| match v {
+            Value::Distribution(d) => Ok(d),
+            _ => err!("{}", message),
+        })
+        .collect()
+}
+
+/// The distribution wrapped by `truncated` or `shift-scale`, and its two numeric arguments.
+fn distribution_and_two_numbers(
+    name: &str,
+    vals: Vec<Value>,
+) -> Result<(Rc<dyn Distribution>, f64, f64), RuntimeError> {
+    let message = format!(
+        "`{}` expects exactly three arguments, a distribution and two numbers.",
+        name
+    );
+    match <[Value; 3]>::try_from(vals) {
+        Ok([Value::Distribution(d), a, b]) => Ok((
+            d,
+            a.try_into_numeric(&message)?,
+            b.try_into_numeric(&message)?,
+        )),
+        _ => err!("{}", message),
+    }
+}
+
+impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
+    pub(super) fn variance(&mut self, vals: Vec<Value>) -> EvalResult {
+        one_distribution("variance", vals)?.variance()
+    }
+
+    pub(super) fn cdf(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (d, x) = distribution_and_value("cdf", vals)?;
+        Ok(Value::Float(d.cdf(&x)?))
+    }
+
+    pub(super) fn quantile(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (d, p) = distribution_and_value("quantile", vals)?;
+        let p = p.try_into_numeric("The probability given to `quantile` must be a number.")?;
+        if !(0.0..=1.0).contains(&p) {
+            return err!(
+                "The probability given to `quantile` must be between 0 and 1, but was {}.",
+                p
+            );
+        }
+        d.quantile(p)
+    }
+
+    pub(super) fn entropy(&mut self, vals: Vec<Value>) -> EvalResult {
+        Ok(Value::Float(one_distribution("entropy", vals)?.entropy()?))
+    }
+
+    /// `[low high]` for distributions over ranges of numbers, or every possible value when there are finitely many.
+    pub(super) fn support(&mut self, vals: Vec<Value>) -> EvalResult {
+        let d = one_distribution("support", vals)?;
+        let support = d.support();
+        match support.to_value() {
+            Some(val) => Ok(val),
+            None => err!(
+                "The support of `{}` is {}, which can't be represented as a value.",
+                d.name(),
+                support
+            ),
+        }
+    }
+
+    /// The parameter values, in the order the distribution's built-in takes them.
+    pub(super) fn params(&mut self, vals: Vec<Value>) -> EvalResult {
+        let d = one_distribution("params", vals)?;
+        Ok(Value::Vector(
+            d.params().into_iter().map(|(_, v)| v).collect(),
+        ))
+    }
+
+    pub(super) fn beta(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (alpha, beta) =
+            vals.try_into_two_numeric("`beta` expects exactly two numeric arguments.")?;
+        Ok(Value::Distribution(Rc::new(Beta::new(alpha, beta)?)))
+    }
+
+    pub(super) fn poisson(&mut self, vals: Vec<Value>) -> EvalResult {
+        let rate = vals.try_into_one_numeric("`poisson` expects a single numeric argument.")?;
+        Ok(Value::Distribution(Rc::new(Poisson::new(rate)?)))
+    }
+
+    pub(super) fn uniform_continuous(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (low, high) = vals
+            .try_into_two_numeric("`uniform-continuous` expects exactly two numeric arguments.")?;
+        Ok(Value::Distribution(Rc::new(UniformContinuous::new(
+            low, high,
+        )?)))
+    }
+
+    pub(super) fn uniform_discrete(&mut self, vals: Vec<Value>) -> EvalResult {
+        let message = "`uniform-discrete` expects exactly two integer arguments.";
+        let (low, high) = vals.try_into_two(message)?;
+        let (low, high) = (
+            low.try_into_integer(message)?,
+            high.try_into_integer(message)?,
+        );
+        Ok(Value::Distribution(Rc::new(UniformDiscrete::new(
+            low, high,
+        )?)))
+    }
+
+    pub(super) fn exponential(&mut self, vals: Vec<Value>) -> EvalResult {
+        let rate = vals.try_into_one_numeric("`exponential` expects a single numeric argument.")?;
+        Ok(Value::Distribution(Rc::new(Exponential::new(rate)?)))
+    }
+
+    pub(super) fn binomial(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (n, p) = vals.try_into_two("`binomial` expects exactly two arguments.")?;
+        let message = "The number of trials for `binomial` must be a non-negative integer.";
+        let n = match n.try_into_integer(message)? {
+            n if n >= 0 => n,
+            _ => return err!("{}", message),
+        };
+        let p = p.try_into_numeric("The probability for `binomial` must be a number.")?;
+        Ok(Value::Distribution(Rc::new(Binomial::new(n as u64, p)?)))
+    }
+
+    pub(super) fn categorical(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (values, probs) = vals.try_into_two(
+            "`categorical` expects exactly two arguments, a vector of values and a vector of probabilities.",
+        )?;
+        let values = values.try_into_vector("First argument to `categorical` must be a vector.")?;
+        let probs = probs
+            .try_into_vector("Second argument to `categorical` must be a vector.")?
+            .try_into_numeric("Second argument to `categorical` must be a vector of numbers.")?;
+        Ok(Value::Distribution(Rc::new(Categorical::new(
+            values, probs,
+        )?)))
+    }
+
+    /// `(student-t nu)`, or `(student-t nu loc scale)`.
+    pub(super) fn student_t(&mut self, vals: Vec<Value>) -> EvalResult {
+        let message = "`student-t` expects either 1 or 3 numeric arguments.";
+        let (nu, loc, scale) = match vals.len() {
+            1 => (vals.try_into_one_numeric(message)?, 0.0, 1.0),
+            3 => match vals.try_into_numeric(message)?.as_slice() {
+                [nu, loc, scale] => (*nu, *loc, *scale),
+                _ => unreachable!(),
+            },
+            _ => return err!("{}", message),
+        };
+        Ok(Value::Distribution(Rc::new(StudentT::new(nu, loc, scale)?)))
+    }
+
+    pub(super) fn laplace(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (loc, scale) =
+            vals.try_into_two_numeric("`laplace` expects exactly two numeric arguments.")?;
+        Ok(Value::Distribution(Rc::new(Laplace::new(loc, scale)?)))
+    }
+
+    pub(super) fn cauchy(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (loc, scale) =
+            vals.try_into_two_numeric("`cauchy` expects exactly two numeric arguments.")?;
+        Ok(Value::Distribution(Rc::new(Cauchy::new(loc, scale)?)))
+    }
+
+    pub(super) fn log_normal(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (mu, sigma) =
+            vals.try_into_two_numeric("`log-normal` expects exactly two numeric arguments.")?;
+        Ok(Value::Distribution(Rc::new(LogNormal::new(mu, sigma)?)))
+    }
+
+    pub(super) fn half_normal(&mut self, vals: Vec<Value>) -> EvalResult {
+        let sigma =
+            vals.try_into_one_numeric("`half-normal` expects a single numeric argument.")?;
+        Ok(Value::Distribution(Rc::new(HalfNormal::new(sigma)?)))
+    }
+
+    pub(super) fn geometric(&mut self, vals: Vec<Value>) -> EvalResult {
+        let p = vals.try_into_one_numeric("`geometric` expects a single numeric argument.")?;
+        Ok(Value::Distribution(Rc::new(Geometric::new(p)?)))
+    }
+
+    pub(super) fn negative_binomial(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (r, p) = vals
+            .try_into_two_numeric("`negative-binomial` expects exactly two numeric arguments.")?;
+        Ok(Value::Distribution(Rc::new(NegativeBinomial::new(r, p)?)))
+    }
+
+    pub(super) fn multivariate_normal(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (mean, covariance) = vals.try_into_two(
+            "`mvn` expects exactly two arguments, a mean vector and a covariance matrix.",
+        )?;
+        let mean = mean
+            .try_into_vector("The mean for `mvn` must be a vector.")?
+            .try_into_numeric("The mean for `mvn` must be a vector of numbers.")?;
+        let covariance = Matrix::try_from_value(&covariance, "The covariance for `mvn`")?;
+        Ok(Value::Distribution(Rc::new(MultivariateNormal::new(
+            mean,
+            (*covariance).clone(),
+        )?)))
+    }
+
+    pub(super) fn diagonal_normal(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (mean, sigmas) = vals.try_into_two(
+            "`mvn-diag` expects exactly two arguments, a mean vector and a vector of standard deviations.",
+        )?;
+        let mean = mean
+            .try_into_vector("The mean for `mvn-diag` must be a vector.")?
+            .try_into_numeric("The mean for `mvn-diag` must be a vector of numbers.")?;
+        let sigmas = sigmas
+            .try_into_vector("The standard deviations for `mvn-diag` must be a vector.")?
+            .try_into_numeric(
+                "The standard deviations for `mvn-diag` must be a vector of numbers.",
+            )?;
+        Ok(Value::Distribution(Rc::new(DiagonalNormal::new(
+            mean, sigmas,
+        )?)))
+    }
+
+    pub(super) fn wishart(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (df, scale) = vals.try_into_two(
+            "`wishart` expects exactly two arguments, the degrees of freedom and a scale matrix.",
+        )?;
+        let df = df.try_into_numeric("The degrees of freedom for `wishart` must be a number.")?;
+        let scale = Matrix::try_from_value(&scale, "The scale for `wishart`")?;
+        Ok(Value::Distribution(Rc::new(Wishart::new(
+            df,
+            (*scale).clone(),
+        )?)))
+    }
+
+    pub(super) fn inverse_wishart(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (df, scale) = vals.try_into_two(
+            "`inverse-wishart` expects exactly two arguments, the degrees of freedom and a scale matrix.",
+        )?;
+        let df =
+            df.try_into_numeric("The degrees of freedom for `inverse-wishart` must be a number.")?;
+        let scale = Matrix::try_from_value(&scale, "The scale for `inverse-wishart`")?;
+        Ok(Value::Distribution(Rc::new(InverseWishart::new(
+            df,
+            (*scale).clone(),
+        )?)))
+    }
+
+    pub(super) fn lkj(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (dim, eta) =
+            vals.try_into_two("`lkj` expects exactly two arguments, a dimension and eta.")?;
+        let dim = dim.try_into_usize("The dimension for `lkj` must be a positive integer.")?;
+        let eta = eta.try_into_numeric("Eta for `lkj` must be a number.")?;
+        Ok(Value::Distribution(Rc::new(Lkj::new(dim, eta)?)))
+    }
+
+    pub(super) fn multinomial(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (n, probs) = vals.try_into_two(
+            "`multinomial` expects exactly two arguments, a number of trials and a vector of probabilities.",
+        )?;
+        // No trials is allowed, and puts all the mass on a vector of zeros.
+        let message = "The number of trials for `multinomial` must be a non-negative integer.";
+        let n = match n.try_into_integer(message)? {
+            n if n >= 0 => n,
+            _ => return err!("{}", message),
+        };
+        let probs = probs
+            .try_into_vector("The probabilities for `multinomial` must be a vector.")?
+            .try_into_numeric("The probabilities for `multinomial` must be a vector of numbers.")?;
+        Ok(Value::Distribution(Rc::new(Multinomial::new(
+            n as u64, probs,
+        )?)))
+    }
+
+    /// Applies a distribution declared with `defdist` to its parameters.
+    pub(super) fn user_distribution(
+        &mut self,
+        definition: Rc<DistributionDef>,
+        vals: Vec<Value>,
+    ) -> EvalResult {
+        if vals.len() != definition.parameters.len() {
+            return err!(
+                "{} expected {} arguments but got {}",
+                definition.name,
+                definition.parameters.len(),
+                vals.len()
+            );
+        }
+        Ok(Value::Distribution(Rc::new(UserDistribution::new(
+            definition,
+            vals,
+            self.definitions.clone(),
+        ))))
+    }
+
+    pub(super) fn mixture(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (weights, components) = vals.try_into_two(
+            "`mixture` expects exactly two arguments, a vector of weights and a vector of distributions.",
+        )?;
+        let weights = weights
+            .try_into_vector("The weights for `mixture` must be a vector.")?
+            .try_into_numeric("The weights for `mixture` must be a vector of numbers.")?;
+        let components = distribution_vector("mixture", components)?;
+        Ok(Value::Distribution(Rc::new(Mixture::new(
+            weights, components,
+        )?)))
+    }
+
+    pub(super) fn truncated(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (d, low, high) = distribution_and_two_numbers("truncated", vals)?;
+        Ok(Value::Distribution(Rc::new(Truncated::new(d, low, high)?)))
+    }
+
+    pub(super) fn shift_scale(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (d, shift, scale) = distribution_and_two_numbers("shift-scale", vals)?;
+        Ok(Value::Distribution(Rc::new(ShiftScale::new(
+            d, shift, scale,
+        )?)))
+    }
+
+    pub(super) fn product(&mut self, vals: Vec<Value>) -> EvalResult {
+        let components = vals.try_into_one("`product` expects a single vector of distributions.")?;
+        let components = distribution_vector("product", components)?;
+        Ok(Value::Distribution(Rc::new(Product::new(components)?)))
+    }
+
+    pub(super) fn iid(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (d, n) = distribution_and_value("iid", vals)?;
+        let n = n.try_into_usize("The number of draws for `iid` must be a positive integer.")?;
+        Ok(Value::Distribution(Rc::new(Iid::new(d, n)?)))
+    }
+}
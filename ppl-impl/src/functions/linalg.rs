@@ -0,0 +1,194 @@
+use std::rc::Rc;
+
+use super::assert_all_numeric_type;
+use crate::{
+    inference::InferenceAlg,
+    interpreter::Interpreter,
+    matrix::{mat_add_compatible, Matrix},
+    types::{Value, ValueImpls, ValueType},
+    EvalResult,
+};
+
+impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
+    pub(super) fn dot(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (a, b) = vals.try_into_two("`dot` must have exactly 2 arguments.")?;
+        let a = a.try_into_vector("First argument to `dot` must be a vector.")?;
+        let b = b.try_into_vector("Second argument to `dot` must be a vector.")?;
+
+        if a.len() != b.len() {
+            return err!(
+                "`dot` needs vectors of the same length, but was given lengths {} and {}.",
+                a.len(),
+                b.len()
+            );
+        }
+
+        let t = assert_all_numeric_type("dot", &a)?;
+        let t = match (t, assert_all_numeric_type("dot", &b)?) {
+            (ValueType::Integer, ValueType::Integer) => ValueType::Integer,
+            _ => ValueType::Float,
+        };
+
+        if t == ValueType::Integer {
+            let mut sum = 0i64;
+            for (x, y) in a.iter().zip(&b) {
+                let product = x.try_get_integer("")?.checked_mul(y.try_get_integer("")?);
+                sum = match product.and_then(|product| sum.checked_add(product)) {
+                    Some(sum) => sum,
+                    None => return err!("`dot` overflowed multiplying and adding up integers."),
+                };
+            }
+            return Ok(Value::Integer(sum));
+        }
+
+        let mut sum = 0f64;
+        for (x, y) in a.iter().zip(&b) {
+            sum += x.try_get_numeric("")? * y.try_get_numeric("")?;
+        }
+        Ok(Value::Float(sum))
+    }
+
+    pub(super) fn range(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (start, end) = match vals.len() {
+            1 => (
+                0,
+                vals[0].try_get_integer("`range` expects integer arguments.")?,
+            ),
+            2 => (
+                vals[0].try_get_integer("`range` expects integer arguments.")?,
+                vals[1].try_get_integer("`range` expects integer arguments.")?,
+            ),
+            _ => return err!("`range` must have 1 or 2 arguments."),
+        };
+
+        Ok(Value::Vector((start..end).map(Value::Integer).collect()))
+    }
+
+    pub(super) fn repeat(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (val, n) = vals.try_into_two("`repeat` must have exactly 2 arguments.")?;
+        let n = n.try_into_usize("Second argument to `repeat` must be a non-negative integer.")?;
+
+        Ok(Value::Vector(vec![val; n]))
+    }
+
+    pub(super) fn length(&mut self, vals: Vec<Value>) -> EvalResult {
+        let val = vals.try_into_one("`length` must have exactly 1 argument.")?;
+
+        Ok(match val {
+            Value::Vector(v) => Value::Integer(v.len() as i64),
+            Value::Matrix(m) => Value::Integer(m.n_rows() as i64),
+            other => return err!("`length` expected a vector, but found {}", other.get_type()),
+        })
+    }
+
+    /// Joins vectors end to end. Matrices with the same number of columns are stacked into a matrix.
+    pub(super) fn concat(&mut self, vals: Vec<Value>) -> EvalResult {
+        let stackable = match vals.first() {
+            Some(Value::Matrix(first)) => vals
+                .iter()
+                .all(|v| matches!(v, Value::Matrix(m) if m.n_cols() == first.n_cols())),
+            _ => false,
+        };
+
+        if stackable {
+            let n_cols = match &vals[0] {
+                Value::Matrix(m) => m.n_cols(),
+                _ => unreachable!(),
+            };
+            // Counted rather than worked out from the data, which is empty for matrices with no columns.
+            let mut n_rows = 0;
+            let mut data = Vec::new();
+            for val in &vals {
+                if let Value::Matrix(m) = val {
+                    n_rows += m.n_rows();
+                    data.extend_from_slice(m.data());
+                }
+            }
+            return Ok(Value::Matrix(Rc::new(Matrix::new(n_rows, n_cols, data))));
+        }
+
+        let mut result = Vec::new();
+        for (i, val) in vals.into_iter().enumerate() {
+            match val {
+                Value::Vector(v) => result.extend(v),
+                Value::Matrix(m) => result.extend(m.rows_as_values()),
+                other => {
+                    return err!(
+                        "Argument {} to `concat` must be a vector, but found {}",
+                        i + 1,
+                        other.get_type()
+                    )
+                }
+            }
+        }
+        Ok(Value::Vector(result))
+    }
+
+    pub(super) fn cholesky(&mut self, vals: Vec<Value>) -> EvalResult {
+        let mat = vals.try_into_one("`cholesky` must have exactly 1 argument.")?;
+        let mat = Matrix::try_from_value(&mat, "The argument to `cholesky`")?;
+
+        match mat.cholesky() {
+            Some(l) => Ok(Value::Matrix(Rc::new(l))),
+            None => err!("`cholesky` needs a symmetric positive definite matrix."),
+        }
+    }
+
+    pub(super) fn matrix_inverse(&mut self, vals: Vec<Value>) -> EvalResult {
+        let mat = vals.try_into_one("`mat-inverse` must have exactly 1 argument.")?;
+        let mat = Matrix::try_from_value(&mat, "The argument to `mat-inverse`")?;
+
+        if !mat.is_square() {
+            return err!("`mat-inverse` needs a square matrix.");
+        }
+
+        match mat.inverse() {
+            Some(inverse) => Ok(Value::Matrix(Rc::new(inverse))),
+            None => err!("`mat-inverse` was given a singular matrix."),
+        }
+    }
+
+    pub(super) fn matrix_determinant(&mut self, vals: Vec<Value>) -> EvalResult {
+        let mat = vals.try_into_one("`mat-det` must have exactly 1 argument.")?;
+        let mat = Matrix::try_from_value(&mat, "The argument to `mat-det`")?;
+
+        match mat.determinant() {
+            Some(det) => Ok(Value::Float(det)),
+            None => err!("`mat-det` needs a square matrix."),
+        }
+    }
+
+    /// The `mat-*` counterparts of the unary numeric functions, like `mat-tanh`.
+    pub(super) fn matrix_map(
+        &mut self,
+        fn_name: &str,
+        vals: Vec<Value>,
+        f: fn(f64) -> f64,
+    ) -> EvalResult {
+        let mat = vals.try_into_one(&format!("`{}` must have exactly 1 argument.", fn_name))?;
+        let mat = Matrix::try_from_value(&mat, &format!("The argument to `{}`", fn_name))?;
+
+        Ok(Value::Matrix(Rc::new(mat.map(f))))
+    }
+
+    /// The `mat-*` counterparts of the binary numeric functions, with the same broadcasting rule as `mat-add`.
+    pub(super) fn matrix_binary(
+        &mut self,
+        fn_name: &str,
+        vals: Vec<Value>,
+        f: fn(f64, f64) -> f64,
+    ) -> EvalResult {
+        let (mat1, mat2) =
+            vals.try_into_two(&format!("`{}` must have exactly 2 arguments.", fn_name))?;
+        let mat1 = Matrix::try_from_value(&mat1, &format!("Argument 1 to `{}`", fn_name))?;
+        let mat2 = Matrix::try_from_value(&mat2, &format!("Argument 2 to `{}`", fn_name))?;
+
+        let dims1 = (mat1.n_rows(), mat1.n_cols());
+        let dims2 = (mat2.n_rows(), mat2.n_cols());
+        if !mat_add_compatible(dims1, dims2) {
+            return err!("arguments to `{}` have incompatible dimensions.", fn_name);
+        }
+
+        Ok(Value::Matrix(Rc::new(mat1.broadcast(&mat2, f).unwrap())))
+    }
+}
@@ -0,0 +1,256 @@
+use std::convert::TryFrom;
+
+use special::Gamma;
+
+use super::assert_all_numeric_type;
+use crate::{
+    inference::InferenceAlg,
+    interpreter::Interpreter,
+    types::{RuntimeError, Value, ValueImpls, ValueType},
+    EvalResult,
+};
+
+fn numeric(v: f64, t: ValueType) -> Value {
+    match t {
+        ValueType::Integer => Value::Integer(v as i64),
+        _ => Value::Float(v),
+    }
+}
+
+/// The elements of a numeric vector (or all the entries of a matrix) as floats, along with the type they promote to.
+fn numeric_elements(fn_name: &str, val: &Value) -> Result<(Vec<f64>, ValueType), RuntimeError> {
+    match val {
+        Value::Vector(els) => {
+            let t = assert_all_numeric_type(fn_name, els)?;
+            let els = els
+                .iter()
+                .map(|v| v.try_get_numeric(""))
+                .collect::<Result<Vec<f64>, RuntimeError>>()?;
+            Ok((els, t))
+        }
+        Value::Matrix(m) => Ok((m.data().to_vec(), ValueType::Float)),
+        _ => err!(
+            "`{}` expected a vector, but found {}",
+            fn_name,
+            val.get_type()
+        ),
+    }
+}
+
+/// `min` and `max` take either several numbers, or a single vector.
+fn extremum_args(fn_name: &str, vals: Vec<Value>) -> Result<(Vec<f64>, ValueType), RuntimeError> {
+    let (els, t) = match vals.as_slice() {
+        [] => return err!("`{}` must have at least 1 argument.", fn_name),
+        [val @ Value::Vector(_)] | [val @ Value::Matrix(_)] => numeric_elements(fn_name, val)?,
+        _ => {
+            let t = assert_all_numeric_type(fn_name, &vals)?;
+            (vals.try_into_numeric("")?, t)
+        }
+    };
+    if els.is_empty() {
+        return err!("`{}` was given an empty vector.", fn_name);
+    }
+    Ok((els, t))
+}
+
+fn log_sum_exp(xs: &[f64]) -> f64 {
+    let max = xs.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    if max.is_infinite() {
+        return max;
+    }
+    max + xs.iter().map(|x| (x - max).exp()).sum::<f64>().ln()
+}
+
+pub fn sigmoid(x: f64) -> f64 {
+    1.0 / (1.0 + (-x).exp())
+}
+
+pub fn relu(x: f64) -> f64 {
+    x.max(0.0)
+}
+
+impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
+    pub(super) fn pow(&mut self, vals: Vec<Value>) -> EvalResult {
+        if vals.len() != 2 {
+            return err!("`pow` must have exactly 2 arguments.");
+        }
+
+        assert_all_numeric_type("pow", &vals)?;
+
+        Ok(match (&vals[0], &vals[1]) {
+            (Value::Integer(base), Value::Integer(exponent)) => {
+                let exponent = match u32::try_from(*exponent) {
+                    Ok(e) => e,
+                    Err(_) => {
+                        return err!(
+                            "`pow` of two integers needs a non-negative exponent, but got {}.",
+                            exponent
+                        )
+                    }
+                };
+                match base.checked_pow(exponent) {
+                    Some(v) => Value::Integer(v),
+                    None => {
+                        return err!(
+                            "`pow` overflowed computing {} to the power {}.",
+                            base,
+                            exponent
+                        )
+                    }
+                }
+            }
+            (base, exponent) => Value::Float(
+                base.try_get_numeric("")?
+                    .powf(exponent.try_get_numeric("")?),
+            ),
+        })
+    }
+
+    pub(super) fn abs(&mut self, vals: Vec<Value>) -> EvalResult {
+        let val = vals.try_into_one("`abs` must have exactly 1 argument.")?;
+
+        Ok(match val {
+            Value::Integer(v) => match v.checked_abs() {
+                Some(v) => Value::Integer(v),
+                None => return err!("`abs` overflowed computing the absolute value of {}.", v),
+            },
+            Value::Float(v) => Value::Float(v.abs()),
+            other => {
+                return err!(
+                    "abs expected a numeric type, but found {}",
+                    other.get_type()
+                )
+            }
+        })
+    }
+
+    pub(super) fn float_function(
+        &mut self,
+        fn_name: &str,
+        vals: Vec<Value>,
+        f: fn(f64) -> f64,
+    ) -> EvalResult {
+        if vals.len() != 1 {
+            return err!("`{}` must have exactly 1 argument.", fn_name);
+        }
+
+        assert_all_numeric_type(fn_name, &vals)?;
+
+        Ok(Value::Float(f(vals[0].try_get_numeric("")?)))
+    }
+
+    pub(super) fn lgamma(&mut self, vals: Vec<Value>) -> EvalResult {
+        self.float_function("lgamma", vals, |x| Gamma::ln_gamma(x).0)
+    }
+
+    /// `floor` and `round` always give an integer, so their results can be used as indices.
+    pub(super) fn round_to_integer(
+        &mut self,
+        fn_name: &str,
+        vals: Vec<Value>,
+        f: fn(f64) -> f64,
+    ) -> EvalResult {
+        let val = vals.try_into_one(&format!("`{}` must have exactly 1 argument.", fn_name))?;
+
+        match val {
+            Value::Integer(v) => Ok(Value::Integer(v)),
+            Value::Float(v) if v.is_finite() => Ok(Value::Integer(f(v) as i64)),
+            Value::Float(v) => err!("`{}` can't convert {} to an integer.", fn_name, v),
+            other => err!(
+                "{} expected a numeric type, but found {}",
+                fn_name,
+                other.get_type()
+            ),
+        }
+    }
+
+    pub(super) fn relu(&mut self, vals: Vec<Value>) -> EvalResult {
+        let val = vals.try_into_one("`relu` must have exactly 1 argument.")?;
+
+        Ok(match val {
+            Value::Integer(v) => Value::Integer(v.max(0)),
+            Value::Float(v) => Value::Float(relu(v)),
+            other => {
+                return err!(
+                    "relu expected a numeric type, but found {}",
+                    other.get_type()
+                )
+            }
+        })
+    }
+
+    pub(super) fn min(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (els, t) = extremum_args("min", vals)?;
+        Ok(numeric(els.into_iter().fold(f64::INFINITY, f64::min), t))
+    }
+
+    pub(super) fn max(&mut self, vals: Vec<Value>) -> EvalResult {
+        let (els, t) = extremum_args("max", vals)?;
+        Ok(numeric(
+            els.into_iter().fold(f64::NEG_INFINITY, f64::max),
+            t,
+        ))
+    }
+
+    pub(super) fn sum(&mut self, vals: Vec<Value>) -> EvalResult {
+        let val = vals.try_into_one("`sum` must have exactly 1 argument.")?;
+
+        if let Value::Vector(els) = &val {
+            if assert_all_numeric_type("sum", els)? == ValueType::Integer {
+                let mut sum = 0i64;
+                for v in els {
+                    sum = match sum.checked_add(v.try_get_integer("")?) {
+                        Some(sum) => sum,
+                        None => return err!("`sum` overflowed adding up integers."),
+                    };
+                }
+                return Ok(Value::Integer(sum));
+            }
+        }
+
+        let (els, _) = numeric_elements("sum", &val)?;
+        Ok(Value::Float(els.into_iter().sum()))
+    }
+
+    /// The mean of a vector or matrix of numbers, or of a distribution.
+    pub(super) fn mean(&mut self, vals: Vec<Value>) -> EvalResult {
+        let val = vals.try_into_one("`mean` must have exactly 1 argument.")?;
+        if let Value::Distribution(d) = &val {
+            return d.mean();
+        }
+        let (els, _) = numeric_elements("mean", &val)?;
+
+        if els.is_empty() {
+            return err!("`mean` was given an empty vector.");
+        }
+
+        Ok(Value::Float(els.iter().sum::<f64>() / els.len() as f64))
+    }
+
+    pub(super) fn logsumexp(&mut self, vals: Vec<Value>) -> EvalResult {
+        let val = vals.try_into_one("`logsumexp` must have exactly 1 argument.")?;
+        let (els, _) = numeric_elements("logsumexp", &val)?;
+
+        Ok(Value::Float(log_sum_exp(&els)))
+    }
+
+    pub(super) fn softmax(&mut self, vals: Vec<Value>) -> EvalResult {
+        let val = vals.try_into_one("`softmax` must have exactly 1 argument.")?;
+        let (els, _) = match val {
+            Value::Vector(_) => numeric_elements("softmax", &val)?,
+            other => {
+                return err!(
+                    "`softmax` expected a vector, but found {}",
+                    other.get_type()
+                )
+            }
+        };
+
+        let normaliser = log_sum_exp(&els);
+        Ok(Value::Vector(
+            els.into_iter()
+                .map(|x| Value::Float((x - normaliser).exp()))
+                .collect(),
+        ))
+    }
+}
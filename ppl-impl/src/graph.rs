@@ -0,0 +1,729 @@
+//! Compiling a program into a graphical model, by unrolling it: every `sample`, `observe`, `factor` and
+//! `condition` the program would run becomes a vertex, with arcs from the random variables its distribution (or
+//! value, or path through `if`s) depends on.
+
+use std::{
+    collections::{BTreeSet, HashMap},
+    convert::TryFrom,
+    fmt,
+};
+
+use crate::{
+    ast::{self, Definition, Expression, ForEach, Ident, Let, Program},
+    inference::prior_only::PriorOnly,
+    interpreter::{Binding, Interpreter},
+    types::{RuntimeError, Value},
+};
+
+/// How deeply `defn`s can call each other while being unrolled, so that recursion which doesn't end after a known
+/// number of steps is reported rather than overflowing the stack.
+const MAX_CALL_DEPTH: usize = 256;
+/// Variables whose constant values are written out longer than this are written as their names instead.
+const MAX_INLINE_CONSTANT: usize = 20;
+/// Labels are cut short after this many characters.
+const MAX_LABEL: usize = 60;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum VertexKind {
+    Latent,
+    /// Made by `observe`, `factor` or `condition`.
+    Observed,
+}
+
+#[derive(Debug)]
+pub struct Vertex {
+    /// `sample`, `observe`, `factor` or `condition`, followed by the vertex's index, as in `pgms-json`.
+    pub name: String,
+    pub kind: VertexKind,
+    /// The link function making the vertex's distribution from its parents, e.g. `(normal sample0 1.0)`.
+    pub link: String,
+    /// The observed value, for `observe`s.
+    pub value: Option<String>,
+    pub parents: BTreeSet<usize>,
+    /// The `foreach` plates the vertex was made in, outermost first, each with the iteration it was made in.
+    pub plates: Vec<(usize, usize)>,
+    /// The vertex in the first iteration of its plates that it's a copy of, which stands for it when plates are
+    /// drawn.
+    pub representative: usize,
+}
+
+/// One `foreach`, which is drawn as a box around a single copy of its body.
+#[derive(Debug)]
+pub struct Plate {
+    pub n_iters: usize,
+    pub parent: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct Pgm {
+    pub vertices: Vec<Vertex>,
+    pub plates: Vec<Plate>,
+    /// The program's result, written in terms of the vertices.
+    pub result: String,
+    pub result_parents: BTreeSet<usize>,
+}
+
+/// What's known about a value before the program runs.
+#[derive(Clone)]
+struct Sym {
+    /// The value itself, if it doesn't depend on any random choices.
+    known: Option<Value>,
+    /// The elements of a vector whose elements are known separately, even though some are random.
+    elements: Option<Vec<Sym>>,
+    /// The vertices the value depends on.
+    parents: BTreeSet<usize>,
+    /// The value written in the program's own syntax, with random choices written as their vertices' names.
+    text: String,
+}
+
+impl Sym {
+    fn known(val: Value, text: String) -> Self {
+        Sym {
+            known: Some(val),
+            elements: None,
+            parents: BTreeSet::new(),
+            text,
+        }
+    }
+
+    fn constant(val: Value) -> Self {
+        let text = val.to_string();
+        Sym::known(val, text)
+    }
+
+    fn random(parents: BTreeSet<usize>, text: String) -> Self {
+        Sym {
+            known: None,
+            elements: None,
+            parents,
+            text,
+        }
+    }
+
+    fn vector(elements: Vec<Sym>) -> Self {
+        let text = format!("[{}]", texts(&elements));
+        if let Some(vals) = elements.iter().map(|e| e.known.clone()).collect() {
+            return Sym::known(Value::Vector(vals), text);
+        }
+        Sym {
+            known: None,
+            parents: all_parents(&elements),
+            elements: Some(elements),
+            text,
+        }
+    }
+
+    /// The elements, if the value is a vector or matrix whose length is known.
+    fn elements(&self) -> Option<Vec<Sym>> {
+        match (&self.elements, &self.known) {
+            (Some(elements), _) => Some(elements.clone()),
+            (None, Some(Value::Vector(xs))) => {
+                Some(xs.iter().cloned().map(Sym::constant).collect())
+            }
+            (None, Some(Value::Matrix(m))) => {
+                Some(m.rows_as_values().into_iter().map(Sym::constant).collect())
+            }
+            _ => None,
+        }
+    }
+}
+
+fn texts(syms: &[Sym]) -> String {
+    syms.iter()
+        .map(|s| s.text.as_str())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn all_parents(syms: &[Sym]) -> BTreeSet<usize> {
+    syms.iter()
+        .flat_map(|s| s.parents.iter().copied())
+        .collect()
+}
+
+struct Compiler<'p, 'alg> {
+    functions: HashMap<&'p str, &'p Definition>,
+    data: &'p [Binding],
+    /// Runs built-ins on constant arguments.
+    interpreter: Interpreter<'alg, PriorOnly>,
+    scope: Vec<(&'p str, Sym)>,
+    /// The parents of the conditions of the `if`s being compiled, which every vertex in their branches depends on.
+    conditions: Vec<BTreeSet<usize>>,
+    /// The plates being compiled, outermost first, each with its current iteration.
+    plates: Vec<(usize, usize)>,
+    plate_ids: HashMap<(usize, Option<usize>, usize), usize>,
+    /// How many times each `foreach`, and each random choice, has been compiled in each iteration of the plates
+    /// around it, by its address in the program.
+    counts: HashMap<(Vec<(usize, usize)>, usize), usize>,
+    /// The first vertex made by each random choice, in each plate, for each time it's been compiled.
+    representatives: HashMap<(Vec<usize>, usize, usize), usize>,
+    mem_cache: HashMap<String, Sym>,
+    depth: usize,
+    pgm: Pgm,
+}
+
+/// Unrolls the program into a graph, with the values bound by `--data` treated as constants. Every `defn` is
+/// inlined, `loop`s and `foreach`es are unrolled, and `if`s whose conditions are constant only compile the branch
+/// that's taken. The distributions of `infer` and `load-empirical` are treated as constants.
+pub fn compile(program: &Program, data: &[Binding]) -> Result<Pgm, RuntimeError> {
+    let mut alg = PriorOnly::new();
+    let mut compiler = Compiler {
+        functions: program
+            .definitions
+            .iter()
+            .map(|d| (d.ident.0.as_str(), d))
+            .collect(),
+        data,
+        interpreter: Interpreter::with_data(&mut alg, Vec::new()),
+        scope: Vec::new(),
+        conditions: Vec::new(),
+        plates: Vec::new(),
+        plate_ids: HashMap::new(),
+        counts: HashMap::new(),
+        representatives: HashMap::new(),
+        mem_cache: HashMap::new(),
+        depth: 0,
+        pgm: Pgm {
+            vertices: Vec::new(),
+            plates: Vec::new(),
+            result: String::new(),
+            result_parents: BTreeSet::new(),
+        },
+    };
+
+    let result = compiler.compile(&program.expression)?;
+    let mut pgm = compiler.pgm;
+    pgm.result = result.text;
+    pgm.result_parents = result.parents;
+    Ok(pgm)
+}
+
+/// Identifies a part of the program, for telling apart the copies of it made by unrolling.
+fn address(expr: &Expression) -> usize {
+    expr as *const Expression as usize
+}
+
+impl<'p, 'alg> Compiler<'p, 'alg> {
+    fn lookup_var(&self, name: &str) -> Option<Sym> {
+        let sym = match self.scope.iter().rev().find(|(ident, _)| *ident == name) {
+            Some((_, sym)) => sym.clone(),
+            None => {
+                let binding = self.data.iter().find(|binding| binding.ident == name)?;
+                return Some(Sym::known(binding.val.clone(), name.to_owned()));
+            }
+        };
+        if sym.parents.is_empty() && sym.text.chars().count() > MAX_INLINE_CONSTANT {
+            return Some(Sym {
+                text: name.to_owned(),
+                ..sym
+            });
+        }
+        Some(sym)
+    }
+
+    fn compile_all(&mut self, exprs: &'p [Expression]) -> Result<Vec<Sym>, RuntimeError> {
+        exprs.iter().map(|e| self.compile(e)).collect()
+    }
+
+    fn compile(&mut self, expr: &'p Expression) -> Result<Sym, RuntimeError> {
+        match expr {
+            Expression::Variable(Ident(name)) => match self.lookup_var(name) {
+                Some(sym) => Ok(sym),
+                None => err!("Variable `{}` is not defined.", name),
+            },
+            Expression::Let(Let { bindings, body }) => {
+                let old_scope_count = self.scope.len();
+                for (Ident(name), expr) in bindings {
+                    let sym = self.compile(expr);
+                    let sym = match sym {
+                        Ok(sym) => sym,
+                        Err(e) => {
+                            self.scope.truncate(old_scope_count);
+                            return Err(e);
+                        }
+                    };
+                    self.scope.push((name, sym));
+                }
+                let syms = self.compile_all(body);
+                self.scope.truncate(old_scope_count);
+                match syms?.pop() {
+                    Some(sym) => Ok(sym),
+                    None => err!("`let` must have a body."),
+                }
+            }
+            Expression::Sample(dist, _) => {
+                let dist = self.compile(dist)?;
+                let vertex = self.add_vertex(
+                    expr,
+                    "sample",
+                    VertexKind::Latent,
+                    dist.text,
+                    dist.parents,
+                    None,
+                );
+                let name = self.pgm.vertices[vertex].name.clone();
+                Ok(Sym::random(std::iter::once(vertex).collect(), name))
+            }
+            Expression::Observe(dist, val, _) => {
+                let dist = self.compile(dist)?;
+                let val = self.compile(val)?;
+                let parents = dist.parents.union(&val.parents).copied().collect();
+                self.add_vertex(
+                    expr,
+                    "observe",
+                    VertexKind::Observed,
+                    dist.text,
+                    parents,
+                    Some(val.text.clone()),
+                );
+                Ok(val)
+            }
+            Expression::Factor(log_weight) => {
+                let log_weight = self.compile(log_weight)?;
+                let link = format!("(factor {})", log_weight.text);
+                let parents = log_weight.parents.clone();
+                self.add_vertex(expr, "factor", VertexKind::Observed, link, parents, None);
+                Ok(log_weight)
+            }
+            Expression::Condition(cond) => {
+                let cond = self.compile(cond)?;
+                let link = format!("(condition {})", cond.text);
+                let parents = cond.parents.clone();
+                self.add_vertex(expr, "condition", VertexKind::Observed, link, parents, None);
+                Ok(cond)
+            }
+            Expression::If(cond, true_branch, false_branch) => {
+                let cond = self.compile(cond)?;
+                if let Some(val) = &cond.known {
+                    return match val.try_get_bool("The condition of `if` must be a boolean.")? {
+                        true => self.compile(true_branch),
+                        false => self.compile(false_branch),
+                    };
+                }
+
+                self.conditions.push(cond.parents.clone());
+                let branches = self
+                    .compile(true_branch)
+                    .and_then(|t| Ok((t, self.compile(false_branch)?)));
+                self.conditions.pop();
+                let (t, f) = branches?;
+                let parents = all_parents(&[cond.clone(), t.clone(), f.clone()]);
+                Ok(Sym::random(
+                    parents,
+                    format!("(if {} {} {})", cond.text, t.text, f.text),
+                ))
+            }
+            Expression::FunctionApplication(Ident(name), args) => {
+                let args = self.compile_all(args)?;
+                self.apply(name, args)
+            }
+            Expression::Mem(Ident(name), args) => {
+                let args = self.compile_all(args)?;
+                // Calls with the same arguments give the same value in a run, whether or not they're random.
+                let key = std::iter::once(name.clone())
+                    .chain(args.iter().map(|a| match &a.known {
+                        Some(val) => val.to_string(),
+                        None => a.text.clone(),
+                    }))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                if let Some(sym) = self.mem_cache.get(&key) {
+                    return Ok(sym.clone());
+                }
+                // The call's random choices are shared by every iteration of the plates around it, so they're
+                // drawn outside of them.
+                let plates = std::mem::take(&mut self.plates);
+                let sym = self.apply(name, args);
+                self.plates = plates;
+                let sym = sym?;
+                self.mem_cache.insert(key, sym.clone());
+                Ok(sym)
+            }
+            Expression::ForEach(ForEach {
+                n_iters,
+                bindings,
+                body,
+            }) => {
+                let n_iters = *n_iters;
+                let mut columns = Vec::with_capacity(bindings.len());
+                for (Ident(name), expr) in bindings {
+                    let sym = self.compile(expr)?;
+                    let elements = match sym.elements() {
+                        Some(elements) if elements.len() != n_iters => {
+                            return err!(
+                                "`foreach` binding vectors must have the specified length."
+                            )
+                        }
+                        Some(elements) => elements,
+                        None => (0..n_iters)
+                            .map(|i| {
+                                Sym::random(
+                                    sym.parents.clone(),
+                                    format!("(get {} {})", sym.text, i),
+                                )
+                            })
+                            .collect(),
+                    };
+                    columns.push((name.as_str(), elements));
+                }
+
+                self.enter_plate(expr, n_iters);
+                let mut results = Vec::with_capacity(n_iters);
+                for i in 0..n_iters {
+                    self.plates.last_mut().unwrap().1 = i;
+                    let old_scope_count = self.scope.len();
+                    self.scope.extend(
+                        columns
+                            .iter()
+                            .map(|(name, elements)| (*name, elements[i].clone())),
+                    );
+                    let syms = self.compile_all(body);
+                    self.scope.truncate(old_scope_count);
+                    match syms.map(|mut syms| syms.pop()) {
+                        Ok(Some(sym)) => results.push(sym),
+                        Ok(None) => {
+                            self.plates.pop();
+                            return err!("`foreach` must have a body.");
+                        }
+                        Err(e) => {
+                            self.plates.pop();
+                            return Err(e);
+                        }
+                    }
+                }
+                self.plates.pop();
+                Ok(Sym::vector(results))
+            }
+            Expression::Loop(ast::Loop {
+                n_iters,
+                accumulator,
+                fn_name: Ident(name),
+                params,
+            }) => {
+                let mut accumulator = self.compile(accumulator)?;
+                let params = self.compile_all(params)?;
+                for i in 0..*n_iters {
+                    let mut args = vec![Sym::constant(Value::Integer(i as i64)), accumulator];
+                    args.extend(params.iter().cloned());
+                    accumulator = self.apply(name, args)?;
+                }
+                Ok(accumulator)
+            }
+            Expression::Infer(ast::Infer {
+                alg: Ident(alg),
+                n_samples,
+                fn_name: Ident(name),
+                params,
+            }) => {
+                let args = self.compile_all(params)?;
+                let text = format!("(infer {} {} {} {})", alg, n_samples, name, texts(&args));
+                Ok(Sym::random(all_parents(&args), text))
+            }
+            Expression::LoadEmpirical(path) => Ok(Sym::random(
+                BTreeSet::new(),
+                format!("(load-empirical {:?})", path),
+            )),
+            Expression::Boolean(x) => Ok(Sym::constant(Value::Boolean(*x))),
+            Expression::Integer(x) => Ok(Sym::constant(Value::Integer(*x))),
+            Expression::Float(x) => Ok(Sym::constant(Value::Float(*x))),
+            Expression::Vector(elements) => Ok(Sym::vector(self.compile_all(elements)?)),
+            Expression::Null => Ok(Sym::constant(Value::Null)),
+        }
+    }
+
+    /// Applies a built-in, working it out if its arguments are constant, or inlines a `defn`.
+    fn apply(&mut self, name: &str, args: Vec<Sym>) -> Result<Sym, RuntimeError> {
+        if let Some(definition) = self.functions.get(name).copied() {
+            return self.inline(definition, args);
+        }
+
+        let text = format!("({} {})", name, texts(&args));
+        let vals: Option<Vec<Value>> = args.iter().map(|a| a.known.clone()).collect();
+        if let (Some(vals), Some(_)) = (vals, crate::functions::builtin_signature(name)) {
+            let val = self.interpreter.dispatch_function(name, vals)?;
+            // Short values are written out, and long ones and distributions as the expression that made them.
+            let written = val.to_string();
+            if written.chars().count() <= MAX_INLINE_CONSTANT
+                && !matches!(val, Value::Distribution(_))
+            {
+                return Ok(Sym::known(val, written));
+            }
+            return Ok(Sym::known(val, text));
+        }
+
+        // Vectors of random values keep track of their elements, so that taking one out only depends on it.
+        let elements: Vec<Option<Vec<Sym>>> = args.iter().map(Sym::elements).collect();
+        let index = args.get(1).and_then(|a| match &a.known {
+            Some(Value::Integer(i)) => usize::try_from(*i).ok(),
+            _ => None,
+        });
+        let found = match (name, elements.as_slice()) {
+            ("vector", _) => Some(Sym::vector(args.clone())),
+            ("get", [Some(xs), _]) => index.and_then(|i| xs.get(i).cloned()),
+            ("first", [Some(xs)]) => xs.first().cloned(),
+            ("second", [Some(xs)]) => xs.get(1).cloned(),
+            ("last", [Some(xs)]) => xs.last().cloned(),
+            ("rest", [Some(xs)]) if !xs.is_empty() => Some(Sym::vector(xs[1..].to_vec())),
+            ("append", [Some(xs), _]) => {
+                let mut xs = xs.clone();
+                xs.push(args[1].clone());
+                Some(Sym::vector(xs))
+            }
+            ("length", [Some(xs)]) => Some(Sym::constant(Value::Integer(xs.len() as i64))),
+            ("concat", xss) if xss.iter().all(Option::is_some) => Some(Sym::vector(
+                xss.iter().flatten().flatten().cloned().collect(),
+            )),
+            _ => None,
+        };
+        Ok(found.unwrap_or_else(|| Sym::random(all_parents(&args), text)))
+    }
+
+    fn inline(&mut self, definition: &'p Definition, args: Vec<Sym>) -> Result<Sym, RuntimeError> {
+        let name = &definition.ident.0;
+        if args.len() != definition.params.len() {
+            return err!(
+                "{} expected {} arguments but got {}",
+                name,
+                definition.params.len(),
+                args.len()
+            );
+        }
+        if self.depth == MAX_CALL_DEPTH {
+            return err!(
+                "`{}` calls itself too deeply to unroll. Only programs whose recursion stops after a known number of \
+                 steps can be drawn as graphs.",
+                name
+            );
+        }
+
+        // A `defn` only sees its parameters and the data, not the scope it's called from.
+        let scope = std::mem::replace(
+            &mut self.scope,
+            definition
+                .params
+                .iter()
+                .map(|Ident(p)| p.as_str())
+                .zip(args)
+                .collect(),
+        );
+        self.depth += 1;
+        let sym = self.compile(&definition.body);
+        self.depth -= 1;
+        self.scope = scope;
+        sym
+    }
+
+    fn enter_plate(&mut self, expr: &Expression, n_iters: usize) {
+        let parent = self.plates.last().map(|(plate, _)| *plate);
+        let count = self.count(address(expr));
+        let next_id = self.pgm.plates.len();
+        let plate = *self
+            .plate_ids
+            .entry((address(expr), parent, count))
+            .or_insert(next_id);
+        if plate == next_id {
+            self.pgm.plates.push(Plate { n_iters, parent });
+        }
+        self.plates.push((plate, 0));
+    }
+
+    /// How many times the part of the program at `address` has been compiled before, in the current iteration of
+    /// every plate.
+    fn count(&mut self, address: usize) -> usize {
+        let count = self
+            .counts
+            .entry((self.plates.clone(), address))
+            .or_insert(0);
+        *count += 1;
+        *count - 1
+    }
+
+    fn add_vertex(
+        &mut self,
+        expr: &Expression,
+        kind: &str,
+        vertex_kind: VertexKind,
+        link: String,
+        mut parents: BTreeSet<usize>,
+        value: Option<String>,
+    ) -> usize {
+        for condition in self.conditions.iter() {
+            parents.extend(condition.iter().copied());
+        }
+        let index = self.pgm.vertices.len();
+        let count = self.count(address(expr));
+        let plate_ids = self.plates.iter().map(|(plate, _)| *plate).collect();
+        let representative = *self
+            .representatives
+            .entry((plate_ids, address(expr), count))
+            .or_insert(index);
+        self.pgm.vertices.push(Vertex {
+            name: format!("{}{}", kind, index),
+            kind: vertex_kind,
+            link,
+            value,
+            parents,
+            plates: self.plates.clone(),
+            representative,
+        });
+        index
+    }
+}
+
+/// Cuts `text` short with an ellipsis if it's longer than `MAX_LABEL` characters.
+fn label(text: &str) -> String {
+    if text.chars().count() <= MAX_LABEL {
+        return text.to_owned();
+    }
+    let mut label: String = text.chars().take(MAX_LABEL - 1).collect();
+    label.push('…');
+    label
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl Pgm {
+    /// Writes the graph in Graphviz's DOT language. Each vertex's distribution is drawn as a box, with arcs from its
+    /// parents into the box and from the box to the vertex. Latent vertices are white and observed ones grey. With
+    /// `plates`, only the first iteration of each `foreach` is drawn, inside a box labelled with the number of
+    /// iterations.
+    pub fn to_dot(&self, plates: bool) -> String {
+        let shown = |i: usize| {
+            if plates {
+                self.vertices[i].representative
+            } else {
+                i
+            }
+        };
+        let mut lines = vec![
+            "digraph pgm {".to_owned(),
+            "    node [fontname=\"Helvetica\"];".to_owned(),
+            "    edge [color=\"gray40\"];".to_owned(),
+        ];
+
+        // The vertices in each plate, and the plates in each plate, with `None` for those outside every plate.
+        let mut contents: HashMap<Option<usize>, (Vec<usize>, Vec<usize>)> = HashMap::new();
+        for (i, v) in self.vertices.iter().enumerate() {
+            if shown(i) == i {
+                let plate = if plates {
+                    v.plates.last().map(|(p, _)| *p)
+                } else {
+                    None
+                };
+                contents.entry(plate).or_default().0.push(i);
+            }
+        }
+        if plates {
+            for (i, plate) in self.plates.iter().enumerate() {
+                contents.entry(plate.parent).or_default().1.push(i);
+            }
+        }
+        self.write_plate(&mut lines, &contents, None, plates, 1);
+
+        let result_parents: BTreeSet<usize> =
+            self.result_parents.iter().map(|p| shown(*p)).collect();
+        if !result_parents.is_empty() {
+            lines.push(format!(
+                "    result [label={}, shape=box, peripheries=2, fontsize=10];",
+                quote(&label(&self.result))
+            ));
+        }
+
+        let mut arcs = BTreeSet::new();
+        // Copies of a vertex in later iterations of a plate can have parents the first copy doesn't, as when they
+        // use different `mem` calls, so the arcs into every copy are drawn into the first.
+        for (i, v) in self.vertices.iter().enumerate() {
+            let to = &self.vertices[shown(i)].name;
+            arcs.insert((format!("{}_link", to), to.clone()));
+            for p in v.parents.iter() {
+                let parent = shown(*p);
+                if parent != shown(i) {
+                    arcs.insert((self.vertices[parent].name.clone(), format!("{}_link", to)));
+                }
+            }
+        }
+        for p in result_parents {
+            arcs.insert((self.vertices[p].name.clone(), "result".to_owned()));
+        }
+        for (from, to) in arcs {
+            lines.push(format!("    {} -> {};", quote(&from), quote(&to)));
+        }
+        lines.push("}".to_owned());
+        lines.join("\n") + "\n"
+    }
+
+    fn write_plate(
+        &self,
+        lines: &mut Vec<String>,
+        contents: &HashMap<Option<usize>, (Vec<usize>, Vec<usize>)>,
+        plate: Option<usize>,
+        plates: bool,
+        depth: usize,
+    ) {
+        let indent = "    ".repeat(depth);
+        let (vertices, children) = match contents.get(&plate) {
+            Some(contents) => contents,
+            None => return,
+        };
+        for i in vertices {
+            let v = &self.vertices[*i];
+            // Only the first of a plate's observed values would be shown, so none are.
+            let value = v.value.as_ref().filter(|_| v.plates.is_empty() || !plates);
+            let (fill, text) = match (v.kind, value) {
+                (VertexKind::Latent, _) => ("white", quote(&v.name)),
+                (VertexKind::Observed, Some(value)) => (
+                    "gray75",
+                    quote(&format!("{}\n= {}", v.name, label(value))).replace('\n', "\\n"),
+                ),
+                (VertexKind::Observed, None) => ("gray75", quote(&v.name)),
+            };
+            lines.push(format!(
+                "{}{} [label={}, shape=ellipse, style=filled, fillcolor={}];",
+                indent,
+                quote(&v.name),
+                text,
+                fill
+            ));
+            lines.push(format!(
+                "{}{} [label={}, shape=box, style=rounded, color=gray40, fontsize=10];",
+                indent,
+                quote(&format!("{}_link", v.name)),
+                quote(&label(&v.link))
+            ));
+        }
+        for p in children {
+            lines.push(format!("{}subgraph cluster_plate{} {{", indent, p));
+            lines.push(format!(
+                "{}    label={}; labeljust=r; style=rounded;",
+                indent,
+                quote(&format!("foreach {}", self.plates[*p].n_iters))
+            ));
+            self.write_plate(lines, contents, Some(*p), plates, depth + 1);
+            lines.push(format!("{}}}", indent));
+        }
+    }
+}
+
+/// One line for each vertex, with its distribution and parents.
+impl fmt::Display for Pgm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for v in self.vertices.iter() {
+            write!(f, "{} ~ {}", v.name, v.link)?;
+            if let Some(value) = &v.value {
+                write!(f, " = {}", value)?;
+            }
+            if !v.parents.is_empty() {
+                let parents: Vec<&str> = v
+                    .parents
+                    .iter()
+                    .map(|p| self.vertices[*p].name.as_str())
+                    .collect();
+                write!(f, "  <- {}", parents.join(", "))?;
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "result: {}", self.result)
+    }
+}
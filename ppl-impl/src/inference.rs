@@ -1,38 +1,93 @@
+use std::rc::Rc;
+
 use crate::{
     distributions::Distribution,
     types::{RuntimeError, Value},
-    DataFile, ResultValue, ProgramResult,
+    DataFile, DistributionResult, ResultValue, ProgramResult,
 };
 
-fn flatten_to_numeric_vec_only(vals: Vec<Value>) -> Result<Vec<ProgramResult>, RuntimeError> {
+pub fn flatten_to_numeric_vec_only(vals: Vec<Value>) -> Result<Vec<ProgramResult>, RuntimeError> {
     vals.into_iter()
         .map(|v| match v {
             Value::Integer(x) => Ok(ProgramResult::One(ResultValue::Int(x))),
             Value::Boolean(x) => Ok(ProgramResult::One(ResultValue::Boolean(x))),
             Value::Float(x) => Ok(ProgramResult::One(ResultValue::Float(x))),
             Value::Vector(x) => Ok(ProgramResult::Many(flatten_to_numeric_vec_only(x)?)),
-            _ => err!("Program should only return numbers or vecs of numbers."),
+            Value::Matrix(m) => Ok(ProgramResult::Many(
+                (0..m.n_rows())
+                    .map(|i| {
+                        let row = m.row(i).iter().map(|x| ProgramResult::One(ResultValue::Float(*x)));
+                        ProgramResult::Many(row.collect())
+                    })
+                    .collect(),
+            )),
+            Value::Distribution(d) => {
+                let (names, vals): (Vec<_>, Vec<_>) = d.params().into_iter().unzip();
+                let vals = flatten_to_numeric_vec_only(vals)?;
+                Ok(ProgramResult::Distribution(DistributionResult {
+                    distribution: d.name(),
+                    params: names.into_iter().zip(vals).collect(),
+                }))
+            }
+            _ => err!("Program should only return numbers, distributions or vecs of them."),
         })
         .collect::<Result<Vec<ProgramResult>, RuntimeError>>()
 }
 
+/// Identifies one random choice in a run of the program, so that inference algorithms can match up the choices
+/// made by different runs.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug, Default)]
+pub struct Address {
+    /// The memoised call the choice was made in, e.g. `f [3]`, or `None` outside of `mem`. A memoised call is
+    /// only evaluated once per run, so its choices are addressed by its arguments rather than by where the call
+    /// happened to first be made.
+    pub mem_call: Option<Rc<str>>,
+    /// Which `sample` or `observe` expression made the choice.
+    pub site: usize,
+    /// How many choices were made before this one, in the same memoised call or outside of any.
+    pub count: usize,
+}
+
+/// The algorithms `infer` can run inside a program.
+pub const NESTED_ALGS: &[&str] = &["prior-only", "likelihood-weighting", "single-site-metropolis"];
+
 pub trait InferenceAlg {
     fn sample(
         &mut self,
         dist: &dyn Distribution,
-        sample_number: Option<usize>,
+        address: Option<Address>,
     ) -> Result<Value, RuntimeError>;
     fn observe(
         &mut self,
         dist: &dyn Distribution,
         val: Value,
-        observe_number: Option<usize>,
+        address: Option<Address>,
     ) -> Result<Value, RuntimeError>;
 
+    /// Adds `log_weight` to the weight of the current evaluation, as `factor` and `condition` do.
+    fn factor(&mut self, log_weight: f64) -> Result<(), RuntimeError>;
+
     fn finish_one_evaluation(&mut self, val: Value);
     fn finalize_and_make_dataset(self) -> Result<DataFile, RuntimeError>;
+
+    /// Removes the results kept so far, with their log weights if the algorithm weights them, so that they can be
+    /// written out while inference is still running instead of being held until the end.
+    fn take_kept(&mut self) -> Vec<(Value, Option<f64>)>;
+
+    /// The results kept since the last `take_kept`, with their log weights if the algorithm weights them, for
+    /// showing progress.
+    fn kept(&self) -> (&[Value], Option<&[f64]>);
+
+    /// The fraction of proposals accepted so far, for MCMC algorithms.
+    fn acceptance_rate(&self) -> Option<f64> {
+        None
+    }
+
+    /// The results kept by the algorithm, with their log weights, for `infer` inside a program.
+    fn into_weighted_results(self) -> (Vec<Value>, Vec<f64>);
 }
 
 pub mod likelihood_weighting;
 pub mod prior_only;
 pub mod single_site_metropolis;
+pub mod tracing;
@@ -4,7 +4,7 @@ use crate::{
     DataFile, ResultValue, ProgramResult,
 };
 
-use super::{flatten_to_numeric_vec_only, InferenceAlg};
+use super::{flatten_to_numeric_vec_only, Address, InferenceAlg};
 
 pub struct LikelihoodWeighting {
     pub log_w: f64,
@@ -26,7 +26,7 @@ impl InferenceAlg for LikelihoodWeighting {
     fn sample(
         &mut self,
         dist: &dyn Distribution,
-        _sample_number: Option<usize>,
+        _address: Option<Address>,
     ) -> Result<Value, RuntimeError> {
         dist.sample()
     }
@@ -35,12 +35,18 @@ impl InferenceAlg for LikelihoodWeighting {
         &mut self,
         dist: &dyn Distribution,
         val: Value,
-        _observe_number: Option<usize>,
+        _address: Option<Address>,
     ) -> Result<Value, RuntimeError> {
         self.log_w += dist.log_pdf(&val)?;
 
         Ok(val)
     }
+
+    fn factor(&mut self, log_weight: f64) -> Result<(), RuntimeError> {
+        self.log_w += log_weight;
+        Ok(())
+    }
+
     fn finish_one_evaluation(&mut self, result: Value) {
         let log_w = self.log_w;
         self.log_w = 0f64;
@@ -59,6 +65,22 @@ impl InferenceAlg for LikelihoodWeighting {
                     ProgramResult::Many(vec![val, ProgramResult::One(ResultValue::Float(*weight))])
                 })
                 .collect(),
+            sample_stats: None,
         })
     }
+
+    fn take_kept(&mut self) -> Vec<(Value, Option<f64>)> {
+        self.results
+            .drain(..)
+            .zip(self.weights.drain(..).map(Some))
+            .collect()
+    }
+
+    fn kept(&self) -> (&[Value], Option<&[f64]>) {
+        (&self.results, Some(&self.weights))
+    }
+
+    fn into_weighted_results(self) -> (Vec<Value>, Vec<f64>) {
+        (self.results, self.weights)
+    }
 }
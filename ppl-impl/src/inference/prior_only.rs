@@ -4,7 +4,7 @@ use crate::{
     DataFile,
 };
 
-use super::{flatten_to_numeric_vec_only, InferenceAlg};
+use super::{flatten_to_numeric_vec_only, Address, InferenceAlg};
 
 pub struct PriorOnly {
     results: Vec<Value>,
@@ -22,7 +22,7 @@ impl InferenceAlg for PriorOnly {
     fn sample(
         &mut self,
         dist: &dyn Distribution,
-        _sample_number: Option<usize>,
+        _address: Option<Address>,
     ) -> Result<Value, RuntimeError> {
         dist.sample()
     }
@@ -31,11 +31,15 @@ impl InferenceAlg for PriorOnly {
         &mut self,
         dist: &dyn Distribution,
         _val: Value,
-        _observe_number: Option<usize>,
+        _address: Option<Address>,
     ) -> Result<Value, RuntimeError> {
         dist.sample()
     }
 
+    fn factor(&mut self, _log_weight: f64) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+
     fn finish_one_evaluation(&mut self, result: Value) {
         self.results.push(result);
     }
@@ -46,6 +50,20 @@ impl InferenceAlg for PriorOnly {
         Ok(DataFile {
             has_weights: false,
             data: vals,
+            sample_stats: None,
         })
     }
+
+    fn take_kept(&mut self) -> Vec<(Value, Option<f64>)> {
+        self.results.drain(..).map(|val| (val, None)).collect()
+    }
+
+    fn kept(&self) -> (&[Value], Option<&[f64]>) {
+        (&self.results, None)
+    }
+
+    fn into_weighted_results(self) -> (Vec<Value>, Vec<f64>) {
+        let weights = vec![0.0; self.results.len()];
+        (self.results, weights)
+    }
 }
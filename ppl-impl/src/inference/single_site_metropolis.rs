@@ -1,18 +1,20 @@
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-use rand::{thread_rng, Rng};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
 use crate::{
+    checkpoint::{restore_floats, save_floats, Rebuild, SavedAddress, SavedFloat, SavedValue},
     distributions::Distribution,
+    random::rng,
     types::{RuntimeError, Value},
-    DataFile,
+    DataFile, SampleStats,
 };
 
-use super::{flatten_to_numeric_vec_only, InferenceAlg};
+use super::{flatten_to_numeric_vec_only, Address, InferenceAlg};
 
-// refers to a unique instantiation of a `sample` or `observe` expression.
-type VariableAddress = (usize, usize);
-type Trace = HashMap<VariableAddress, (Value, f64)>;
+// Ordered, so that the site chosen for a proposal only depends on the random number generator.
+type Trace = BTreeMap<Address, (Value, f64)>;
 
 pub struct SingleSiteMetropolis {
     skip: usize,
@@ -23,17 +25,40 @@ pub struct SingleSiteMetropolis {
     proposal: RunMemory,
 
     reached_proposal_site: bool,
-    proposal_site: VariableAddress,
-    var_counter: usize,
+    proposal_site: Address,
+
+    // How many proposals have been made and accepted, for the acceptance rate.
+    proposals: usize,
+    accepted: usize,
 
     // all_program traces
     samples: Vec<Value>,
+    lp: Vec<f64>,
+    accept_stat: Vec<f64>,
+    log_likelihood: Vec<Vec<f64>>,
 }
 
 struct RunMemory {
     trace: Trace,
     reused_log_weight: f64,
     observed_log_weight: f64,
+    // The log density of each observation, for the sample stats.
+    observations: Vec<f64>,
+}
+
+impl RunMemory {
+    fn new() -> Self {
+        RunMemory {
+            trace: BTreeMap::new(),
+            reused_log_weight: 0.,
+            observed_log_weight: 0.,
+            observations: Vec::new(),
+        }
+    }
+
+    fn log_joint(&self) -> f64 {
+        self.trace.values().map(|(_, log_weight)| log_weight).sum::<f64>() + self.observed_log_weight
+    }
 }
 
 impl SingleSiteMetropolis {
@@ -42,34 +67,125 @@ impl SingleSiteMetropolis {
             skip,
             count: 0,
             last: None,
-            proposal: RunMemory {
-                trace: HashMap::new(),
-                reused_log_weight: 0.,
-                observed_log_weight: 0.,
-            },
+            proposal: RunMemory::new(),
             reached_proposal_site: false,
-            proposal_site: (0, 0),
-            var_counter: 0,
+            proposal_site: Address::default(),
+            proposals: 0,
+            accepted: 0,
             samples: Vec::new(),
+            lp: Vec::new(),
+            accept_stat: Vec::new(),
+            log_likelihood: Vec::new(),
         }
     }
 }
 
+/// The state of a chain between runs of the program, for `--checkpoint`.
+#[derive(Serialize, Deserialize)]
+pub struct SavedChain {
+    skip: usize,
+    count: usize,
+    last: Option<SavedRun>,
+    proposal_site: SavedAddress,
+    proposals: usize,
+    accepted: usize,
+    samples: Vec<SavedValue>,
+    lp: Vec<SavedFloat>,
+    accept_stat: Vec<SavedFloat>,
+    log_likelihood: Vec<Vec<SavedFloat>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedRun {
+    trace: Vec<(SavedAddress, SavedValue, SavedFloat)>,
+    reused_log_weight: SavedFloat,
+    observed_log_weight: SavedFloat,
+    observations: Vec<SavedFloat>,
+}
+
+impl SavedRun {
+    fn save(run: &RunMemory) -> Self {
+        SavedRun {
+            trace: run
+                .trace
+                .iter()
+                .map(|(address, (val, log_weight))| {
+                    (SavedAddress::save(address), SavedValue::save(val), SavedFloat(*log_weight))
+                })
+                .collect(),
+            reused_log_weight: SavedFloat(run.reused_log_weight),
+            observed_log_weight: SavedFloat(run.observed_log_weight),
+            observations: save_floats(&run.observations),
+        }
+    }
+
+    fn restore(self, rebuild: &mut Rebuild) -> Result<RunMemory, RuntimeError> {
+        let mut trace = BTreeMap::new();
+        for (address, val, SavedFloat(log_weight)) in self.trace {
+            trace.insert(address.restore(), (val.restore(rebuild)?, log_weight));
+        }
+        Ok(RunMemory {
+            trace,
+            reused_log_weight: self.reused_log_weight.0,
+            observed_log_weight: self.observed_log_weight.0,
+            observations: restore_floats(self.observations),
+        })
+    }
+}
+
+impl SingleSiteMetropolis {
+    /// Only called between runs of the program, when there's no proposal part way through being made.
+    pub fn save(&self) -> SavedChain {
+        SavedChain {
+            skip: self.skip,
+            count: self.count,
+            last: self.last.as_ref().map(SavedRun::save),
+            proposal_site: SavedAddress::save(&self.proposal_site),
+            proposals: self.proposals,
+            accepted: self.accepted,
+            samples: self.samples.iter().map(SavedValue::save).collect(),
+            lp: save_floats(&self.lp),
+            accept_stat: save_floats(&self.accept_stat),
+            log_likelihood: self.log_likelihood.iter().map(|l| save_floats(l)).collect(),
+        }
+    }
+
+    /// `rebuild` makes the distributions in the trace again, since they can't be saved as they are.
+    pub fn restore(saved: SavedChain, rebuild: &mut Rebuild) -> Result<Self, RuntimeError> {
+        Ok(Self {
+            skip: saved.skip,
+            count: saved.count,
+            last: saved.last.map(|run| run.restore(rebuild)).transpose()?,
+            proposal: RunMemory::new(),
+            reached_proposal_site: false,
+            proposal_site: saved.proposal_site.restore(),
+            proposals: saved.proposals,
+            accepted: saved.accepted,
+            samples: saved
+                .samples
+                .into_iter()
+                .map(|val| val.restore(rebuild))
+                .collect::<Result<_, _>>()?,
+            lp: restore_floats(saved.lp),
+            accept_stat: restore_floats(saved.accept_stat),
+            log_likelihood: saved.log_likelihood.into_iter().map(restore_floats).collect(),
+        })
+    }
+}
+
 impl InferenceAlg for SingleSiteMetropolis {
     fn sample(
         &mut self,
         dist: &dyn Distribution,
-        sample_number: Option<usize>,
+        address: Option<Address>,
     ) -> Result<Value, RuntimeError> {
-        let sample_number = sample_number
-            .expect("Shouldn't happen: SingleSiteMetropolis didn't recieve a sample_number.");
-
-        // construct a unique identifier for a random variable based on:
+        // The address identifies a random variable by:
         // - The `sample` statement it represents
-        // - The number of times any random variable has been sampled/observed in this program execution.
+        // - The number of times any random variable has been sampled/observed in this program execution, or in
+        //   the memoised call it was made in.
         // This is slightly sketchy, I'm pretty sure I can construct a program which causes nonsense
-        let key = (sample_number, self.var_counter);
-        self.var_counter += 1;
+        let key =
+            address.expect("Shouldn't happen: SingleSiteMetropolis didn't recieve an address.");
 
         let (val, log_weight) = if let Some(last) = &mut self.last {
             if key == self.proposal_site {
@@ -86,7 +202,7 @@ impl InferenceAlg for SingleSiteMetropolis {
                 // This is currently conservative - it assumes dependence on the proposal site more often than neccessary. We could decrease it with
                 // a data dependency graph.
                 let (val, log_weight) = if self.reached_proposal_site {
-                    (val.clone(), dist.log_pdf(&val)?)
+                    (val.clone(), dist.log_pdf(val)?)
                 } else {
                     (val.clone(), *log_weight)
                 };
@@ -116,13 +232,19 @@ impl InferenceAlg for SingleSiteMetropolis {
         &mut self,
         dist: &dyn Distribution,
         val: Value,
-        _observe_number: Option<usize>,
+        _address: Option<Address>,
     ) -> Result<Value, RuntimeError> {
         let log_weight = dist.log_pdf(&val)?;
         self.proposal.observed_log_weight += log_weight;
+        self.proposal.observations.push(log_weight);
         Ok(val)
     }
 
+    fn factor(&mut self, log_weight: f64) -> Result<(), RuntimeError> {
+        self.proposal.observed_log_weight += log_weight;
+        Ok(())
+    }
+
     fn finish_one_evaluation(&mut self, val: Value) {
         // ratio of selecting x as the random variable
 
@@ -131,19 +253,33 @@ impl InferenceAlg for SingleSiteMetropolis {
         let accept = if let Some(last) = &self.last {
             let proposal = &self.proposal;
             // Introduction to PPL equation 4.21 (as of the version of the book in the repo in commit 105ee07cea1b61d83fcc0898cf9c5cce767bb9c0)
-            let log_domain_prev = (last.trace.len() as f64).ln(); // probability we chose x as our single site
-            let log_domain_proposal = (proposal.trace.len() as f64).ln(); // reverse probability we chose x site
+            // A run with no `sample`s left to change, as when `--choices` fixes them all, is the same every time,
+            // so it has no site to choose and is always kept.
+            let log_domain = |trace: &BTreeMap<Address, (Value, f64)>| match trace.len() {
+                0 => 0.0,
+                n => (n as f64).ln(),
+            };
+            let log_domain_prev = log_domain(&last.trace); // probability we chose x as our single site
+            let log_domain_proposal = log_domain(&proposal.trace); // reverse probability we chose x site
             let log_acceptance_ratio =
                 log_domain_prev + proposal.observed_log_weight + proposal.reused_log_weight
                     - log_domain_proposal
                     - last.observed_log_weight
                     - last.reused_log_weight;
             let acceptance_ratio = log_acceptance_ratio.exp();
-            if acceptance_ratio >= 1_f64 || thread_rng().gen::<f64>() < acceptance_ratio {
+            // A run ruled out by `condition` (or an impossible observation) must always be left, even for
+            // another impossible one, or the chain could never reach the evidence.
+            let last_impossible = last.observed_log_weight + last.reused_log_weight == f64::NEG_INFINITY;
+            self.proposals += 1;
+            if last_impossible || acceptance_ratio >= 1_f64 || rng().gen::<f64>() < acceptance_ratio {
+                self.accepted += 1;
                 self.count += 1;
                 if self.count >= self.skip {
                     self.count = 0;
                     self.samples.push(val);
+                    self.lp.push(proposal.log_joint());
+                    self.accept_stat.push(if last_impossible { 1.0 } else { acceptance_ratio.min(1.0) });
+                    self.log_likelihood.push(proposal.observations.clone());
                 }
 
                 true
@@ -154,29 +290,28 @@ impl InferenceAlg for SingleSiteMetropolis {
             true
         };
 
-        let fresh = RunMemory {
-            trace: HashMap::new(),
-            reused_log_weight: 0.,
-            observed_log_weight: 0.,
-        };
-        let prev_proposal = std::mem::replace(&mut self.proposal, fresh);
+        let prev_proposal = std::mem::replace(&mut self.proposal, RunMemory::new());
 
         if accept {
             self.last.replace(prev_proposal);
         }
 
         // Choose new single site to change the sample. From book chapter 4.2: choose new x_0.
-        let proposal_idx = thread_rng().gen_range(0..self.last.as_ref().unwrap().trace.len());
-        self.proposal_site = *self
+        let n_sites = self.last.as_ref().unwrap().trace.len();
+        if n_sites == 0 {
+            return;
+        }
+        let proposal_idx = rng().gen_range(0..n_sites);
+        self.proposal_site = self
             .last
             .as_ref()
             .unwrap()
             .trace
             .keys()
             .nth(proposal_idx)
-            .unwrap();
+            .unwrap()
+            .clone();
         self.reached_proposal_site = false;
-        self.var_counter = 0;
     }
 
     fn finalize_and_make_dataset(self) -> Result<DataFile, RuntimeError> {
@@ -185,6 +320,34 @@ impl InferenceAlg for SingleSiteMetropolis {
         Ok(DataFile {
             has_weights: false,
             data: vals,
+            sample_stats: Some(SampleStats {
+                algorithm: "single-site-metropolis",
+                thin: self.skip,
+                lp: self.lp,
+                accept_stat: self.accept_stat,
+                log_likelihood: self.log_likelihood,
+            }),
         })
     }
+
+    /// The sample stats of the draws taken are dropped with them, since they're only written at the end.
+    fn take_kept(&mut self) -> Vec<(Value, Option<f64>)> {
+        self.lp.clear();
+        self.accept_stat.clear();
+        self.log_likelihood.clear();
+        self.samples.drain(..).map(|val| (val, None)).collect()
+    }
+
+    fn kept(&self) -> (&[Value], Option<&[f64]>) {
+        (&self.samples, None)
+    }
+
+    fn acceptance_rate(&self) -> Option<f64> {
+        Some(self.accepted as f64 / self.proposals as f64).filter(|_| self.proposals > 0)
+    }
+
+    fn into_weighted_results(self) -> (Vec<Value>, Vec<f64>) {
+        let weights = vec![0.0; self.samples.len()];
+        (self.samples, weights)
+    }
 }
@@ -0,0 +1,313 @@
+use std::collections::HashMap;
+
+use crate::{
+    checkpoint::{Rebuild, SavedAddress, SavedFloat, SavedValue},
+    distributions::Distribution,
+    trace::{EventKind, Trace, TraceEvent},
+    types::{RuntimeError, Value},
+    DataFile,
+};
+
+use super::{flatten_to_numeric_vec_only, Address, InferenceAlg};
+
+/// Runs the program like `PriorOnly`, recording every `sample` and `observe`. When replaying a trace, the values
+/// in the trace are used instead of drawing new ones, wherever the run makes a choice at an address the trace has.
+pub struct Tracing {
+    results: Vec<Value>,
+    events: Vec<TraceEvent>,
+    replay: Option<Replay>,
+}
+
+/// A `sample` or `observe` from the trace being replayed.
+struct Recorded {
+    kind: EventKind,
+    address: Address,
+    distribution: String,
+    value: Value,
+    reached: bool,
+}
+
+struct Replay {
+    recorded: Vec<Recorded>,
+    by_address: HashMap<Address, usize>,
+    result: Value,
+    /// How many of the recorded choices the run has made in the same order as the trace, until it first makes a
+    /// different one.
+    in_step: Option<usize>,
+    differences: Vec<String>,
+}
+
+/// A distribution written the way values are, e.g. `normal(mu=0.0, sigma=1.0)`.
+fn show_distribution(name: &str, params: &[(String, Value)]) -> String {
+    let params: Vec<String> = params
+        .iter()
+        .map(|(name, val)| format!("{}={}", name, val))
+        .collect();
+    format!("{}({})", name, params.join(", "))
+}
+
+fn describe(kind: EventKind, address: &Address) -> String {
+    let mut description = format!(
+        "the `{}` at site {} (choice {})",
+        kind.name(),
+        address.site,
+        address.count
+    );
+    if let Some(mem_call) = &address.mem_call {
+        description.push_str(&format!(" in `(mem {})`", mem_call));
+    }
+    description
+}
+
+impl Tracing {
+    pub fn new() -> Self {
+        Self {
+            results: Vec::new(),
+            events: Vec::new(),
+            replay: None,
+        }
+    }
+
+    /// Replays `trace`, with its distributions made again by `rebuild`.
+    pub fn replaying(trace: Trace, rebuild: &mut Rebuild) -> Result<Self, RuntimeError> {
+        let mut recorded = Vec::with_capacity(trace.events.len());
+        for event in trace.events {
+            let params = event
+                .params
+                .into_iter()
+                .map(|(name, param)| Ok((name, param.restore(rebuild)?)))
+                .collect::<Result<Vec<_>, RuntimeError>>()?;
+            recorded.push(Recorded {
+                kind: event.kind,
+                address: event.address.restore(),
+                distribution: show_distribution(&event.distribution, &params),
+                value: event.value.restore(rebuild)?,
+                reached: false,
+            });
+        }
+        let by_address = recorded
+            .iter()
+            .enumerate()
+            .map(|(i, r)| (r.address.clone(), i))
+            .collect();
+
+        Ok(Self {
+            replay: Some(Replay {
+                recorded,
+                by_address,
+                result: trace.result.restore(rebuild)?,
+                in_step: Some(0),
+                differences: Vec::new(),
+            }),
+            ..Self::new()
+        })
+    }
+
+    /// The trace of the last run.
+    pub fn take_trace(&mut self) -> Option<Trace> {
+        Some(Trace {
+            events: std::mem::take(&mut self.events),
+            result: SavedValue::save(self.results.last()?),
+        })
+    }
+
+    /// Everything about the last run that differs from the trace being replayed, in the order it happened. Empty if
+    /// the run followed the trace exactly.
+    pub fn differences(&self) -> Vec<String> {
+        let replay = match &self.replay {
+            Some(replay) => replay,
+            None => return Vec::new(),
+        };
+        let mut differences = replay.differences.clone();
+
+        let unreached: Vec<&Recorded> = replay.recorded.iter().filter(|r| !r.reached).collect();
+        if let Some(first) = unreached.first() {
+            differences.push(format!(
+                "{} choice(s) in the trace were never made, starting with {}.",
+                unreached.len(),
+                describe(first.kind, &first.address)
+            ));
+        }
+        if let Some(result) = self.results.last() {
+            if result.to_string() != replay.result.to_string() {
+                differences.push(format!(
+                    "The result was {} in the trace, but is now {}.",
+                    replay.result, result
+                ));
+            }
+        }
+        differences
+    }
+
+    fn choose(
+        &mut self,
+        kind: EventKind,
+        dist: &dyn Distribution,
+        observed: Option<Value>,
+        address: Option<Address>,
+    ) -> Result<Value, RuntimeError> {
+        // Choices made while drawing from a `defdist` have no address, and are part of drawing from it.
+        let address = match address {
+            Some(address) => address,
+            None => return dist.sample(),
+        };
+
+        let params: Vec<(String, Value)> = dist
+            .params()
+            .into_iter()
+            .map(|(name, val)| (name.to_owned(), val))
+            .collect();
+        let distribution = show_distribution(dist.name(), &params);
+        let forced = match &mut self.replay {
+            Some(replay) => replay.force(kind, &distribution, &address),
+            None => None,
+        };
+        let (value, log_prob) = match forced {
+            Some(value) => match dist.log_pdf(&value) {
+                Ok(log_prob) => (value, log_prob),
+                // The distribution has changed to one the traced value can't come from.
+                Err(_) => {
+                    if let Some(replay) = &mut self.replay {
+                        replay.differences.push(format!(
+                            "{} was {} in the trace, which {} can't give, so a new value was drawn.",
+                            capitalise(&describe(kind, &address)),
+                            value,
+                            distribution
+                        ));
+                    }
+                    let value = dist.sample()?;
+                    let log_prob = dist.log_pdf(&value)?;
+                    (value, log_prob)
+                }
+            },
+            None => {
+                let value = dist.sample()?;
+                let log_prob = dist.log_pdf(&value)?;
+                (value, log_prob)
+            }
+        };
+
+        self.events.push(TraceEvent {
+            kind,
+            address: SavedAddress::save(&address),
+            distribution: dist.name().to_owned(),
+            params: params
+                .iter()
+                .map(|(name, val)| (name.clone(), SavedValue::save(val)))
+                .collect(),
+            value: SavedValue::save(&value),
+            observed: observed.as_ref().map(SavedValue::save),
+            log_prob: SavedFloat(log_prob),
+        });
+        Ok(value)
+    }
+}
+
+impl Replay {
+    /// The value the trace has for a choice, noting where the run differs from the trace.
+    fn force(&mut self, kind: EventKind, distribution: &str, address: &Address) -> Option<Value> {
+        // Choices are counted from 0 here, as they are in addresses.
+        if let Some(n) = self.in_step {
+            match self.recorded.get(n) {
+                Some(r) if r.kind == kind && r.address == *address => self.in_step = Some(n + 1),
+                Some(r) => {
+                    self.differences.push(format!(
+                        "The run leaves the trace at its choice {}: the trace has {} there, but the program made {}.",
+                        n,
+                        describe(r.kind, &r.address),
+                        describe(kind, address)
+                    ));
+                    self.in_step = None;
+                }
+                None => {
+                    self.differences.push(format!(
+                        "The run leaves the trace at its choice {}, {}, after the last choice in the trace.",
+                        n,
+                        describe(kind, address)
+                    ));
+                    self.in_step = None;
+                }
+            }
+        }
+
+        let r = match self.by_address.get(address) {
+            Some(&i) if self.recorded[i].kind == kind => &mut self.recorded[i],
+            _ => {
+                self.differences.push(format!(
+                    "{} isn't in the trace, so a new value was drawn from {}.",
+                    capitalise(&describe(kind, address)),
+                    distribution
+                ));
+                return None;
+            }
+        };
+        r.reached = true;
+        if r.distribution != distribution {
+            self.differences.push(format!(
+                "{} was from {} in the trace, but is now from {}.",
+                capitalise(&describe(kind, address)),
+                r.distribution,
+                distribution
+            ));
+        }
+        Some(r.value.clone())
+    }
+}
+
+fn capitalise(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+impl InferenceAlg for Tracing {
+    fn sample(
+        &mut self,
+        dist: &dyn Distribution,
+        address: Option<Address>,
+    ) -> Result<Value, RuntimeError> {
+        self.choose(EventKind::Sample, dist, None, address)
+    }
+
+    fn observe(
+        &mut self,
+        dist: &dyn Distribution,
+        val: Value,
+        address: Option<Address>,
+    ) -> Result<Value, RuntimeError> {
+        self.choose(EventKind::Observe, dist, Some(val), address)
+    }
+
+    fn factor(&mut self, _log_weight: f64) -> Result<(), RuntimeError> {
+        Ok(())
+    }
+
+    fn finish_one_evaluation(&mut self, result: Value) {
+        self.results.push(result);
+    }
+
+    fn finalize_and_make_dataset(self) -> Result<DataFile, RuntimeError> {
+        let vals = flatten_to_numeric_vec_only(self.results)?;
+
+        Ok(DataFile {
+            has_weights: false,
+            data: vals,
+            sample_stats: None,
+        })
+    }
+
+    fn take_kept(&mut self) -> Vec<(Value, Option<f64>)> {
+        self.results.drain(..).map(|val| (val, None)).collect()
+    }
+
+    fn kept(&self) -> (&[Value], Option<&[f64]>) {
+        (&self.results, None)
+    }
+
+    fn into_weighted_results(self) -> (Vec<Value>, Vec<f64>) {
+        let weights = vec![0.0; self.results.len()];
+        (self.results, weights)
+    }
+}
@@ -1,12 +1,20 @@
 use crate::{
     ast::{self, Expression, ForEach, Ident, Let, Program},
-    inference::InferenceAlg,
+    choices::ChoiceMap,
+    data,
+    distributions::Empirical,
+    inference::{
+        likelihood_weighting::LikelihoodWeighting, prior_only::PriorOnly,
+        single_site_metropolis::SingleSiteMetropolis, Address, InferenceAlg,
+    },
+    matrix::Matrix,
     types::{RuntimeError, Value},
 };
 
 
-use std::{collections::HashMap, convert::TryFrom, rc::Rc};
+use std::{collections::HashMap, convert::TryFrom, path::Path, rc::Rc};
 
+#[derive(Clone)]
 pub struct Binding {
     pub ident: String,
     pub val: Value,
@@ -17,6 +25,58 @@ pub struct Function {
     pub body: Expression,
 }
 
+/// A distribution declared with `defdist`.
+pub struct DistributionDef {
+    // `Distribution::name` and `params` return static strings, so the names are leaked once when the
+    // program is loaded.
+    pub name: &'static str,
+    pub parameters: Vec<&'static str>,
+    pub sampler: Expression,
+    pub value: Ident,
+    pub log_density: Expression,
+}
+
+/// Everything the program declares before its expression. Shared with user-defined distributions, which
+/// evaluate their bodies outside of the interpreter that created them.
+#[derive(Default)]
+pub struct Definitions {
+    pub functions: HashMap<String, Rc<Function>>,
+    pub distributions: HashMap<String, Rc<DistributionDef>>,
+    /// Values bound by `--data`, which every expression can see, including inside functions and distributions.
+    pub data: Vec<Binding>,
+}
+
+/// A literal whose elements are rows of floats of the same length is made a matrix once, here, rather than by every
+/// `mat-*` call it's given to. Rows of integers are left alone, since a matrix would make them floats.
+fn vector_literal(elements: Vec<Value>) -> Value {
+    let n_cols = match elements.first() {
+        Some(Value::Vector(row)) if !row.is_empty() => row.len(),
+        _ => return Value::Vector(elements),
+    };
+    let is_row = |val: &Value| match val {
+        Value::Vector(row) => row.len() == n_cols && row.iter().all(|x| matches!(x, Value::Float(_))),
+        _ => false,
+    };
+    if !elements.iter().all(is_row) {
+        return Value::Vector(elements);
+    }
+
+    let mut data = Vec::with_capacity(elements.len() * n_cols);
+    for row in &elements {
+        if let Value::Vector(row) = row {
+            data.extend(row.iter().filter_map(|x| match x {
+                Value::Float(x) => Some(*x),
+                _ => None,
+            }));
+        }
+    }
+    Value::Matrix(Rc::new(Matrix::new(elements.len(), n_cols, data)))
+}
+
+fn leak(s: String) -> &'static str {
+    Box::leak(s.into_boxed_str())
+}
+
 fn traverse_expr<F: FnMut(&mut Expression)>(expr: &mut Expression, f: &mut F) {
     f(expr);
     match expr {
@@ -29,7 +89,7 @@ fn traverse_expr<F: FnMut(&mut Expression)>(expr: &mut Expression, f: &mut F) {
                 traverse_expr(e, f);
             }
         }
-        Expression::Sample(expr, _number) => {
+        Expression::Sample(expr, _) | Expression::Factor(expr) | Expression::Condition(expr) => {
             traverse_expr(expr, f);
         }
         Expression::Observe(e1, e2, _number) => {
@@ -41,7 +101,7 @@ fn traverse_expr<F: FnMut(&mut Expression)>(expr: &mut Expression, f: &mut F) {
             traverse_expr(e2, f);
             traverse_expr(e3, f);
         }
-        Expression::FunctionApplication(_, parameters) => {
+        Expression::FunctionApplication(_, parameters) | Expression::Mem(_, parameters) => {
             for e in parameters {
                 traverse_expr(e, f);
             }
@@ -74,7 +134,13 @@ fn traverse_expr<F: FnMut(&mut Expression)>(expr: &mut Expression, f: &mut F) {
                 traverse_expr(e, f);
             }
         }
+        Expression::Infer(ast::Infer { params, .. }) => {
+            for e in params {
+                traverse_expr(e, f);
+            }
+        }
         Expression::Null
+        | Expression::LoadEmpirical(_)
         | Expression::Variable(_)
         | Expression::Boolean(_)
         | Expression::Integer(_)
@@ -82,7 +148,22 @@ fn traverse_expr<F: FnMut(&mut Expression)>(expr: &mut Expression, f: &mut F) {
     }
 }
 
-fn assign_variable_numbers(program: &mut Program) {
+/// Calls `f` on every expression in the program: the main expression first, then each function's body.
+pub(crate) fn traverse_program<F: FnMut(&mut Expression)>(program: &mut Program, f: &mut F) {
+    traverse_expr(&mut program.expression, f);
+
+    for ast::Definition {
+        ident: _,
+        params: _,
+        body,
+    } in program.definitions.iter_mut()
+    {
+        traverse_expr(body, f);
+    }
+}
+
+/// Numbers every `sample` and `observe` with its site. Numbering a program again gives the same sites.
+pub(crate) fn assign_variable_numbers(program: &mut Program) {
     let counter = &mut 0;
 
     let mut assign_number_to_random_variable_expressions = |expr: &mut Expression| match expr {
@@ -97,19 +178,7 @@ fn assign_variable_numbers(program: &mut Program) {
         _ => {}
     };
 
-    traverse_expr(
-        &mut program.expression,
-        &mut assign_number_to_random_variable_expressions,
-    );
-
-    for ast::Definition {
-        ident: _,
-        params: _,
-        body,
-    } in program.definitions.iter_mut()
-    {
-        traverse_expr(body, &mut assign_number_to_random_variable_expressions);
-    }
+    traverse_program(program, &mut assign_number_to_random_variable_expressions);
 }
 
 pub(crate) struct Interpreter<'alg, T>
@@ -119,19 +188,74 @@ where
     // TODO some mutable state for the observe side effects.
     // observe_state: u64,
     pub scope: Vec<Binding>,
-    pub functions: HashMap<String, Rc<Function>>,
+    pub definitions: Rc<Definitions>,
     pub inference_alg: &'alg mut T,
+    // Results of `mem` calls in the current run, by the call's key.
+    mem_cache: HashMap<String, Value>,
+    // The memoised calls being evaluated, innermost last, each with the number of random choices made in it
+    // so far. The first entry is for choices outside of any call.
+    mem_calls: Vec<(Option<Rc<str>>, usize)>,
+    // Distributions read by `load-empirical`, by path, so each file is only read once.
+    loaded: HashMap<String, Value>,
+    // The `sample`s fixed by `--choices`. Only the program's own runs are constrained, not those of `infer`.
+    choices: ChoiceMap,
 }
 
 impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
-    pub fn new(inference_alg: &'alg mut T) -> Self {
+    /// An interpreter for a whole program, with the values bound by `--data` in scope.
+    pub fn with_data(inference_alg: &'alg mut T, data: Vec<Binding>) -> Self {
+        let definitions = Definitions {
+            data,
+            ..Definitions::default()
+        };
+        Self::with_definitions(inference_alg, Rc::new(definitions))
+    }
+
+    pub fn with_definitions(inference_alg: &'alg mut T, definitions: Rc<Definitions>) -> Self {
         Interpreter {
-            functions: HashMap::new(),
+            definitions,
             scope: Vec::new(),
             inference_alg,
+            mem_cache: HashMap::new(),
+            mem_calls: vec![(None, 0)],
+            loaded: HashMap::new(),
+            choices: ChoiceMap::default(),
         }
     }
 
+    /// Observes every `sample` that `choices` has a value for, instead of drawing it.
+    pub fn constrain(&mut self, choices: ChoiceMap) {
+        self.choices = choices;
+    }
+
+    /// Warns about each key of the choice map that hasn't matched any choice the runs so far have made, since it
+    /// conditions nothing.
+    pub fn warn_unmatched_choices(&self) {
+        for key in self.choices.unmatched() {
+            eprintln!(
+                "Warning: `{}` in the choice map never matched a random choice the program made, so it was ignored.",
+                key
+            );
+        }
+    }
+
+    /// Forgets everything memoised by the last run, so that each run makes its own random choices.
+    fn start_run(&mut self) {
+        self.mem_cache.clear();
+        self.mem_calls = vec![(None, 0)];
+    }
+
+    fn next_address(&mut self, site: usize) -> Address {
+        let (mem_call, count) = self.mem_calls.last_mut().unwrap();
+        let address = Address {
+            mem_call: mem_call.clone(),
+            site,
+            count: *count,
+        };
+        *count += 1;
+        address
+    }
+
     fn lookup_var(&self, var: &Ident) -> Option<Value> {
         for Binding { ident, val } in self.scope.iter().rev() {
             if *ident == var.0 {
@@ -139,16 +263,39 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
             }
         }
 
-        None
+        self.definitions
+            .data
+            .iter()
+            .find(|binding| binding.ident == var.0)
+            .map(|binding| binding.val.clone())
+    }
+
+    pub fn eval_program(&mut self, program: Program, n_samples: usize) -> Result<(), RuntimeError> {
+        self.eval_program_with(program, n_samples, |_| Ok(()))
     }
 
-    pub fn eval_program(
+    /// Like `eval_program`, also calling `after_each` with the inference algorithm after every run.
+    pub fn eval_program_with<F>(
         &mut self,
-        mut program: Program,
+        program: Program,
         n_samples: usize,
-    ) -> Result<(), RuntimeError> {
+        after_each: F,
+    ) -> Result<(), RuntimeError>
+    where
+        F: FnMut(&mut T) -> Result<(), RuntimeError>,
+    {
+        let expression = self.load_program(program);
+        self.eval_loaded(&expression, n_samples, after_each)
+    }
+
+    /// Makes the program's `defn`s and `defdist`s callable, returning the expression to run.
+    pub fn load_program(&mut self, mut program: Program) -> Expression {
         assign_variable_numbers(&mut program);
 
+        let mut definitions = Definitions {
+            data: self.definitions.data.clone(),
+            ..Definitions::default()
+        };
         for ast::Definition {
             ident,
             params,
@@ -160,15 +307,48 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
                 parameters: params,
                 body,
             };
-            self.functions.insert(name, Rc::new(function));
+            definitions.functions.insert(name, Rc::new(function));
+        }
+
+        for ast::DistributionDefinition {
+            ident,
+            params,
+            sampler,
+            value,
+            log_density,
+        } in program.distributions
+        {
+            let Ident(name) = ident;
+            let distribution = DistributionDef {
+                name: leak(name.clone()),
+                parameters: params.into_iter().map(|Ident(p)| leak(p)).collect(),
+                sampler,
+                value,
+                log_density,
+            };
+            definitions.distributions.insert(name, Rc::new(distribution));
         }
+        self.definitions = Rc::new(definitions);
+
+        program.expression
+    }
 
-        let expression = program.expression;
+    /// Runs an expression from `load_program` `n_samples` times.
+    pub fn eval_loaded<F>(
+        &mut self,
+        expression: &Expression,
+        n_samples: usize,
+        mut after_each: F,
+    ) -> Result<(), RuntimeError>
+    where
+        F: FnMut(&mut T) -> Result<(), RuntimeError>,
+    {
         (0..n_samples)
             .try_for_each(|_i| {
-                let val = self.eval(&expression)?;
+                self.start_run();
+                let val = self.eval(expression)?;
                 self.inference_alg.finish_one_evaluation(val);
-                Ok(())
+                after_each(self.inference_alg)
             })
     }
 
@@ -217,7 +397,17 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
                 let val = self.eval(expr)?;
                 match val {
                     Value::Distribution(d) => {
-                        self.inference_alg.sample(d.as_ref(), number.map(|n| n))
+                        let address = number.map(|n| self.next_address(n));
+                        let chosen = address.as_ref().and_then(|a| self.choices.get(a)).cloned();
+                        match chosen {
+                            // `PriorOnly` draws every observation from the prior, so the chosen value is given
+                            // to the program rather than whatever `observe` returns.
+                            Some(val) => {
+                                self.inference_alg.observe(d.as_ref(), val.clone(), address)?;
+                                Ok(val)
+                            }
+                            None => self.inference_alg.sample(d.as_ref(), address),
+                        }
                     }
                     _ => Err(RuntimeError::new(
                         "Sample must only be called on a Distribution value.".to_owned(),
@@ -228,6 +418,20 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
                 let vals = self.eval_all(args)?;
                 self.dispatch_function(&ident.0, vals)
             }
+            Expression::Mem(ident, args) => {
+                let vals = self.eval_all(args)?;
+                let key = format!("{} {}", ident.0, Value::Vector(vals.clone()));
+                if let Some(val) = self.mem_cache.get(&key) {
+                    return Ok(val.clone());
+                }
+
+                self.mem_calls.push((Some(Rc::from(key.as_str())), 0));
+                let val = self.dispatch_function(&ident.0, vals);
+                self.mem_calls.pop();
+                let val = val?;
+                self.mem_cache.insert(key, val.clone());
+                Ok(val)
+            }
             // Expression::Division(left, right) => {}
             // Expression::Subtraction(left, right) => {}
             // Expression::Negation(expr) => {}
@@ -243,13 +447,27 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
                 };
                 let val = self.eval(val)?;
 
-                let val = self
-                    .inference_alg
-                    .observe(dist.as_ref(), val, number.map(|n| n))?;
+                let address = number.map(|n| self.next_address(n));
+                let val = self.inference_alg.observe(dist.as_ref(), val, address)?;
 
                 // observe does nothing for now
                 Ok(val)
             }
+            Expression::Factor(log_weight) => {
+                let log_weight = self
+                    .eval(log_weight)?
+                    .try_into_numeric("`factor` must be given a number.")?;
+                self.inference_alg.factor(log_weight)?;
+                Ok(Value::Float(log_weight))
+            }
+            Expression::Condition(comp) => {
+                let comp = self
+                    .eval(comp)?
+                    .try_into_bool("`condition` expression must eval to a boolean.")?;
+                let log_weight = if comp { 0.0 } else { f64::NEG_INFINITY };
+                self.inference_alg.factor(log_weight)?;
+                Ok(Value::Boolean(comp))
+            }
             Expression::ForEach(l) => {
                 let ast::ForEach {
                     n_iters,
@@ -272,6 +490,14 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
                                     v
                                 }
                             }
+                            Value::Matrix(m) => {
+                                if m.n_rows() != n_iters {
+                                    return err!(
+                                        "`foreach` binding vectors must have the specified length."
+                                    );
+                                }
+                                m.rows_as_values()
+                            }
                             _ => return err!("`foreach` binding values must be vectors."),
                         };
                         Ok((ident.0.to_string(), val))
@@ -309,12 +535,40 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
                         RuntimeError::new("Loop index overflow. Shouldn't be possible.".to_string())
                     })?;
                     let mut args = vec![Value::Integer(idx), accumulator];
-                    args.extend(params.clone().into_iter());
+                    args.extend(params.clone());
                     accumulator = self.dispatch_function(&fn_name.0, args)?;
                 }
 
                 Ok(accumulator)
             }
+            Expression::Infer(ast::Infer {
+                alg,
+                n_samples,
+                fn_name,
+                params,
+            }) => {
+                let args = self.eval_all(params)?;
+                let (n_samples, fn_name) = (*n_samples, fn_name.0.as_str());
+                match alg.0.as_str() {
+                    "prior-only" => self.eval_infer(PriorOnly::new(), n_samples, fn_name, args),
+                    "likelihood-weighting" => {
+                        self.eval_infer(LikelihoodWeighting::new(), n_samples, fn_name, args)
+                    }
+                    "single-site-metropolis" => {
+                        self.eval_infer(SingleSiteMetropolis::new(1), n_samples, fn_name, args)
+                    }
+                    other => err!("`{}` is not an inference algorithm `infer` can run.", other),
+                }
+            }
+            Expression::LoadEmpirical(path) => {
+                if let Some(val) = self.loaded.get(path) {
+                    return Ok(val.clone());
+                }
+                let (values, log_weights) = data::read_results(Path::new(path))?;
+                let val = Value::Distribution(Rc::new(Empirical::new(values, log_weights)?));
+                self.loaded.insert(path.clone(), val.clone());
+                Ok(val)
+            }
             Expression::If(comp, true_branch, false_branch) => {
 
                 let comp = self.eval(comp)?;
@@ -327,13 +581,36 @@ impl<'alg, T: InferenceAlg> Interpreter<'alg, T> {
                     self.eval(false_branch)
                 }
             }
-            Expression::Vector(elements) => Ok(Value::Vector(self.eval_all(elements)?)),
+            Expression::Vector(elements) => Ok(vector_literal(self.eval_all(elements)?)),
             // Expression::HashMap(pairs) => {}
             Expression::Boolean(val) => Ok(Value::Boolean(*val)),
             x => Err(RuntimeError::new(format!("Unimplemented: {:?}", x))),
         }
     }
 
+    /// Runs `fn_name` on `args` `n_samples` times with a fresh inference algorithm, so that weights from its
+    /// `observe`s never reach this interpreter's algorithm.
+    fn eval_infer<A: InferenceAlg>(
+        &mut self,
+        mut alg: A,
+        n_samples: usize,
+        fn_name: &str,
+        args: Vec<Value>,
+    ) -> Result<Value, RuntimeError> {
+        let mut interpreter = Interpreter::with_definitions(&mut alg, self.definitions.clone());
+        for _ in 0..n_samples {
+            interpreter.start_run();
+            let val = interpreter.dispatch_function(fn_name, args.clone())?;
+            interpreter.inference_alg.finish_one_evaluation(val);
+        }
+
+        let (values, log_weights) = alg.into_weighted_results();
+        if values.is_empty() {
+            return err!("`infer` of `{}` didn't keep any samples.", fn_name);
+        }
+        Ok(Value::Distribution(Rc::new(Empirical::new(values, log_weights)?)))
+    }
+
     pub fn eval_all(&mut self, args: &[ast::Expression]) -> Result<Vec<Value>, RuntimeError> {
         let mut vals = Vec::with_capacity(args.len());
         for arg in args {
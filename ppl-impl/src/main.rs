@@ -8,12 +8,27 @@ macro_rules! err {
 type EvalResult = Result<Value, RuntimeError>;
 
 mod ast;
+mod checker;
+mod checkpoint;
+mod choices;
+mod data;
 mod functions;
+mod graph;
+mod output;
+mod plot;
+mod progress;
+mod random;
+mod summary;
+mod trace;
 mod types;
 
 use std::{ffi::OsStr, path::{Path, PathBuf}};
 
 use ast::Program;
+use choices::ChoiceMap;
+use data::DataArg;
+use output::Format;
+use progress::Progress;
 use clap::{AppSettings, Clap};
 use inference::likelihood_weighting::LikelihoodWeighting;
 use lalrpop_util::lalrpop_mod;
@@ -25,14 +40,19 @@ mod ancestral_sampler;
 mod distributions;
 mod inference;
 mod interpreter;
+mod matrix;
+mod shapes;
 
-use interpreter::Interpreter;
+use interpreter::{Binding, Interpreter};
 
 lalrpop_mod!(#[allow(clippy::all)] pub grammar);
 
 #[derive(Clap)]
 #[clap(setting = AppSettings::ColoredHelp)]
 struct Opts {
+    /// Don't print the parsed program, or show progress while inference runs.
+    #[clap(long, global = true)]
+    quiet: bool,
     #[clap(subcommand)]
     cmd: Command,
 }
@@ -52,33 +72,174 @@ enum Command {
     PriorOnly {
         #[clap(short, long, default_value = "10000")]
         n_samples: usize,
+        /// Where to write the results. Defaults to `data/<file stem>.<format>`.
+        #[clap(long)]
+        out: Option<PathBuf>,
+        /// One of json, csv, ndjson, arrow, stan-csv or arviz. Defaults to the extension of `--out`, or json.
+        #[clap(long)]
+        format: Option<Format>,
+        /// Write each result to the output as soon as it is kept, instead of all at the end. Only for ndjson and
+        /// csv.
+        #[clap(long)]
+        stream: bool,
+        /// How many results to write between flushes of the output, when streaming.
+        #[clap(long, default_value = "1000")]
+        flush_every: usize,
+        /// Binds the contents of a JSON or CSV file, as `name=path`, or each of its keys or columns, as `path`.
+        #[clap(long, number_of_values = 1)]
+        data: Vec<DataArg>,
+        /// Seeds the random number generator, so that a run can be repeated exactly.
+        #[clap(long)]
+        seed: Option<u64>,
+        /// Print summary statistics of the results once they're written.
+        #[clap(long)]
+        summarize: bool,
+        /// A JSON object of values for some of the program's `sample`s, which are observed instead of drawn. Keys
+        /// are names bound by `let` to a `sample`, or addresses as in traces: `3` for every choice at site 3, `3/5`
+        /// for the one there after 5 other random choices in the run, and `effect [1]/3/0` inside `(mem effect 1)`.
+        #[clap(long)]
+        choices: Option<PathBuf>,
         file: PathBuf,
     },
     Infer {
         #[clap(short, long, default_value = "10000")]
         n_samples: usize,
+        /// Where to write the results. Defaults to `data/<file stem>.<format>`.
+        #[clap(long)]
+        out: Option<PathBuf>,
+        /// One of json, csv, ndjson, arrow, stan-csv or arviz. Defaults to the extension of `--out`, or json.
+        #[clap(long)]
+        format: Option<Format>,
+        /// Write each result to the output as soon as it is kept, instead of all at the end. Only for ndjson and
+        /// csv.
+        #[clap(long)]
+        stream: bool,
+        /// How many results to write between flushes of the output, when streaming.
+        #[clap(long, default_value = "1000")]
+        flush_every: usize,
+        /// Binds the contents of a JSON or CSV file, as `name=path`, or each of its keys or columns, as `path`.
+        #[clap(long, number_of_values = 1)]
+        data: Vec<DataArg>,
+        /// Seeds the random number generator, so that a run can be repeated exactly.
+        #[clap(long)]
+        seed: Option<u64>,
+        /// Print summary statistics of the results once they're written.
+        #[clap(long)]
+        summarize: bool,
+        /// A JSON object of values for some of the program's `sample`s, which are observed instead of drawn. Keys
+        /// are names bound by `let` to a `sample`, or addresses as in traces: `3` for every choice at site 3, `3/5`
+        /// for the one there after 5 other random choices in the run, and `effect [1]/3/0` inside `(mem effect 1)`.
+        #[clap(long)]
+        choices: Option<PathBuf>,
+        /// Save the state of a single-site-metropolis chain to this file every `--checkpoint-every` runs, so
+        /// that it can be carried on with `--resume`.
+        #[clap(long)]
+        checkpoint: Option<PathBuf>,
+        #[clap(long, default_value = "1000")]
+        checkpoint_every: usize,
+        /// Carry on the chain saved in this checkpoint, with its number of samples and skip, until it has made
+        /// them all. Keeps saving to the same file, unless `--checkpoint` is given.
+        #[clap(long)]
+        resume: Option<PathBuf>,
         #[clap(subcommand)]
         alg: Alg,
         file: PathBuf,
     },
     EvalOnce {
+        /// Binds the contents of a JSON or CSV file, as `name=path`, or each of its keys or columns, as `path`.
+        #[clap(long, number_of_values = 1)]
+        data: Vec<DataArg>,
+        /// Write every `sample` and `observe` the run makes to this file, with its address, distribution, value and
+        /// log density, so that the run can be repeated with `replay`.
+        #[clap(long)]
+        trace: Option<PathBuf>,
         file: PathBuf,
     },
+    /// Run the program once with the values in a trace written by `eval-once --trace`, at each address the trace
+    /// has, and report where the run differs from the trace if the program has changed since.
+    Replay {
+        /// Binds the contents of a JSON or CSV file, as `name=path`, or each of its keys or columns, as `path`.
+        #[clap(long, number_of_values = 1)]
+        data: Vec<DataArg>,
+        file: PathBuf,
+        trace: PathBuf,
+    },
     AncestralSample {
         file: PathBuf,
     },
+    /// Print the mean, standard deviation, quantiles, 95% HPD interval and mode of each number in the results
+    /// written by `infer` or `prior-only`, weighted if the results are.
+    Summarize {
+        /// Print the summary as JSON instead of a table.
+        #[clap(long)]
+        json: bool,
+        /// A JSON data file.
+        file: PathBuf,
+    },
+    /// Draw histograms of results written by `infer` or `prior-only`, with all of them in a grid in `all.<format>`,
+    /// and trace and autocorrelation plots of unweighted results.
+    Plot {
+        /// The JSON data files to plot. Defaults to every one in `data/`.
+        files: Vec<PathBuf>,
+        /// The directory to write the charts to.
+        #[clap(long, default_value = "charts")]
+        out_dir: PathBuf,
+        /// png or svg.
+        #[clap(long, default_value = "png")]
+        format: plot::ImageFormat,
+    },
+    /// Print the program's graphical model: each random choice the program makes, the distribution it's drawn
+    /// from, and the random choices that distribution depends on.
+    Graph {
+        /// Write the graph in Graphviz's DOT language, to be drawn with e.g. `dot -Tpng`.
+        #[clap(long)]
+        dot: bool,
+        /// Draw each `foreach` as a plate around one copy of its body, instead of unrolling it. Only with `--dot`.
+        #[clap(long)]
+        plates: bool,
+        /// Binds the contents of a JSON or CSV file, as `name=path`, or each of its keys or columns, as `path`.
+        #[clap(long, number_of_values = 1)]
+        data: Vec<DataArg>,
+        file: PathBuf,
+    },
+    /// Check the program for unknown identifiers, wrong numbers of arguments and type errors, without running it.
+    Check {
+        /// Also print the inferred type and shape of every `let` binding.
+        #[clap(long)]
+        shapes: bool,
+        /// Binds the contents of a JSON or CSV file, as `name=path`, or each of its keys or columns, as `path`.
+        #[clap(long, number_of_values = 1)]
+        data: Vec<DataArg>,
+        file: PathBuf,
+    },
 }
 
 use serde::Serialize;
 
 use crate::inference::{
-    prior_only::PriorOnly, single_site_metropolis::SingleSiteMetropolis, InferenceAlg,
+    prior_only::PriorOnly, single_site_metropolis::SingleSiteMetropolis, tracing::Tracing, InferenceAlg,
 };
 
 #[derive(Debug, Serialize)]
 pub struct DataFile {
     pub has_weights: bool,
     pub data: Vec<ProgramResult>,
+    /// Only written by the `stan-csv` and `arviz` formats, so the JSON data file is the same for every algorithm.
+    #[serde(skip)]
+    pub sample_stats: Option<SampleStats>,
+}
+
+/// What an MCMC algorithm knows about each draw it kept, besides the program's result.
+#[derive(Debug)]
+pub struct SampleStats {
+    pub algorithm: &'static str,
+    pub thin: usize,
+    /// The log density of the whole trace, including the observations.
+    pub lp: Vec<f64>,
+    /// The probability of accepting the proposal which led to the draw.
+    pub accept_stat: Vec<f64>,
+    /// The log density of each `observe` in the draw, in the order they were made.
+    pub log_likelihood: Vec<Vec<f64>>,
 }
 
 #[derive(Debug, Serialize)]
@@ -86,6 +247,23 @@ pub struct DataFile {
 pub enum ProgramResult {
     One(ResultValue),
     Many(Vec<ProgramResult>),
+    Distribution(DistributionResult),
+}
+
+/// A distribution returned by a program, written as `{"distribution": "normal", "params": {"mu": 0.0, ...}}`.
+#[derive(Debug, Serialize)]
+pub struct DistributionResult {
+    pub distribution: &'static str,
+    #[serde(serialize_with = "serialize_params")]
+    pub params: Vec<(&'static str, ProgramResult)>,
+}
+
+/// Writes the parameters as a map, keeping the order the distribution's built-in takes them in.
+fn serialize_params<S: serde::Serializer>(
+    params: &[(&'static str, ProgramResult)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(params.iter().map(|(name, val)| (name, val)))
 }
 
 #[derive(Debug, Serialize)]
@@ -99,6 +277,27 @@ pub enum ResultValue {
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     let opts: Opts = Opts::parse();
 
+    // The commands that read results rather than a program.
+    match &opts.cmd {
+        Command::Summarize { json, file } => {
+            if let Err(e) = summarize(file, *json) {
+                eprintln!("{}", e);
+            }
+            return Ok(());
+        }
+        Command::Plot {
+            files,
+            out_dir,
+            format,
+        } => {
+            if let Err(e) = plot(files, out_dir, *format) {
+                eprintln!("{}", e);
+            }
+            return Ok(());
+        }
+        _ => {}
+    }
+
     let file_name = file_name(&opts);
     let _file_stem = match file_stem(file_name) {
         Some(s) => s,
@@ -112,32 +311,274 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let text: &'static str = Box::leak(std::fs::read_to_string(file_name)?.into_boxed_str());
 
     let parser = grammar::ProgramParser::new();
-    let program = parser.parse(&text)?;
-    println!("{:#?}", program);
+    let mut program = parser.parse(text)?;
+    // The graph is printed on its own, so that it can be piped to `dot`.
+    let graph = matches!(opts.cmd, Command::Graph { .. });
+    if !opts.quiet && !graph {
+        println!("{:#?}", program);
+    }
+
+    let data = match data::read_data(data_args(&opts)) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Ok(());
+        }
+    };
+
+    // Every command runs the checker first, so that mistakes are reported before any samples are drawn.
+    let report = checker::check_program(&program, &data);
+    if let Command::Check { shapes: true, .. } = opts.cmd {
+        for binding in report.bindings.iter() {
+            println!("In {}: {} : {}", binding.context, binding.name, binding.t);
+        }
+    }
+    if !report.errors.is_empty() {
+        for e in report.errors.iter() {
+            eprintln!("{}", e);
+        }
+        eprintln!("Found {} error(s).", report.errors.len());
+        // So that `check` can be used in scripts, and a program which is refused isn't taken to have run.
+        std::process::exit(1);
+    }
+    if let Command::Check { .. } = opts.cmd {
+        println!(
+            "No errors found. The program's result type is {}.",
+            report.result_type
+        );
+    }
 
     match opts.cmd {
-        Command::EvalOnce { file } => eval_once(program, &file),
-        Command::PriorOnly { n_samples, file } => infer(program, &file, n_samples, PriorOnly::new()),
+        Command::EvalOnce { trace, .. } => eval_once(program, data, trace),
+        Command::Replay { trace, .. } => replay(program, data, &trace),
+        Command::PriorOnly {
+            n_samples,
+            file,
+            out,
+            format,
+            stream,
+            flush_every,
+            seed,
+            summarize,
+            choices,
+            ..
+        } => {
+            if let Some(seed) = seed {
+                random::seed(seed);
+            }
+            let flush_every = Some(flush_every).filter(|_| stream);
+            let out = match prepare_output(&file, out, format, flush_every, false, summarize) {
+                Ok(out) => out,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(());
+                }
+            };
+            let choices = match read_choices(choices, &mut program) {
+                Ok(choices) => choices,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(());
+                }
+            };
+            infer(program, data, choices, out, n_samples, PriorOnly::new(), opts.quiet)
+        }
         Command::Infer {
             alg,
             file,
             n_samples,
-        } => match alg {
-            Alg::LikelihoodWeighting => infer(program, &file, n_samples, LikelihoodWeighting::new()),
-            Alg::SingleSiteMetropolis { skip } => {
-                infer(program, &file, n_samples, SingleSiteMetropolis::new(skip))
+            out,
+            format,
+            stream,
+            flush_every,
+            seed,
+            summarize,
+            checkpoint,
+            checkpoint_every,
+            resume,
+            choices,
+            ..
+        } => {
+            if let Some(seed) = seed {
+                random::seed(seed);
+            }
+            let flush_every = Some(flush_every).filter(|_| stream);
+            let mcmc = alg != Alg::LikelihoodWeighting;
+            let out = match prepare_output(&file, out, format, flush_every, mcmc, summarize) {
+                Ok(out) => out,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(());
+                }
+            };
+            let checkpoints =
+                prepare_checkpoints(text, checkpoint, checkpoint_every, resume, &alg, stream);
+            let checkpoints = match checkpoints {
+                Ok(checkpoints) => checkpoints,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(());
+                }
+            };
+            let choices = match read_choices(choices, &mut program) {
+                Ok(choices) => choices,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(());
+                }
+            };
+            match alg {
+                Alg::LikelihoodWeighting => {
+                    infer(program, data, choices, out, n_samples, LikelihoodWeighting::new(), opts.quiet)
+                }
+                Alg::SingleSiteMetropolis { skip } => match checkpoints {
+                    Some(checkpoints) => infer_with_checkpoints(
+                        program,
+                        data,
+                        choices,
+                        out,
+                        n_samples,
+                        skip,
+                        checkpoints,
+                        opts.quiet,
+                    ),
+                    None => infer(
+                        program,
+                        data,
+                        choices,
+                        out,
+                        n_samples,
+                        SingleSiteMetropolis::new(skip),
+                        opts.quiet,
+                    ),
+                },
             }
-        },
+        }
         Command::AncestralSample { .. } => unimplemented!("Inference not implemented yet."),
+        Command::Graph { dot, plates, .. } => {
+            match graph::compile(&program, &data) {
+                Ok(pgm) if dot => print!("{}", pgm.to_dot(plates)),
+                Ok(pgm) => print!("{}", pgm),
+                Err(e) => eprintln!("{}", e),
+            }
+            Ok(())
+        }
+        Command::Summarize { .. } | Command::Plot { .. } => {
+            unreachable!("Handled before the program is read.")
+        }
+        Command::Check { .. } => Ok(()),
     }
 }
 
 fn file_name(opts: &Opts) -> &Path {
     match &opts.cmd {
         Command::EvalOnce { file, .. } => file,
+        Command::Replay { file, .. } => file,
         Command::PriorOnly { file, .. } => file,
         Command::Infer { file, .. } => file,
         Command::AncestralSample { file, .. } => file,
+        Command::Graph { file, .. } => file,
+        Command::Summarize { file, .. } => file,
+        Command::Plot { .. } => unreachable!("Plot reads results rather than a program."),
+        Command::Check { file, .. } => file,
+    }
+}
+
+fn data_args(opts: &Opts) -> &[DataArg] {
+    match &opts.cmd {
+        Command::EvalOnce { data, .. } => data,
+        Command::Replay { data, .. } => data,
+        Command::PriorOnly { data, .. } => data,
+        Command::Infer { data, .. } => data,
+        Command::AncestralSample { .. } => &[],
+        Command::Graph { data, .. } => data,
+        Command::Summarize { .. } | Command::Plot { .. } => &[],
+        Command::Check { data, .. } => data,
+    }
+}
+
+/// Works out where the results go, and checks that the format suits the algorithm and streaming before any
+/// samples are drawn.
+fn prepare_output(
+    file: &Path,
+    out: Option<PathBuf>,
+    format: Option<Format>,
+    flush_every: Option<usize>,
+    mcmc: bool,
+    summarize: bool,
+) -> Result<output::Output, RuntimeError> {
+    let mut out = output::output_path(file, out, format)?;
+    if out.format.needs_sample_stats() && !mcmc {
+        return err!("The stan-csv and arviz formats are only for single-site-metropolis.");
+    }
+    if flush_every.is_some() && !out.format.can_stream() {
+        return err!("Only the ndjson and csv formats can be streamed.");
+    }
+    if flush_every.is_some() && summarize {
+        return err!(
+            "Streamed results can't be summarised, since they aren't kept. Summarise the written file with \
+             `summarize` instead."
+        );
+    }
+    out.flush_every = flush_every;
+    out.summarize = summarize;
+    Ok(out)
+}
+
+/// Where to save checkpoints, and the checkpoint to carry on from, if any.
+struct Checkpoints {
+    path: PathBuf,
+    every: usize,
+    source: &'static str,
+    resume: Option<checkpoint::Checkpoint>,
+}
+
+/// Checks that checkpoints can be taken, and reads the checkpoint to resume from, before any samples are drawn.
+fn prepare_checkpoints(
+    source: &'static str,
+    checkpoint: Option<PathBuf>,
+    every: usize,
+    resume: Option<PathBuf>,
+    alg: &Alg,
+    stream: bool,
+) -> Result<Option<Checkpoints>, RuntimeError> {
+    let path = match checkpoint.as_ref().or(resume.as_ref()) {
+        Some(path) => path.clone(),
+        None => return Ok(None),
+    };
+    if *alg == Alg::LikelihoodWeighting {
+        return err!("Only single-site-metropolis chains can be checkpointed and resumed.");
+    }
+    if stream {
+        return err!(
+            "Checkpoints can't be taken with `--stream`, since resuming would write the results since the last \
+             checkpoint again."
+        );
+    }
+    if every == 0 {
+        return err!("`--checkpoint-every` must be at least 1.");
+    }
+    let resume = match resume {
+        Some(path) => {
+            let checkpoint = checkpoint::Checkpoint::load(&path)?;
+            if checkpoint.source != source {
+                return err!(
+                    "`{}` was saved from a different program, so it can't be resumed with this one.",
+                    path.display()
+                );
+            }
+            Some(checkpoint)
+        }
+        None => None,
+    };
+    Ok(Some(Checkpoints { path, every, source, resume }))
+}
+
+/// Reads the choice map given by `--choices`, if any, checking its names and addresses against the program.
+fn read_choices(path: Option<PathBuf>, program: &mut Program) -> Result<ChoiceMap, RuntimeError> {
+    match path {
+        Some(path) => ChoiceMap::read(&path, program),
+        None => Ok(ChoiceMap::default()),
     }
 }
 
@@ -147,13 +588,55 @@ fn file_stem(file_name: &Path) -> Option<&OsStr> {
 
 fn infer<T: InferenceAlg>(
     program: Program,
-    file: &Path,
+    data: Vec<Binding>,
+    choices: ChoiceMap,
+    out: output::Output,
     n_samples: usize,
     mut alg: T,
+    quiet: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut interpreter = Interpreter::new(&mut alg);
+    let mut interpreter = Interpreter::with_data(&mut alg, data);
+    interpreter.constrain(choices);
+    let mut progress = if quiet { None } else { Progress::new(n_samples, 0) };
+
+    if out.flush_every.is_some() {
+        let mut sink = output::Sink::new(&out)?;
+        let result = interpreter.eval_program_with(program, n_samples, |alg| {
+            if let Some(progress) = &mut progress {
+                progress.update(alg);
+            }
+            for (val, log_weight) in alg.take_kept() {
+                sink.write(val, log_weight)?;
+            }
+            if let Some(progress) = &mut progress {
+                progress.taken();
+            }
+            Ok(())
+        });
+        sink.flush()?;
+        interpreter.warn_unmatched_choices();
+        if let Err(e) = result {
+            eprintln!("{:?}", e);
+        }
+        return Ok(());
+    }
+
+    let result = interpreter.eval_program_with(program, n_samples, |alg| {
+        if let Some(progress) = &mut progress {
+            progress.update(alg);
+        }
+        Ok(())
+    });
+    interpreter.warn_unmatched_choices();
+    match result {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return Ok(());
+        }
+    };
 
-    match interpreter.eval_program(program, n_samples) {
+    let data = match alg.finalize_and_make_dataset() {
         Ok(v) => v,
         Err(e) => {
             eprintln!("{:?}", e);
@@ -161,6 +644,74 @@ fn infer<T: InferenceAlg>(
         }
     };
 
+    write_results(&data, &out)
+}
+
+/// Like `infer` with single-site Metropolis, saving the chain every `checkpoints.every` runs and once it's
+/// finished, and first carrying on from `checkpoints.resume` if there is one.
+#[allow(clippy::too_many_arguments)]
+fn infer_with_checkpoints(
+    program: Program,
+    data: Vec<Binding>,
+    choices: ChoiceMap,
+    out: output::Output,
+    n_samples: usize,
+    skip: usize,
+    checkpoints: Checkpoints,
+    quiet: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let Checkpoints { path, every, source, resume } = checkpoints;
+    let mut alg = SingleSiteMetropolis::new(skip);
+    let mut interpreter = Interpreter::with_data(&mut alg, data);
+    interpreter.constrain(choices);
+    let expression = interpreter.load_program(program);
+
+    let (n_samples, mut completed) = match resume {
+        Some(checkpoint) => {
+            // The trace's distributions are made again by calling their functions, with the program's definitions.
+            let mut prior = PriorOnly::new();
+            let definitions = interpreter.definitions.clone();
+            let mut rebuilder = Interpreter::with_definitions(&mut prior, definitions);
+            let chain = SingleSiteMetropolis::restore(checkpoint.chain, &mut |name, args| {
+                rebuilder.dispatch_function(name, args)
+            });
+            match chain {
+                Ok(chain) => *interpreter.inference_alg = chain,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return Ok(());
+                }
+            }
+            random::set_state(checkpoint.rng);
+            (checkpoint.n_samples, checkpoint.completed)
+        }
+        None => (n_samples, 0),
+    };
+
+    let mut progress = if quiet { None } else { Progress::new(n_samples, completed) };
+    let result = interpreter.eval_loaded(&expression, n_samples.saturating_sub(completed), |alg| {
+        if let Some(progress) = &mut progress {
+            progress.update(alg);
+        }
+        completed += 1;
+        if completed % every != 0 && completed != n_samples {
+            return Ok(());
+        }
+        checkpoint::Checkpoint {
+            source: source.to_owned(),
+            n_samples,
+            completed,
+            rng: random::state(),
+            chain: alg.save(),
+        }
+        .save(&path)
+    });
+    interpreter.warn_unmatched_choices();
+    if let Err(e) = result {
+        eprintln!("{:?}", e);
+        return Ok(());
+    }
+
     let data = match alg.finalize_and_make_dataset() {
         Ok(v) => v,
         Err(e) => {
@@ -169,21 +720,54 @@ fn infer<T: InferenceAlg>(
         }
     };
 
-    let data_json = serde_json::to_string(&data)?;
+    write_results(&data, &out)
+}
 
-    let out_dir = std::path::Path::new("data/");
-    let file_stem = file_stem(&file).unwrap();
-    let out_file = out_dir.join(file_stem).with_extension("json");
+fn write_results(data: &DataFile, out: &output::Output) -> Result<(), Box<dyn std::error::Error>> {
+    output::write_results(data, out)?;
+    if out.summarize {
+        match summary::summarize(data) {
+            Ok(summaries) => summary::print_table(&summaries),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    Ok(())
+}
 
-    std::fs::create_dir_all(out_dir)?;
-    std::fs::write(out_file, data_json)?;
+/// Draws charts of the given data files, or of every data file in `data/`.
+fn plot(files: &[PathBuf], out_dir: &Path, format: plot::ImageFormat) -> Result<(), Box<dyn std::error::Error>> {
+    let files = if files.is_empty() {
+        plot::data_files(Path::new("data"))?
+    } else {
+        files.to_vec()
+    };
+    if files.is_empty() {
+        return Err("There are no data files to plot.".into());
+    }
+    plot::plot_all(&files, out_dir, format)
+}
 
+/// Prints summary statistics of a data file written by `infer` or `prior-only`.
+fn summarize(file: &Path, json: bool) -> Result<(), RuntimeError> {
+    let data = data::read_data_file(file)?;
+    let summaries = summary::summarize(&data)?;
+    if json {
+        let json = serde_json::to_string_pretty(&summaries)
+            .or_else(|e| err!("Couldn't write the summary as JSON: {}", e))?;
+        println!("{}", json);
+    } else {
+        summary::print_table(&summaries);
+    }
     Ok(())
 }
 
-fn eval_once(program: Program, _file: &Path) -> Result<(), Box<dyn std::error::Error>> {
-    let mut alg = PriorOnly::new();
-    let mut interpreter = Interpreter::new(&mut alg);
+fn eval_once(
+    program: Program,
+    data: Vec<Binding>,
+    trace: Option<PathBuf>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut alg = Tracing::new();
+    let mut interpreter = Interpreter::with_data(&mut alg, data);
 
     match interpreter.eval_program(program, 1) {
         Ok(v) => v,
@@ -193,6 +777,57 @@ fn eval_once(program: Program, _file: &Path) -> Result<(), Box<dyn std::error::E
         }
     };
 
+    if let Some(path) = trace {
+        let saved = alg.take_trace().map_or(Ok(()), |t| t.save(&path));
+        if let Err(e) = saved {
+            eprintln!("{}", e);
+            return Ok(());
+        }
+    }
+
+    let data = match alg.finalize_and_make_dataset() {
+        Ok(v) => v,
+        Err(e) => {
+            eprintln!("{:?}", e);
+            return Ok(());
+        }
+    };
+
+    println!("{:#?}", data.data[0]);
+
+    Ok(())
+}
+
+fn replay(program: Program, data: Vec<Binding>, path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let trace = match trace::Trace::load(path) {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Ok(());
+        }
+    };
+
+    let mut alg = Tracing::new();
+    let mut interpreter = Interpreter::with_data(&mut alg, data);
+    let expression = interpreter.load_program(program);
+
+    // The trace's distributions are made again by calling their functions, with the program's definitions.
+    let mut prior = PriorOnly::new();
+    let mut rebuilder = Interpreter::with_definitions(&mut prior, interpreter.definitions.clone());
+    match Tracing::replaying(trace, &mut |name, args| rebuilder.dispatch_function(name, args)) {
+        Ok(replaying) => *interpreter.inference_alg = replaying,
+        Err(e) => {
+            eprintln!("{}", e);
+            return Ok(());
+        }
+    }
+
+    if let Err(e) = interpreter.eval_loaded(&expression, 1, |_| Ok(())) {
+        eprintln!("{:?}", e);
+        return Ok(());
+    }
+
+    let differences = alg.differences();
     let data = match alg.finalize_and_make_dataset() {
         Ok(v) => v,
         Err(e) => {
@@ -202,6 +837,12 @@ fn eval_once(program: Program, _file: &Path) -> Result<(), Box<dyn std::error::E
     };
 
     println!("{:#?}", data.data[0]);
+    if differences.is_empty() {
+        println!("The run followed the trace exactly.");
+    }
+    for difference in differences {
+        println!("{}", difference);
+    }
 
     Ok(())
 }
@@ -0,0 +1,311 @@
+use std::rc::Rc;
+
+use crate::types::{RuntimeError, Value};
+
+/// A dense, row-major matrix of floats. Backs `Value::Matrix`, so that the `mat-*` built-ins don't need to
+/// unwrap and re-box every element of a vector-of-vectors.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    pub fn new(rows: usize, cols: usize, data: Vec<f64>) -> Self {
+        assert_eq!(rows * cols, data.len(), "Matrix data has the wrong length.");
+        Matrix { rows, cols, data }
+    }
+
+    pub fn from_fn<F: FnMut(usize, usize) -> f64>(rows: usize, cols: usize, mut f: F) -> Self {
+        let mut data = Vec::with_capacity(rows * cols);
+        for i in 0..rows {
+            for j in 0..cols {
+                data.push(f(i, j));
+            }
+        }
+        Matrix { rows, cols, data }
+    }
+
+    pub fn n_rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn n_cols(&self) -> usize {
+        self.cols
+    }
+
+    pub fn get(&self, i: usize, j: usize) -> f64 {
+        self.data[i * self.cols + j]
+    }
+
+    pub fn row(&self, i: usize) -> &[f64] {
+        &self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    pub fn data(&self) -> &[f64] {
+        &self.data
+    }
+
+    /// The row at index `i`, as the vector value the language sees when indexing into a matrix.
+    pub fn row_value(&self, i: usize) -> Value {
+        Value::Vector(self.row(i).iter().copied().map(Value::Float).collect())
+    }
+
+    pub fn rows_as_values(&self) -> Vec<Value> {
+        (0..self.rows).map(|i| self.row_value(i)).collect()
+    }
+
+    /// A matrix made of the rows from `start` onwards.
+    pub fn rows_from(&self, start: usize) -> Matrix {
+        Matrix::new(
+            self.rows - start,
+            self.cols,
+            self.data[start * self.cols..].to_vec(),
+        )
+    }
+
+    pub fn transpose(&self) -> Matrix {
+        Matrix::from_fn(self.cols, self.rows, |i, j| self.get(j, i))
+    }
+
+    pub fn map<F: Fn(f64) -> f64>(&self, f: F) -> Matrix {
+        Matrix::new(
+            self.rows,
+            self.cols,
+            self.data.iter().map(|x| f(*x)).collect(),
+        )
+    }
+
+    pub fn repeat(&self, row_mul: usize, col_mul: usize) -> Matrix {
+        Matrix::from_fn(self.rows * row_mul, self.cols * col_mul, |i, j| {
+            self.get(i % self.rows, j % self.cols)
+        })
+    }
+
+    /// Returns `None` if the inner dimensions don't match.
+    pub fn mul(&self, other: &Matrix) -> Option<Matrix> {
+        if self.cols != other.rows {
+            return None;
+        }
+        let mut data = vec![0f64; self.rows * other.cols];
+        for i in 0..self.rows {
+            let out_row = &mut data[i * other.cols..(i + 1) * other.cols];
+            for (k, a) in self.row(i).iter().enumerate() {
+                for (out, b) in out_row.iter_mut().zip(other.row(k)) {
+                    *out += a * b;
+                }
+            }
+        }
+        Some(Matrix::new(self.rows, other.cols, data))
+    }
+
+    /// Applies `f` element-wise, broadcasting dimensions of size 1 against the other matrix.
+    /// Returns `None` if the shapes can't be broadcast together.
+    pub fn broadcast<F: Fn(f64, f64) -> f64>(&self, other: &Matrix, f: F) -> Option<Matrix> {
+        let rows = broadcast_dim(self.rows, other.rows)?;
+        let cols = broadcast_dim(self.cols, other.cols)?;
+        Some(Matrix::from_fn(rows, cols, |i, j| {
+            let a = self.get(i % self.rows, j % self.cols);
+            let b = other.get(i % other.rows, j % other.cols);
+            f(a, b)
+        }))
+    }
+
+    pub fn is_square(&self) -> bool {
+        self.rows == self.cols
+    }
+
+    /// The lower-triangular `L` such that `L L^T` is this matrix. Returns `None` unless the matrix is symmetric and
+    /// positive definite.
+    pub fn cholesky(&self) -> Option<Matrix> {
+        if !self.is_square() {
+            return None;
+        }
+        let n = self.rows;
+        let mut l = vec![0f64; n * n];
+        for i in 0..n {
+            for j in 0..=i {
+                let a_ij = self.get(i, j);
+                let tolerance = 1e-9 * (a_ij.abs() + self.get(j, i).abs()).max(1.0);
+                if (a_ij - self.get(j, i)).abs() > tolerance {
+                    return None;
+                }
+                let mut sum = a_ij;
+                for k in 0..j {
+                    sum -= l[i * n + k] * l[j * n + k];
+                }
+                if i == j {
+                    if sum <= 0.0 || !sum.is_finite() {
+                        return None;
+                    }
+                    l[i * n + i] = sum.sqrt();
+                } else {
+                    l[i * n + j] = sum / l[j * n + j];
+                }
+            }
+        }
+        Some(Matrix::new(n, n, l))
+    }
+
+    pub fn identity(n: usize) -> Matrix {
+        Matrix::from_fn(n, n, |i, j| if i == j { 1.0 } else { 0.0 })
+    }
+
+    pub fn mul_vec(&self, v: &[f64]) -> Vec<f64> {
+        (0..self.rows)
+            .map(|i| self.row(i).iter().zip(v).map(|(a, b)| a * b).sum())
+            .collect()
+    }
+
+    pub fn trace(&self) -> f64 {
+        (0..self.rows.min(self.cols)).map(|i| self.get(i, i)).sum()
+    }
+
+    /// Solves `self * x = b` by forward substitution, where this matrix is lower triangular, like the result of
+    /// `cholesky`.
+    pub fn solve_lower(&self, b: &[f64]) -> Vec<f64> {
+        let mut x = b.to_vec();
+        for i in 0..self.rows {
+            for k in 0..i {
+                x[i] -= self.get(i, k) * x[k];
+            }
+            x[i] /= self.get(i, i);
+        }
+        x
+    }
+
+    /// The log of the determinant of the matrix whose Cholesky factor is this matrix.
+    pub fn cholesky_log_determinant(&self) -> f64 {
+        2.0 * (0..self.rows).map(|i| self.get(i, i).ln()).sum::<f64>()
+    }
+
+    /// LU decomposition with partial pivoting, packed into one matrix with the unit diagonal of `L` left implicit.
+    /// Also returns the row permutation and its sign. Returns `None` if the matrix is singular.
+    fn lu(&self) -> Option<(Matrix, Vec<usize>, f64)> {
+        let n = self.rows;
+        let mut lu = self.clone();
+        let mut perm: Vec<usize> = (0..n).collect();
+        let mut sign = 1.0;
+        for k in 0..n {
+            // NaNs are ordered above every number, so a matrix with one gives NaNs rather than panicking.
+            let pivot = (k..n)
+                .max_by(|a, b| lu.get(*a, k).abs().total_cmp(&lu.get(*b, k).abs()))
+                .unwrap();
+            if lu.get(pivot, k) == 0.0 {
+                return None;
+            }
+            if pivot != k {
+                for j in 0..n {
+                    lu.data.swap(k * n + j, pivot * n + j);
+                }
+                perm.swap(k, pivot);
+                sign = -sign;
+            }
+            for i in k + 1..n {
+                let factor = lu.get(i, k) / lu.get(k, k);
+                lu.data[i * n + k] = factor;
+                for j in k + 1..n {
+                    lu.data[i * n + j] -= factor * lu.get(k, j);
+                }
+            }
+        }
+        Some((lu, perm, sign))
+    }
+
+    /// Returns `None` if the matrix isn't square.
+    pub fn determinant(&self) -> Option<f64> {
+        if !self.is_square() {
+            return None;
+        }
+        Some(match self.lu() {
+            Some((lu, _, sign)) => (0..self.rows).map(|i| lu.get(i, i)).product::<f64>() * sign,
+            None => 0.0,
+        })
+    }
+
+    /// Returns `None` if the matrix isn't square, or is singular.
+    pub fn inverse(&self) -> Option<Matrix> {
+        if !self.is_square() {
+            return None;
+        }
+        let n = self.rows;
+        let (lu, perm, _) = self.lu()?;
+        let mut inverse = vec![0f64; n * n];
+        for col in 0..n {
+            // Solve L y = P e_col, then U x = y.
+            let mut x: Vec<f64> = perm.iter().map(|&p| if p == col { 1.0 } else { 0.0 }).collect();
+            for i in 0..n {
+                for k in 0..i {
+                    x[i] -= lu.get(i, k) * x[k];
+                }
+            }
+            for i in (0..n).rev() {
+                for k in i + 1..n {
+                    x[i] -= lu.get(i, k) * x[k];
+                }
+                x[i] /= lu.get(i, i);
+            }
+            for (row, v) in x.into_iter().enumerate() {
+                inverse[row * n + col] = v;
+            }
+        }
+        Some(Matrix::new(n, n, inverse))
+    }
+
+    /// Converts a `Value::Matrix`, or a rectangular vector-of-vectors of numbers, into a matrix.
+    /// `what` describes the value in error messages, e.g. "Argument 1 to `mat-mul`".
+    pub fn try_from_value(val: &Value, what: &str) -> Result<Rc<Matrix>, RuntimeError> {
+        let rows = match val {
+            Value::Matrix(m) => return Ok(m.clone()),
+            Value::Vector(rows) => rows,
+            _ => return err!("{} must be a matrix (a vector of vectors).", what),
+        };
+
+        if rows.is_empty() {
+            return err!("{} must have at least one row.", what);
+        }
+
+        let n_cols = match &rows[0] {
+            Value::Vector(row) => row.len(),
+            _ => return err!("{} must be a matrix (a vector of vectors).", what),
+        };
+
+        if n_cols == 0 {
+            return err!("All rows of {} must have at least one element.", what);
+        }
+
+        let mut data = Vec::with_capacity(rows.len() * n_cols);
+        for row in rows {
+            let row = match row {
+                Value::Vector(row) if row.len() == n_cols => row,
+                Value::Vector(_) => return err!("{} had uneven length rows.", what),
+                _ => return err!("{} had non-vector elements.", what),
+            };
+            for v in row {
+                data.push(v.try_get_numeric(&format!("{} contains non-numeric values.", what))?);
+            }
+        }
+
+        Ok(Rc::new(Matrix::new(rows.len(), n_cols, data)))
+    }
+}
+
+/// The size of a dimension after broadcasting, if sizes `a` and `b` are compatible.
+pub fn broadcast_dim(a: usize, b: usize) -> Option<usize> {
+    if a == b || b == 1 {
+        Some(a)
+    } else if a == 1 {
+        Some(b)
+    } else {
+        None
+    }
+}
+
+/// Whether `mat-add` and the other binary `mat-*` built-ins can combine matrices with these dimensions. They only
+/// broadcast one argument up to the size of the other, unlike `+`.
+pub fn mat_add_compatible((rows1, cols1): (usize, usize), (rows2, cols2): (usize, usize)) -> bool {
+    (rows1 == 1 || rows2 == 1 || rows1 == rows2)
+        && (cols1 == 1 || cols2 == 1 || cols1 == cols2)
+        && ((cols1 <= cols2 && rows1 <= rows2) || (cols2 <= cols1 && rows2 <= rows1))
+}
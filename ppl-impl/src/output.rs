@@ -0,0 +1,538 @@
+//! Writing the results of inference to a file, in one of several formats.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+};
+
+use arrow_array::{ArrayRef, BooleanArray, Float64Array, Int64Array, RecordBatch};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{DataType, Field, Schema};
+use serde::Serialize;
+
+use crate::{
+    inference::flatten_to_numeric_vec_only,
+    types::{RuntimeError, Value},
+    DataFile, ProgramResult, ResultValue, SampleStats,
+};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    /// The whole `DataFile`, as read by `viz.py` and `load-empirical`.
+    Json,
+    /// One row per sample and one column per number in the result, plus `log_weight` if the results are weighted.
+    Csv,
+    /// One JSON object per line, `{"value": ..., "log_weight": ...}`.
+    Ndjson,
+    /// The same columns as `Csv`, in an Arrow IPC file.
+    Arrow,
+    /// CmdStan's output CSV, with `lp__` and `accept_stat__` before the result's columns. Only for MCMC.
+    StanCsv,
+    /// ArviZ InferenceData groups, as the keyword arguments of `arviz.from_dict`. Only for MCMC.
+    Arviz,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            "ndjson" => Ok(Format::Ndjson),
+            "arrow" => Ok(Format::Arrow),
+            "stan-csv" => Ok(Format::StanCsv),
+            "arviz" => Ok(Format::Arviz),
+            _ => Err(format!(
+                "`{}` isn't a format. Use json, csv, ndjson, arrow, stan-csv or arviz.",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    /// Whether the format needs the sample stats that only MCMC algorithms keep.
+    pub fn needs_sample_stats(self) -> bool {
+        matches!(self, Format::StanCsv | Format::Arviz)
+    }
+
+    /// Whether results can be written one at a time, as they're kept.
+    pub fn can_stream(self) -> bool {
+        matches!(self, Format::Ndjson | Format::Csv)
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Csv => "csv",
+            Format::Ndjson => "ndjson",
+            Format::Arrow => "arrow",
+            Format::StanCsv => "stan.csv",
+            Format::Arviz => "arviz.json",
+        }
+    }
+}
+
+/// Where the results of running a program go.
+pub struct Output {
+    pub path: PathBuf,
+    pub format: Format,
+    /// The program's file stem, for formats which record the model's name.
+    pub model: String,
+    /// When results are streamed, how many are written between flushes.
+    pub flush_every: Option<usize>,
+    /// Whether to print summary statistics of the results once they're written.
+    pub summarize: bool,
+}
+
+/// Where to write the results of running `file`, and in which format. Without `--out`, results go to
+/// `data/<stem>.<format>`; without `--format`, the format is taken from the extension of `--out`, or is JSON.
+pub fn output_path(
+    file: &Path,
+    out: Option<PathBuf>,
+    format: Option<Format>,
+) -> Result<Output, RuntimeError> {
+    let file_stem = file.file_stem().unwrap();
+    let (path, format) = match (out, format) {
+        (Some(out), Some(format)) => (out, format),
+        (Some(out), None) => {
+            let extension = out.extension().and_then(|e| e.to_str()).unwrap_or("");
+            match Format::from_str(extension) {
+                Ok(format) => (out, format),
+                Err(_) => {
+                    return err!(
+                    "Can't tell which format to write `{}` in from its extension. Use `--format`.",
+                    out.display()
+                )
+                }
+            }
+        }
+        (None, format) => {
+            let format = format.unwrap_or(Format::Json);
+            let out = Path::new("data/")
+                .join(file_stem)
+                .with_extension(format.extension());
+            (out, format)
+        }
+    };
+    Ok(Output {
+        path,
+        format,
+        model: file_stem.to_string_lossy().into_owned(),
+        flush_every: None,
+        summarize: false,
+    })
+}
+
+pub fn write_results(data: &DataFile, output: &Output) -> Result<(), Box<dyn Error>> {
+    let stats = match (output.format, &data.sample_stats) {
+        (Format::StanCsv, None) | (Format::Arviz, None) => {
+            return Err(RuntimeError::new(
+                "The stan-csv and arviz formats are only for single-site-metropolis.".to_owned(),
+            )
+            .into())
+        }
+        (_, stats) => stats.as_ref(),
+    };
+
+    if let Some(dir) = output.path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    let mut writer = BufWriter::new(File::create(&output.path)?);
+    match output.format {
+        Format::Json => serde_json::to_writer(&mut writer, data)?,
+        Format::Ndjson => {
+            for (value, log_weight) in samples(data) {
+                serde_json::to_writer(&mut writer, &NdjsonLine { value, log_weight })?;
+                writeln!(writer)?;
+            }
+        }
+        Format::Csv => write_csv(&Columns::new(data, Naming::Indexed), &mut writer)?,
+        Format::Arrow => write_arrow(&Columns::new(data, Naming::Indexed), writer)?,
+        Format::StanCsv => write_stan_csv(
+            &Columns::new(data, Naming::Stan),
+            stats.unwrap(),
+            &output.model,
+            &mut writer,
+        )?,
+        Format::Arviz => write_arviz(data, stats.unwrap(), &output.model, &mut writer)?,
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct NdjsonLine<'d> {
+    value: &'d ProgramResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    log_weight: Option<f64>,
+}
+
+/// Each result, with its log weight if the results are weighted. Weighted results are stored as
+/// `[value, log-weight]` pairs.
+pub(crate) fn samples(data: &DataFile) -> impl Iterator<Item = (&ProgramResult, Option<f64>)> {
+    let has_weights = data.has_weights;
+    data.data.iter().map(move |result| match result {
+        ProgramResult::Many(pair) if has_weights => match pair.as_slice() {
+            [value, ProgramResult::One(ResultValue::Float(w))] => (value, Some(*w)),
+            _ => unreachable!("Weighted results are pairs of a value and a weight."),
+        },
+        value => (value, None),
+    })
+}
+
+/// How the elements of vectors are named when results are flattened into columns.
+#[derive(Clone, Copy)]
+pub(crate) enum Naming {
+    /// `value[0]`, indexed like `get`.
+    Indexed,
+    /// `value.1`, as Stan names the elements of arrays and vectors.
+    Stan,
+}
+
+/// The results flattened into named columns: `value` for a number, `value[i]` for the elements of a vector and
+/// `value.name` for the parameters of a distribution, nesting as deep as the result does. A column which a
+/// sample doesn't have, because its results vary in shape, is missing from that row.
+pub(crate) struct Columns<'d> {
+    pub names: Vec<String>,
+    pub rows: Vec<Vec<Option<&'d ResultValue>>>,
+    pub log_weights: Option<Vec<f64>>,
+}
+
+impl<'d> Columns<'d> {
+    pub fn new(data: &'d DataFile, naming: Naming) -> Self {
+        let mut names: Vec<String> = Vec::new();
+        let mut index: HashMap<String, usize> = HashMap::new();
+        let mut rows = Vec::with_capacity(data.data.len());
+        let mut log_weights = Vec::with_capacity(data.data.len());
+
+        for (value, log_weight) in samples(data) {
+            let mut cells = Vec::new();
+            flatten(value, "value".to_owned(), naming, &mut cells);
+            let mut row = vec![None; names.len()];
+            for (name, cell) in cells {
+                let i = *index.entry(name.clone()).or_insert_with(|| {
+                    names.push(name);
+                    names.len() - 1
+                });
+                if i >= row.len() {
+                    row.resize(i + 1, None);
+                }
+                row[i] = Some(cell);
+            }
+            rows.push(row);
+            log_weights.extend(log_weight);
+        }
+        for row in rows.iter_mut() {
+            row.resize(names.len(), None);
+        }
+
+        Columns {
+            names,
+            rows,
+            log_weights: Some(log_weights).filter(|_| data.has_weights),
+        }
+    }
+
+    pub fn column(&self, i: usize) -> impl Iterator<Item = Option<&'d ResultValue>> + '_ {
+        self.rows.iter().map(move |row| row[i])
+    }
+}
+
+fn flatten<'d>(
+    result: &'d ProgramResult,
+    name: String,
+    naming: Naming,
+    cells: &mut Vec<(String, &'d ResultValue)>,
+) {
+    match result {
+        ProgramResult::One(x) => cells.push((name, x)),
+        ProgramResult::Many(elements) => {
+            for (i, element) in elements.iter().enumerate() {
+                let name = match naming {
+                    Naming::Indexed => format!("{}[{}]", name, i),
+                    Naming::Stan => format!("{}.{}", name, i + 1),
+                };
+                flatten(element, name, naming, cells);
+            }
+        }
+        ProgramResult::Distribution(d) => {
+            for (param, val) in d.params.iter() {
+                flatten(val, format!("{}.{}", name, param), naming, cells);
+            }
+        }
+    }
+}
+
+fn write_csv(columns: &Columns, writer: &mut impl Write) -> Result<(), Box<dyn Error>> {
+    let mut header = columns.names.join(",");
+    if columns.log_weights.is_some() {
+        if !header.is_empty() {
+            header.push(',');
+        }
+        header.push_str("log_weight");
+    }
+    writeln!(writer, "{}", header)?;
+
+    for (i, row) in columns.rows.iter().enumerate() {
+        let mut cells: Vec<String> = row.iter().map(|cell| csv_cell(*cell)).collect();
+        if let Some(log_weights) = &columns.log_weights {
+            cells.push(format!("{:?}", log_weights[i]));
+        }
+        writeln!(writer, "{}", cells.join(","))?;
+    }
+    Ok(())
+}
+
+fn csv_cell(cell: Option<&ResultValue>) -> String {
+    match cell {
+        Some(ResultValue::Boolean(x)) => x.to_string(),
+        Some(ResultValue::Int(x)) => x.to_string(),
+        Some(ResultValue::Float(x)) => format!("{:?}", x),
+        None => String::new(),
+    }
+}
+
+/// Columns of only booleans or only integers keep their type, and any other column is floats.
+fn write_arrow(columns: &Columns, writer: impl Write) -> Result<(), Box<dyn Error>> {
+    let mut fields = Vec::new();
+    let mut arrays: Vec<ArrayRef> = Vec::new();
+    for (i, name) in columns.names.iter().enumerate() {
+        let all = |f: fn(&ResultValue) -> bool| columns.column(i).flatten().all(f);
+        let array: ArrayRef = if all(|x| matches!(x, ResultValue::Boolean(_))) {
+            Arc::new(
+                columns
+                    .column(i)
+                    .map(|x| match x {
+                        Some(ResultValue::Boolean(x)) => Some(*x),
+                        _ => None,
+                    })
+                    .collect::<BooleanArray>(),
+            )
+        } else if all(|x| matches!(x, ResultValue::Int(_))) {
+            Arc::new(
+                columns
+                    .column(i)
+                    .map(|x| match x {
+                        Some(ResultValue::Int(x)) => Some(*x),
+                        _ => None,
+                    })
+                    .collect::<Int64Array>(),
+            )
+        } else {
+            Arc::new(
+                columns
+                    .column(i)
+                    .map(|x| match x {
+                        Some(ResultValue::Boolean(x)) => Some(if *x { 1.0 } else { 0.0 }),
+                        Some(ResultValue::Int(x)) => Some(*x as f64),
+                        Some(ResultValue::Float(x)) => Some(*x),
+                        None => None,
+                    })
+                    .collect::<Float64Array>(),
+            )
+        };
+        fields.push(Field::new(name, array.data_type().clone(), true));
+        arrays.push(array);
+    }
+    if let Some(log_weights) = &columns.log_weights {
+        fields.push(Field::new("log_weight", DataType::Float64, false));
+        arrays.push(Arc::new(Float64Array::from(log_weights.clone())));
+    }
+
+    let schema = Arc::new(Schema::new(fields));
+    let mut writer = FileWriter::try_new(writer, &schema)?;
+    if !arrays.is_empty() {
+        writer.write(&RecordBatch::try_new(schema, arrays)?)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+/// CmdStan's layout, so that tools which read Stan's output, like `cmdstanpy` and `arviz.from_cmdstan`, can read
+/// the draws. Stan has no booleans, so they are written as 1 and 0.
+fn write_stan_csv(
+    columns: &Columns,
+    stats: &SampleStats,
+    model: &str,
+    writer: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    writeln!(writer, "# model = {}", model)?;
+    writeln!(writer, "# method = sample (Default)")?;
+    writeln!(writer, "#   sample")?;
+    writeln!(writer, "#     num_samples = {}", columns.rows.len())?;
+    writeln!(writer, "#     num_warmup = 0")?;
+    writeln!(writer, "#     save_warmup = 0")?;
+    writeln!(writer, "#     thin = {}", stats.thin)?;
+    writeln!(writer, "#     algorithm = {}", stats.algorithm)?;
+    writeln!(writer, "# output")?;
+    writeln!(writer, "#   generator = thisppl")?;
+
+    let mut header = vec!["lp__", "accept_stat__"];
+    header.extend(columns.names.iter().map(String::as_str));
+    writeln!(writer, "{}", header.join(","))?;
+
+    for (i, row) in columns.rows.iter().enumerate() {
+        let mut cells = vec![
+            format!("{:?}", stats.lp[i]),
+            format!("{:?}", stats.accept_stat[i]),
+        ];
+        cells.extend(row.iter().map(|cell| match cell {
+            Some(ResultValue::Boolean(x)) => (*x as i64).to_string(),
+            Some(ResultValue::Int(x)) => x.to_string(),
+            Some(ResultValue::Float(x)) => format!("{:?}", x),
+            None => String::new(),
+        }));
+        writeln!(writer, "{}", cells.join(","))?;
+    }
+    Ok(())
+}
+
+/// Each group maps variable names to arrays whose first two dimensions are the chain and the draw. There is only
+/// ever one chain. The program's result is the variable `value`, and the log likelihood of each draw's
+/// observations, in the order they were made, is `obs`. Load it with `arviz.from_dict(**json.load(file))`.
+fn write_arviz(
+    data: &DataFile,
+    stats: &SampleStats,
+    model: &str,
+    writer: &mut impl Write,
+) -> Result<(), Box<dyn Error>> {
+    fn numeric(result: &ProgramResult) -> bool {
+        match result {
+            ProgramResult::One(_) => true,
+            ProgramResult::Many(elements) => elements.iter().all(numeric),
+            ProgramResult::Distribution(_) => false,
+        }
+    }
+    if !data.data.iter().all(numeric) {
+        return Err(RuntimeError::new(
+            "The arviz format needs results made of numbers, not distributions.".to_owned(),
+        )
+        .into());
+    }
+
+    let mut groups = serde_json::json!({
+        "posterior": { "value": [&data.data] },
+        "sample_stats": { "lp": [&stats.lp], "acceptance_rate": [&stats.accept_stat] },
+        "attrs": { "inference_library": "thisppl", "model": model, "algorithm": stats.algorithm },
+    });
+
+    let n_observations = stats.log_likelihood.first().map_or(0, Vec::len);
+    if stats
+        .log_likelihood
+        .iter()
+        .any(|obs| obs.len() != n_observations)
+    {
+        return Err(RuntimeError::new(
+            "The arviz format needs every draw to make the same number of observations.".to_owned(),
+        )
+        .into());
+    }
+    if n_observations > 0 {
+        groups["log_likelihood"] = serde_json::json!({ "obs": [&stats.log_likelihood] });
+    }
+
+    serde_json::to_writer(writer, &groups)?;
+    Ok(())
+}
+
+/// Writes results to the output file as inference produces them, so that they aren't all held in memory and a run
+/// which stops early still leaves the results it got to. Only NDJSON and CSV can be written a row at a time. The
+/// columns of a CSV file are those of the first result, so every result must have the same shape.
+pub struct Sink {
+    path: PathBuf,
+    format: Format,
+    writer: BufWriter<File>,
+    // The CSV columns, once the first result has been written.
+    columns: Option<Vec<String>>,
+    flush_every: usize,
+    since_flush: usize,
+}
+
+impl Sink {
+    pub fn new(output: &Output) -> Result<Self, RuntimeError> {
+        if !output.format.can_stream() {
+            return err!("Only the ndjson and csv formats can be streamed.");
+        }
+        let io_error = |e: std::io::Error| {
+            RuntimeError::new(format!("Couldn't write `{}`: {}", output.path.display(), e))
+        };
+        if let Some(dir) = output.path.parent() {
+            std::fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let file = File::create(&output.path).map_err(io_error)?;
+        Ok(Sink {
+            path: output.path.clone(),
+            format: output.format,
+            writer: BufWriter::new(file),
+            columns: None,
+            flush_every: output.flush_every.unwrap_or(1).max(1),
+            since_flush: 0,
+        })
+    }
+
+    pub fn write(&mut self, value: Value, log_weight: Option<f64>) -> Result<(), RuntimeError> {
+        let value = flatten_to_numeric_vec_only(vec![value])?.pop().unwrap();
+        match self.format {
+            Format::Ndjson => {
+                let line = serde_json::to_string(&NdjsonLine {
+                    value: &value,
+                    log_weight,
+                })
+                .or_else(|e| err!("Couldn't convert a result to JSON: {}", e))?;
+                self.write_line(&line)?;
+            }
+            _ => self.write_csv_row(&value, log_weight)?,
+        }
+
+        self.since_flush += 1;
+        if self.since_flush >= self.flush_every {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    fn write_csv_row(
+        &mut self,
+        value: &ProgramResult,
+        log_weight: Option<f64>,
+    ) -> Result<(), RuntimeError> {
+        let mut cells = Vec::new();
+        flatten(value, "value".to_owned(), Naming::Indexed, &mut cells);
+        let names: Vec<String> = cells.iter().map(|(name, _)| name.clone()).collect();
+        match &self.columns {
+            None => {
+                let mut header = names.clone();
+                header.extend(log_weight.map(|_| "log_weight".to_owned()));
+                self.write_line(&header.join(","))?;
+                self.columns = Some(names);
+            }
+            Some(columns) if *columns != names => return err!(
+                "A result has a different shape to the first, so it doesn't fit the CSV columns. \
+                     Use `--format ndjson` instead."
+            ),
+            Some(_) => {}
+        }
+
+        let mut row: Vec<String> = cells.iter().map(|(_, cell)| csv_cell(Some(cell))).collect();
+        row.extend(log_weight.map(|w| format!("{:?}", w)));
+        self.write_line(&row.join(","))
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<(), RuntimeError> {
+        writeln!(self.writer, "{}", line)
+            .or_else(|e| err!("Couldn't write `{}`: {}", self.path.display(), e))
+    }
+
+    pub fn flush(&mut self) -> Result<(), RuntimeError> {
+        self.since_flush = 0;
+        self.writer
+            .flush()
+            .or_else(|e| err!("Couldn't write `{}`: {}", self.path.display(), e))
+    }
+}
@@ -0,0 +1,640 @@
+//! Charts of the results of inference, as PNG or SVG: the grid of histograms that `viz.py` drew, and trace and
+//! autocorrelation plots of chains.
+
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use plotters::{
+    coord::Shift,
+    prelude::*,
+    style::{
+        colors::colormaps::ViridisRGB,
+        register_font,
+        text_anchor::{HPos, Pos, VPos},
+    },
+};
+
+use crate::{
+    data,
+    output::{self, Columns, Naming},
+    DataFile, ProgramResult, ResultValue,
+};
+
+/// The size of each chart, and of each chart in the grid, in pixels.
+const PANEL_SIZE: u32 = 400;
+const HISTOGRAM_BINS: usize = 45;
+const HISTOGRAM_2D_BINS: usize = 50;
+const HISTOGRAM_2D_RANGE: (f64, f64) = (-5.0, 5.0);
+const MAX_LAG: usize = 100;
+/// Results with more numbers than this only have the first ones traced.
+const MAX_TRACES: usize = 16;
+const TRACE_ROW_HEIGHT: u32 = 200;
+const BAR_COLOUR: RGBColor = RGBColor(31, 119, 180);
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ImageFormat {
+    Png,
+    Svg,
+}
+
+impl FromStr for ImageFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "png" => Ok(ImageFormat::Png),
+            "svg" => Ok(ImageFormat::Svg),
+            _ => Err(format!("`{}` isn't an image format. Use png or svg.", s)),
+        }
+    }
+}
+
+impl ImageFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+}
+
+/// How a file's results are drawn, chosen from the type of the first result as `viz.py` does.
+enum Panel {
+    /// A density histogram of numbers.
+    Histogram(Vec<(f64, f64)>),
+    /// The mass of `false` and of `true`.
+    Booleans(f64, f64),
+    /// A 2D histogram of pairs of numbers.
+    Histogram2d(Vec<(f64, f64, f64)>),
+    /// The mass of each state at each position of vectors of integers, like the states of a hidden Markov model.
+    States(Vec<Vec<f64>>),
+    Unsupported(&'static str),
+}
+
+/// A data file's results, drawn as one panel, and traced if they aren't weighted.
+struct Chart {
+    title: String,
+    panel: Panel,
+    traces: Option<Vec<(String, Vec<f64>)>>,
+}
+
+/// Draws a chart of each data file into `out_dir`, as `<stem>.<format>`, with all of them in a grid in
+/// `all.<format>`. Unweighted results, like the draws of an MCMC chain, also get trace and autocorrelation plots
+/// in `<stem>-trace.<format>`.
+pub fn plot_all(
+    files: &[PathBuf],
+    out_dir: &Path,
+    format: ImageFormat,
+) -> Result<(), Box<dyn Error>> {
+    // Text is drawn with a font built into the binary, so charts look the same everywhere.
+    register_font("sans-serif", FontStyle::Normal, notosans::REGULAR_TTF)
+        .map_err(|_| "Couldn't load the font for charts.")?;
+    std::fs::create_dir_all(out_dir)
+        .map_err(|e| format!("Couldn't create `{}`: {}", out_dir.display(), e))?;
+
+    let mut charts = Vec::with_capacity(files.len());
+    for file in files {
+        let data = data::read_data_file(file)?;
+        let stem = file.file_stem().unwrap_or_default().to_string_lossy();
+        let title = stem.split('.').next().unwrap_or_default().to_owned();
+        charts.push(Chart {
+            panel: panel(&data),
+            traces: Some(traces(&data)).filter(|_| !data.has_weights),
+            title,
+        });
+    }
+
+    let path = |name: &str| out_dir.join(format!("{}.{}", name, format.extension()));
+    for chart in charts.iter() {
+        let size = (PANEL_SIZE, PANEL_SIZE);
+        save(&path(&chart.title), size, format, Figure::Panel(chart))?;
+        if let Some(traces) = &chart.traces {
+            if traces.len() > MAX_TRACES {
+                println!(
+                    "Only tracing the first {} of the {} numbers in {}'s results.",
+                    MAX_TRACES,
+                    traces.len(),
+                    chart.title
+                );
+            }
+            let rows = traces.len().clamp(1, MAX_TRACES) as u32;
+            let size = (PANEL_SIZE * 5 / 2, TRACE_ROW_HEIGHT * rows);
+            save(
+                &path(&format!("{}-trace", chart.title)),
+                size,
+                format,
+                Figure::Traces(chart),
+            )?;
+        }
+    }
+    let n = grid_size(charts.len()) as u32;
+    save(
+        &path("all"),
+        (PANEL_SIZE * n, PANEL_SIZE * n),
+        format,
+        Figure::Grid(&charts),
+    )?;
+    Ok(())
+}
+
+/// The data files `plot` draws when it isn't given any: every JSON file in `data/`, in order of name.
+pub fn data_files(dir: &Path) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Couldn't read `{}`: {}", dir.display(), e))?;
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().is_some_and(|e| e == "json")
+            && !path.to_string_lossy().ends_with(".arviz.json")
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The smallest square grid with room for `n` charts.
+fn grid_size(n: usize) -> usize {
+    let mut i = 0;
+    while i * i < n {
+        i += 1;
+    }
+    i.max(1)
+}
+
+enum Figure<'c> {
+    Panel(&'c Chart),
+    Grid(&'c [Chart]),
+    Traces(&'c Chart),
+}
+
+fn save(
+    path: &Path,
+    size: (u32, u32),
+    format: ImageFormat,
+    figure: Figure,
+) -> Result<(), Box<dyn Error>> {
+    match format {
+        ImageFormat::Png => {
+            let root = BitMapBackend::new(path, size).into_drawing_area();
+            draw_figure(&root, figure)?;
+            root.present()?;
+        }
+        ImageFormat::Svg => {
+            let root = SVGBackend::new(path, size).into_drawing_area();
+            draw_figure(&root, figure)?;
+            root.present()?;
+        }
+    }
+    println!("Wrote {}", path.display());
+    Ok(())
+}
+
+fn draw_figure<DB: DrawingBackend>(
+    root: &DrawingArea<DB, Shift>,
+    figure: Figure,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    root.fill(&WHITE)?;
+    match figure {
+        Figure::Panel(chart) => draw_panel(root, &chart.title, &chart.panel),
+        Figure::Grid(charts) => {
+            let n = grid_size(charts.len());
+            for (area, chart) in root.split_evenly((n, n)).iter().zip(charts) {
+                draw_panel(area, &chart.title, &chart.panel)?;
+            }
+            Ok(())
+        }
+        Figure::Traces(chart) => {
+            let traces = chart.traces.as_deref().unwrap_or_default();
+            let shown = &traces[..traces.len().min(MAX_TRACES)];
+            if shown.is_empty() {
+                return Ok(());
+            }
+            for (row, (name, xs)) in root.split_evenly((shown.len(), 1)).iter().zip(shown) {
+                let (trace, autocorrelation) = row.split_horizontally(row.dim_in_pixel().0 * 3 / 5);
+                draw_trace(&trace, &format!("{} {}", chart.title, name), xs)?;
+                draw_autocorrelation(&autocorrelation, xs)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Each result with its weight, relative to the total, skipping results with no weight.
+fn weighted(data: &DataFile) -> Vec<(&ProgramResult, f64)> {
+    let samples: Vec<_> = output::samples(data).collect();
+    let max = samples
+        .iter()
+        .map(|(_, w)| w.unwrap_or(0.0))
+        .fold(f64::NEG_INFINITY, f64::max);
+    let weights: Vec<f64> = samples
+        .iter()
+        .map(|(_, w)| (w.unwrap_or(0.0) - max).exp())
+        .collect();
+    let total: f64 = weights.iter().sum();
+    samples
+        .into_iter()
+        .zip(weights)
+        .filter(|(_, w)| *w > 0.0)
+        .map(|((result, _), w)| (result, w / total))
+        .collect()
+}
+
+/// Booleans are drawn as 0 and 1.
+fn value(x: &ResultValue) -> f64 {
+    match x {
+        ResultValue::Float(x) => *x,
+        ResultValue::Int(x) => *x as f64,
+        ResultValue::Boolean(x) => {
+            if *x {
+                1.0
+            } else {
+                0.0
+            }
+        }
+    }
+}
+
+fn number(result: &ProgramResult) -> Option<f64> {
+    match result {
+        ProgramResult::One(x) => Some(value(x)),
+        _ => None,
+    }
+}
+
+fn panel(data: &DataFile) -> Panel {
+    let samples = weighted(data);
+    let first = match samples.first() {
+        Some((first, _)) => *first,
+        None => return Panel::Unsupported("No results to plot."),
+    };
+    match first {
+        ProgramResult::One(ResultValue::Boolean(_)) => {
+            let mass_true = samples
+                .iter()
+                .filter(|(result, _)| {
+                    matches!(result, ProgramResult::One(ResultValue::Boolean(true)))
+                })
+                .map(|(_, w)| w)
+                .sum::<f64>();
+            Panel::Booleans(1.0 - mass_true, mass_true)
+        }
+        ProgramResult::One(_) => Panel::Histogram(
+            samples
+                .iter()
+                .filter_map(|(result, w)| Some((number(result)?, *w)))
+                .collect(),
+        ),
+        ProgramResult::Many(xs)
+            if xs.len() == 2 && matches!(xs[0], ProgramResult::One(ResultValue::Float(_))) =>
+        {
+            Panel::Histogram2d(
+                samples
+                    .iter()
+                    .filter_map(|(result, w)| match result {
+                        ProgramResult::Many(xs) if xs.len() == 2 => {
+                            Some((number(&xs[0])?, number(&xs[1])?, *w))
+                        }
+                        _ => None,
+                    })
+                    .collect(),
+            )
+        }
+        ProgramResult::Many(xs)
+            if matches!(xs.first(), Some(ProgramResult::One(ResultValue::Int(_)))) =>
+        {
+            let mut masses: Vec<Vec<f64>> = Vec::new();
+            for (result, w) in samples.iter() {
+                let states = match result {
+                    ProgramResult::Many(xs) => xs,
+                    _ => continue,
+                };
+                for (position, state) in states.iter().enumerate() {
+                    let state = match state {
+                        ProgramResult::One(ResultValue::Int(s)) if *s >= 0 => *s as usize,
+                        _ => continue,
+                    };
+                    if state >= masses.len() {
+                        masses.resize(state + 1, Vec::new());
+                    }
+                    if position >= masses[state].len() {
+                        masses[state].resize(position + 1, 0.0);
+                    }
+                    masses[state][position] += w;
+                }
+            }
+            let positions = masses.iter().map(Vec::len).max().unwrap_or(0);
+            for row in masses.iter_mut() {
+                row.resize(positions, 0.0);
+            }
+            Panel::States(masses)
+        }
+        _ => Panel::Unsupported("This type of result isn't supported yet."),
+    }
+}
+
+/// Each number in the results, in order, for trace plots.
+fn traces(data: &DataFile) -> Vec<(String, Vec<f64>)> {
+    let columns = Columns::new(data, Naming::Indexed);
+    columns
+        .names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            let xs = columns
+                .column(i)
+                .filter_map(|cell| cell.map(value))
+                .collect();
+            (name.clone(), xs)
+        })
+        .collect()
+}
+
+fn draw_panel<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    title: &str,
+    panel: &Panel,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    match panel {
+        Panel::Histogram(samples) => draw_histogram(area, title, samples),
+        Panel::Booleans(mass_false, mass_true) => {
+            draw_booleans(area, title, *mass_false, *mass_true)
+        }
+        Panel::Histogram2d(samples) => draw_histogram_2d(area, title, samples),
+        Panel::States(masses) => draw_states(area, title, masses),
+        Panel::Unsupported(message) => {
+            let area = area.titled(title, ("sans-serif", 20))?;
+            let (width, height) = area.dim_in_pixel();
+            let style =
+                TextStyle::from(("sans-serif", 14)).pos(Pos::new(HPos::Center, VPos::Center));
+            area.draw_text(message, &style, (width as i32 / 2, height as i32 / 2))?;
+            Ok(())
+        }
+    }
+}
+
+fn draw_histogram<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    title: &str,
+    samples: &[(f64, f64)],
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let finite = samples.iter().map(|(x, _)| *x).filter(|x| x.is_finite());
+    let (mut low, mut high) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(l, h), x| {
+        (l.min(x), h.max(x))
+    });
+    if low > high {
+        low = 0.0;
+        high = 1.0;
+    }
+    if low == high {
+        low -= 0.5;
+        high += 0.5;
+    }
+    let width = (high - low) / HISTOGRAM_BINS as f64;
+    let mut density = vec![0.0; HISTOGRAM_BINS];
+    for (x, w) in samples.iter().filter(|(x, _)| x.is_finite()) {
+        let bin = (((x - low) / width) as usize).min(HISTOGRAM_BINS - 1);
+        density[bin] += w / width;
+    }
+    let top = density.iter().copied().fold(0.0, f64::max) * 1.05;
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(title, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(35)
+        .y_label_area_size(45)
+        .build_cartesian_2d(low..high, 0.0..top.max(f64::MIN_POSITIVE))?;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("Value")
+        .y_desc("Mass")
+        .draw()?;
+    chart.draw_series(density.iter().enumerate().map(|(i, d)| {
+        let left = low + width * i as f64;
+        Rectangle::new([(left, 0.0), (left + width, *d)], BAR_COLOUR.filled())
+    }))?;
+    Ok(())
+}
+
+fn draw_booleans<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    title: &str,
+    mass_false: f64,
+    mass_true: f64,
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let mut chart = ChartBuilder::on(area)
+        .caption(title, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(35)
+        .y_label_area_size(45)
+        .build_cartesian_2d(
+            (0..1).into_segmented(),
+            0.0..mass_false.max(mass_true) * 1.05,
+        )?;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("Value")
+        .y_desc("Mass")
+        .x_label_formatter(&|x| match x {
+            SegmentValue::CenterOf(0) => "false".to_owned(),
+            SegmentValue::CenterOf(1) => "true".to_owned(),
+            _ => String::new(),
+        })
+        .draw()?;
+    chart.draw_series([(0, mass_false), (1, mass_true)].iter().map(|(x, mass)| {
+        Rectangle::new(
+            [
+                (SegmentValue::Exact(*x), 0.0),
+                (SegmentValue::Exact(x + 1), *mass),
+            ],
+            BAR_COLOUR.filled(),
+        )
+    }))?;
+    Ok(())
+}
+
+fn draw_histogram_2d<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    title: &str,
+    samples: &[(f64, f64, f64)],
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let (low, high) = HISTOGRAM_2D_RANGE;
+    let width = (high - low) / HISTOGRAM_2D_BINS as f64;
+    let mut mass = vec![vec![0.0; HISTOGRAM_2D_BINS]; HISTOGRAM_2D_BINS];
+    for (x1, x2, w) in samples {
+        if (low..high).contains(x1) && (low..high).contains(x2) {
+            mass[((x1 - low) / width) as usize][((x2 - low) / width) as usize] += w;
+        }
+    }
+    let most = mass.iter().flatten().copied().fold(0.0, f64::max);
+
+    let mut chart = ChartBuilder::on(area)
+        .caption(title, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(35)
+        .y_label_area_size(45)
+        .build_cartesian_2d(low..high, low..high)?;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("x1")
+        .y_desc("x2")
+        .draw()?;
+    chart.draw_series(mass.iter().enumerate().flat_map(|(i, column)| {
+        column.iter().enumerate().map(move |(j, m)| {
+            let (x1, x2) = (low + width * i as f64, low + width * j as f64);
+            let colour = ViridisRGB::get_color(if most > 0.0 { m / most } else { 0.0 });
+            Rectangle::new([(x1, x2), (x1 + width, x2 + width)], colour.filled())
+        })
+    }))?;
+    Ok(())
+}
+
+fn draw_states<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    title: &str,
+    masses: &[Vec<f64>],
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let states = masses.len();
+    let positions = masses.first().map_or(0, Vec::len);
+    // State 0 at the top, as `imshow` draws it.
+    let mut chart = ChartBuilder::on(area)
+        .caption(title, ("sans-serif", 20))
+        .margin(10)
+        .x_label_area_size(35)
+        .y_label_area_size(45)
+        .build_cartesian_2d(-0.5..positions as f64 - 0.5, states as f64 - 0.5..-0.5)?;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("iteration")
+        .x_label_formatter(&|x| format!("{:.0}", x))
+        .y_desc("state")
+        .y_labels(states)
+        .y_label_formatter(&|y| format!("{:.0}", y))
+        .draw()?;
+    chart.draw_series(masses.iter().enumerate().flat_map(|(state, row)| {
+        row.iter().enumerate().map(move |(position, mass)| {
+            let (x, y) = (position as f64, state as f64);
+            Rectangle::new(
+                [(x - 0.5, y - 0.5), (x + 0.5, y + 0.5)],
+                ViridisRGB::get_color(mass.clamp(0.0, 1.0)).filled(),
+            )
+        })
+    }))?;
+    Ok(())
+}
+
+fn draw_trace<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    title: &str,
+    xs: &[f64],
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let finite = xs.iter().copied().filter(|x| x.is_finite());
+    let (mut low, mut high) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(l, h), x| {
+        (l.min(x), h.max(x))
+    });
+    if low > high {
+        low = 0.0;
+        high = 1.0;
+    }
+    if low == high {
+        low -= 0.5;
+        high += 0.5;
+    }
+    let mut chart = ChartBuilder::on(area)
+        .caption(title, ("sans-serif", 16))
+        .margin(8)
+        .x_label_area_size(30)
+        .y_label_area_size(50)
+        .build_cartesian_2d(0.0..xs.len().max(1) as f64, low..high)?;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("draw")
+        .x_label_formatter(&|x| format!("{:.0}", x))
+        .draw()?;
+    chart.draw_series(LineSeries::new(
+        xs.iter().enumerate().map(|(i, x)| (i as f64, *x)),
+        &BAR_COLOUR,
+    ))?;
+    Ok(())
+}
+
+fn draw_autocorrelation<DB: DrawingBackend>(
+    area: &DrawingArea<DB, Shift>,
+    xs: &[f64],
+) -> Result<(), Box<dyn Error>>
+where
+    DB::ErrorType: 'static,
+{
+    let lags = autocorrelation(xs);
+    let mut chart = ChartBuilder::on(area)
+        .caption("autocorrelation", ("sans-serif", 16))
+        .margin(8)
+        .x_label_area_size(30)
+        .y_label_area_size(40)
+        .build_cartesian_2d(-0.5..lags.len().max(1) as f64 - 0.5, -1.0..1.0)?;
+    chart
+        .configure_mesh()
+        .disable_mesh()
+        .x_desc("lag")
+        .x_label_formatter(&|x| format!("{:.0}", x))
+        .draw()?;
+    chart.draw_series(lags.iter().enumerate().map(|(lag, r)| {
+        let x = lag as f64;
+        Rectangle::new([(x - 0.3, 0.0), (x + 0.3, *r)], BAR_COLOUR.filled())
+    }))?;
+    Ok(())
+}
+
+/// The correlation of the chain with itself at each lag up to `MAX_LAG`, or nothing if it never moves.
+fn autocorrelation(xs: &[f64]) -> Vec<f64> {
+    let n = xs.len();
+    if n < 2 {
+        return Vec::new();
+    }
+    let mean = xs.iter().sum::<f64>() / n as f64;
+    let deviations: Vec<f64> = xs.iter().map(|x| x - mean).collect();
+    let variance: f64 = deviations.iter().map(|d| d * d).sum();
+    if variance == 0.0 || !variance.is_finite() {
+        return Vec::new();
+    }
+    (0..=MAX_LAG.min(n - 1))
+        .map(|lag| {
+            deviations
+                .iter()
+                .zip(&deviations[lag..])
+                .map(|(a, b)| a * b)
+                .sum::<f64>()
+                / variance
+        })
+        .collect()
+}
@@ -0,0 +1,272 @@
+//! A progress bar and a histogram of the results so far, redrawn on the terminal while inference runs.
+
+use std::{
+    io::{IsTerminal, Write},
+    time::{Duration, Instant},
+};
+
+use crate::{inference::InferenceAlg, types::Value};
+
+const REDRAW_EVERY: Duration = Duration::from_millis(200);
+const BAR_WIDTH: usize = 30;
+const HISTOGRAM_WIDTH: usize = 40;
+const HISTOGRAM_ROWS: usize = 10;
+/// At most this many results are kept for the histogram. Beyond that, every other one is dropped and only every
+/// second result is kept from then on, and so on, so that long runs use a fixed amount of memory.
+const MAX_HISTOGRAM_VALUES: usize = 20_000;
+
+pub struct Progress {
+    total: usize,
+    done: usize,
+    started: Instant,
+    started_at: usize,
+    last_drawn: Option<Instant>,
+    lines_drawn: usize,
+    /// How many of the algorithm's kept results have been looked at.
+    seen: usize,
+    kept: usize,
+    /// The name of the number in the results that the histogram shows, e.g. `value[0]`.
+    name: Option<String>,
+    booleans: bool,
+    values: Vec<(f64, f64)>,
+    stride: usize,
+    skipped: usize,
+    /// The log of the sum of the weights, and of the sum of their squares, for the effective sample size.
+    log_weights: Option<(f64, f64)>,
+}
+
+impl Progress {
+    /// Progress for a run of `total` evaluations, of which `done` have already been made, as when resuming from a
+    /// checkpoint. Returns `None` if stderr isn't a terminal, so that redrawing doesn't fill up logs.
+    pub fn new(total: usize, done: usize) -> Option<Self> {
+        if !std::io::stderr().is_terminal() {
+            return None;
+        }
+        Some(Progress {
+            total,
+            done,
+            started: Instant::now(),
+            started_at: done,
+            last_drawn: None,
+            lines_drawn: 0,
+            seen: 0,
+            kept: 0,
+            name: None,
+            booleans: false,
+            values: Vec::new(),
+            stride: 1,
+            skipped: 0,
+            log_weights: None,
+        })
+    }
+
+    /// Called after each evaluation, before any kept results are taken from the algorithm.
+    pub fn update<T: InferenceAlg>(&mut self, alg: &T) {
+        self.done += 1;
+        let (kept, log_weights) = alg.kept();
+        for (i, val) in kept.iter().enumerate().skip(self.seen) {
+            self.add(val, log_weights.map(|w| w[i]));
+        }
+        self.seen = kept.len();
+
+        let due = self.last_drawn.is_none_or(|t| t.elapsed() >= REDRAW_EVERY);
+        if due || self.done == self.total {
+            self.draw(alg.acceptance_rate());
+        }
+    }
+
+    /// Called after the algorithm's kept results have been taken, as they are when streaming.
+    pub fn taken(&mut self) {
+        self.seen = 0;
+    }
+
+    fn add(&mut self, val: &Value, log_weight: Option<f64>) {
+        self.kept += 1;
+        if let Some(w) = log_weight {
+            let (sum, sum_squares) = self
+                .log_weights
+                .unwrap_or((f64::NEG_INFINITY, f64::NEG_INFINITY));
+            self.log_weights = Some((log_add_exp(sum, w), log_add_exp(sum_squares, 2.0 * w)));
+        }
+
+        let (name, x, boolean) = match first_number(val, "value".to_owned()) {
+            Some(number) => number,
+            None => return,
+        };
+        if self.name.is_none() {
+            self.name = Some(name);
+            self.booleans = boolean;
+        }
+        self.skipped += 1;
+        if self.skipped < self.stride {
+            return;
+        }
+        self.skipped = 0;
+        self.values.push((x, log_weight.unwrap_or(0.0)));
+        if self.values.len() >= MAX_HISTOGRAM_VALUES {
+            let mut i = 0;
+            self.values.retain(|_| {
+                i += 1;
+                i % 2 == 0
+            });
+            self.stride *= 2;
+        }
+    }
+
+    fn draw(&mut self, acceptance_rate: Option<f64>) {
+        let mut lines = vec![self.status_line(acceptance_rate)];
+        lines.extend(self.histogram());
+
+        let mut stderr = std::io::stderr().lock();
+        // Moves back up over the last drawing, clearing each line before writing over it.
+        if self.lines_drawn > 0 {
+            let _ = write!(stderr, "\x1b[{}A", self.lines_drawn);
+        }
+        for line in lines.iter() {
+            let _ = writeln!(stderr, "\x1b[2K{}", line);
+        }
+        for _ in lines.len()..self.lines_drawn {
+            let _ = writeln!(stderr, "\x1b[2K");
+        }
+        let _ = stderr.flush();
+        self.lines_drawn = self.lines_drawn.max(lines.len());
+        self.last_drawn = Some(Instant::now());
+    }
+
+    fn status_line(&self, acceptance_rate: Option<f64>) -> String {
+        let fraction = if self.total == 0 {
+            1.0
+        } else {
+            self.done as f64 / self.total as f64
+        };
+        let filled = (fraction * BAR_WIDTH as f64) as usize;
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let rate = (self.done - self.started_at) as f64 / elapsed.max(1e-9);
+
+        let mut line = format!(
+            "[{}{}] {}/{} {:>3.0}%  {:.0} samples/s",
+            "█".repeat(filled),
+            " ".repeat(BAR_WIDTH - filled),
+            self.done,
+            self.total,
+            fraction * 100.0,
+            rate
+        );
+        if self.done < self.total && rate > 0.0 {
+            line.push_str(&format!(
+                "  {:.0}s left",
+                (self.total - self.done) as f64 / rate
+            ));
+        }
+        if let Some(rate) = acceptance_rate {
+            line.push_str(&format!("  acceptance {:.3}", rate));
+        }
+        if let Some((sum, sum_squares)) = self.log_weights {
+            let ess = (2.0 * sum - sum_squares).exp();
+            line.push_str(&format!(
+                "  ESS {:.1}",
+                if ess.is_nan() { 0.0 } else { ess }
+            ));
+        }
+        line
+    }
+
+    /// A row for each bin of the results so far, with bars as long as the bin's share of the mass.
+    fn histogram(&self) -> Vec<String> {
+        let name = match &self.name {
+            Some(name) => name,
+            None => return Vec::new(),
+        };
+        let max_log_weight = self
+            .values
+            .iter()
+            .map(|(_, w)| *w)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let finite = || {
+            self.values
+                .iter()
+                .filter(|(x, w)| x.is_finite() && *w > f64::NEG_INFINITY)
+        };
+        let (low, high) = finite().fold((f64::INFINITY, f64::NEG_INFINITY), |(l, h), (x, _)| {
+            (l.min(*x), h.max(*x))
+        });
+        if low > high {
+            return Vec::new();
+        }
+
+        // Booleans and small ranges of integers get a row for each value.
+        let integers =
+            finite().all(|(x, _)| x.fract() == 0.0) && high - low < HISTOGRAM_ROWS as f64;
+        let (rows, width) = if self.booleans || integers {
+            ((high - low) as usize + 1, 1.0)
+        } else {
+            (HISTOGRAM_ROWS, (high - low) / HISTOGRAM_ROWS as f64)
+        };
+        let mut masses = vec![0.0; rows];
+        for (x, w) in finite() {
+            let bin = if width > 0.0 {
+                ((x - low) / width) as usize
+            } else {
+                0
+            };
+            masses[bin.min(rows - 1)] += (w - max_log_weight).exp();
+        }
+        let total: f64 = masses.iter().sum();
+        let most = masses.iter().copied().fold(0.0, f64::max);
+
+        let labels: Vec<String> = (0..rows)
+            .map(|i| {
+                let x = low + width * i as f64;
+                if self.booleans {
+                    (x == 1.0).to_string()
+                } else if integers {
+                    format!("{}", x)
+                } else {
+                    format!("{:.3}", x)
+                }
+            })
+            .collect();
+        let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+
+        let mut lines = vec![format!("{} ({} kept)", name, self.kept)];
+        for (label, mass) in labels.iter().zip(masses) {
+            lines.push(format!(
+                "{:>width$} │{} {:.3}",
+                label,
+                bar(mass / most * HISTOGRAM_WIDTH as f64),
+                mass / total,
+                width = label_width
+            ));
+        }
+        lines
+    }
+}
+
+/// A bar `length` characters long, to the nearest eighth of a character.
+fn bar(length: f64) -> String {
+    const EIGHTHS: [&str; 8] = ["", "▏", "▎", "▍", "▌", "▋", "▊", "▉"];
+    let eighths = (length * 8.0).round() as usize;
+    format!("{}{}", "█".repeat(eighths / 8), EIGHTHS[eighths % 8])
+}
+
+fn log_add_exp(a: f64, b: f64) -> f64 {
+    let max = a.max(b);
+    if max == f64::NEG_INFINITY {
+        return max;
+    }
+    max + ((a - max).exp() + (b - max).exp()).ln()
+}
+
+/// The first number in a result, named as in the CSV format, and whether it's a boolean.
+fn first_number(val: &Value, name: String) -> Option<(String, f64, bool)> {
+    match val {
+        Value::Float(x) => Some((name, *x, false)),
+        Value::Integer(x) => Some((name, *x as f64, false)),
+        Value::Boolean(x) => Some((name, if *x { 1.0 } else { 0.0 }, true)),
+        Value::Vector(xs) => first_number(xs.first()?, format!("{}[0]", name)),
+        Value::Matrix(m) if m.n_rows() > 0 && m.n_cols() > 0 => {
+            Some((format!("{}[0][0]", name), m.get(0, 0), false))
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,49 @@
+//! The random number generator behind every random choice, so that a run can be seeded, and a checkpointed chain
+//! can carry on with exactly the random numbers it would have drawn.
+
+use std::{cell::RefCell, rc::Rc};
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+thread_local! {
+    static RNG: Rc<RefCell<ChaCha8Rng>> = Rc::new(RefCell::new(ChaCha8Rng::from_entropy()));
+}
+
+/// A handle to the generator, used like `rand::thread_rng()`.
+#[derive(Clone)]
+pub struct SharedRng(Rc<RefCell<ChaCha8Rng>>);
+
+pub fn rng() -> SharedRng {
+    RNG.with(|rng| SharedRng(rng.clone()))
+}
+
+pub fn seed(seed: u64) {
+    set_state(ChaCha8Rng::seed_from_u64(seed));
+}
+
+pub fn state() -> ChaCha8Rng {
+    RNG.with(|rng| rng.borrow().clone())
+}
+
+pub fn set_state(state: ChaCha8Rng) {
+    RNG.with(|rng| *rng.borrow_mut() = state);
+}
+
+impl RngCore for SharedRng {
+    fn next_u32(&mut self) -> u32 {
+        self.0.borrow_mut().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.borrow_mut().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.borrow_mut().fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.0.borrow_mut().try_fill_bytes(dest)
+    }
+}
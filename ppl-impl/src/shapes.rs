@@ -0,0 +1,262 @@
+use std::{convert::TryFrom, fmt};
+
+use crate::matrix::{broadcast_dim, mat_add_compatible};
+
+/// The statically known dimensions of a vector value.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shape {
+    /// A vector of `n` elements, which are not known to be vectors themselves.
+    Vector(usize),
+    /// A rectangular vector-of-vectors, as used by the `mat-*` built-ins. Rows by columns.
+    Matrix(usize, usize),
+}
+
+impl fmt::Display for Shape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Shape::Vector(n) => write!(f, "[{}]", n),
+            Shape::Matrix(rows, cols) => write!(f, "[{} x {}]", rows, cols),
+        }
+    }
+}
+
+impl Shape {
+    pub fn length(&self) -> usize {
+        match *self {
+            Shape::Vector(n) => n,
+            Shape::Matrix(rows, _) => rows,
+        }
+    }
+
+    /// The shape of a vector made of elements with the given shapes. Only a vector whose elements all have the
+    /// same vector shape is a matrix.
+    pub fn of_elements(elements: &[Option<Shape>]) -> Shape {
+        match elements.first() {
+            Some(Some(Shape::Vector(cols)))
+                if elements.iter().all(|e| *e == Some(Shape::Vector(*cols))) =>
+            {
+                Shape::Matrix(elements.len(), *cols)
+            }
+            _ => Shape::Vector(elements.len()),
+        }
+    }
+
+    /// The shape of a vector of `n` elements which all have the shape `element`, worked out without making the
+    /// elements, since `n` can be as large as any literal in the program.
+    pub fn repeated(n: usize, element: Option<Shape>) -> Shape {
+        match element {
+            Some(Shape::Vector(cols)) if n > 0 => Shape::Matrix(n, cols),
+            _ => Shape::Vector(n),
+        }
+    }
+
+    /// The shape of a single element of a vector with this shape, if it is known.
+    pub fn element(&self) -> Option<Shape> {
+        match *self {
+            Shape::Vector(_) => None,
+            Shape::Matrix(_, cols) => Some(Shape::Vector(cols)),
+        }
+    }
+
+    fn with_len(&self, len: usize) -> Shape {
+        match *self {
+            Shape::Vector(_) => Shape::Vector(len),
+            Shape::Matrix(_, cols) => Shape::Matrix(len, cols),
+        }
+    }
+}
+
+fn matrix(name: &str, i: usize, shape: Option<Shape>) -> Result<Option<(usize, usize)>, String> {
+    match shape {
+        Some(Shape::Matrix(rows, cols)) => Ok(Some((rows, cols))),
+        Some(Shape::Vector(0)) => Err(format!(
+            "Argument {} to `{}` must be a matrix, but it is an empty vector.",
+            i + 1,
+            name
+        )),
+        // Either unknown, or a vector whose elements might turn out to be vectors at runtime.
+        _ => Ok(None),
+    }
+}
+
+/// The sum of the shapes' lengths, or `None` if it overflows.
+fn total_length(shapes: &[Shape]) -> Option<usize> {
+    shapes
+        .iter()
+        .try_fold(0usize, |total, shape| total.checked_add(shape.length()))
+}
+
+/// Works out the shape of the result of a built-in which operates on vectors, given the shapes of its arguments.
+/// `int_literals` holds the value of each argument which was written as an integer literal.
+/// Returns an error if the shapes are certain to be incompatible at runtime.
+pub fn builtin_result_shape(
+    name: &str,
+    shapes: &[Option<Shape>],
+    int_literals: &[Option<i64>],
+) -> Result<Option<Shape>, String> {
+    let arg = |i: usize| shapes.get(i).copied().flatten();
+    let literal = |i: usize| int_literals.get(i).copied().flatten();
+
+    Ok(match name {
+        "vector" => Some(Shape::of_elements(shapes)),
+        "get" | "first" | "second" | "last" => {
+            let index = match name {
+                "get" => literal(1),
+                "first" => Some(0),
+                "second" => Some(1),
+                _ => None,
+            };
+            match (arg(0), index) {
+                (Some(shape), Some(index)) if index < 0 || index as usize >= shape.length() => {
+                    return Err(format!(
+                        "`{}` index {} is out of bounds for a vector of shape {}.",
+                        name, index, shape
+                    ))
+                }
+                (Some(Shape::Vector(0)), None) | (Some(Shape::Matrix(0, _)), None) => {
+                    return Err(format!("`{}` was given an empty vector.", name))
+                }
+                (Some(shape), _) => shape.element(),
+                (None, _) => None,
+            }
+        }
+        "rest" => match arg(0) {
+            Some(shape) if shape.length() == 0 => {
+                return Err("`rest` was given an empty vector.".to_owned())
+            }
+            Some(shape) => Some(shape.with_len(shape.length() - 1)),
+            None => None,
+        },
+        "append" => match (arg(0), arg(1)) {
+            (Some(Shape::Matrix(rows, cols)), Some(Shape::Vector(n))) if n == cols => {
+                rows.checked_add(1).map(|rows| Shape::Matrix(rows, cols))
+            }
+            (Some(shape), _) => shape.length().checked_add(1).map(Shape::Vector),
+            (None, _) => None,
+        },
+        "mat-transpose" => matrix(name, 0, arg(0))?.map(|(rows, cols)| Shape::Matrix(cols, rows)),
+        "mat-tanh" | "mat-exp" | "mat-log" | "mat-sqrt" | "mat-abs" | "mat-sin" | "mat-cos"
+        | "mat-sigmoid" | "mat-relu" => {
+            matrix(name, 0, arg(0))?.map(|(rows, cols)| Shape::Matrix(rows, cols))
+        }
+        "cholesky" | "mat-inverse" | "mat-det" => match matrix(name, 0, arg(0))? {
+            Some((rows, cols)) if rows != cols => {
+                return Err(format!(
+                    "`{}` needs a square matrix, but was given {}.",
+                    name,
+                    Shape::Matrix(rows, cols)
+                ))
+            }
+            Some((rows, cols)) if name != "mat-det" => Some(Shape::Matrix(rows, cols)),
+            _ => None,
+        },
+        "mat-repmat" => match (matrix(name, 0, arg(0))?, literal(1), literal(2)) {
+            (Some((rows, cols)), Some(row_mul), Some(col_mul)) => {
+                if row_mul <= 0 || col_mul <= 0 {
+                    return Err("`mat-repmat` must be given positive repetition counts.".to_owned());
+                }
+                // Gives up on sizes too large to count.
+                let rows = usize::try_from(row_mul).ok().and_then(|m| rows.checked_mul(m));
+                let cols = usize::try_from(col_mul).ok().and_then(|m| cols.checked_mul(m));
+                rows.zip(cols).map(|(rows, cols)| Shape::Matrix(rows, cols))
+            }
+            _ => None,
+        },
+        "mat-mul" => match (matrix(name, 0, arg(0))?, matrix(name, 1, arg(1))?) {
+            (Some((rows1, cols1)), Some((rows2, cols2))) => {
+                if cols1 != rows2 {
+                    return Err(format!(
+                        "`mat-mul` needs matching inner dimensions, but was given {} and {}.",
+                        Shape::Matrix(rows1, cols1),
+                        Shape::Matrix(rows2, cols2)
+                    ));
+                }
+                Some(Shape::Matrix(rows1, cols2))
+            }
+            _ => None,
+        },
+        "+" | "-" | "*" => {
+            // Only matrix shapes are compared, because a vector with unknown elements might be a matrix at runtime.
+            let mut result = None;
+            for shape in shapes {
+                let (rows2, cols2) = match shape {
+                    Some(Shape::Matrix(rows, cols)) => (*rows, *cols),
+                    _ => continue,
+                };
+                result = match result {
+                    None => Some((rows2, cols2)),
+                    Some((rows1, cols1)) => {
+                        match (broadcast_dim(rows1, rows2), broadcast_dim(cols1, cols2)) {
+                            (Some(rows), Some(cols)) => Some((rows, cols)),
+                            _ => {
+                                return Err(format!(
+                                    "`{}` can't broadcast {} and {} together.",
+                                    name,
+                                    Shape::Matrix(rows1, cols1),
+                                    Shape::Matrix(rows2, cols2)
+                                ))
+                            }
+                        }
+                    }
+                };
+            }
+            let all_matrices = shapes
+                .iter()
+                .all(|s| matches!(s, Some(Shape::Matrix(_, _))));
+            match result {
+                Some((rows, cols)) if all_matrices => Some(Shape::Matrix(rows, cols)),
+                _ => None,
+            }
+        }
+        "mat-add" | "mat-sub" | "mat-emul" | "mat-div" => {
+            match (matrix(name, 0, arg(0))?, matrix(name, 1, arg(1))?) {
+                (Some((rows1, cols1)), Some((rows2, cols2))) => {
+                    if !mat_add_compatible((rows1, cols1), (rows2, cols2)) {
+                        return Err(format!(
+                            "`{}` can't broadcast {} and {} together.",
+                            name,
+                            Shape::Matrix(rows1, cols1),
+                            Shape::Matrix(rows2, cols2)
+                        ));
+                    }
+                    Some(Shape::Matrix(rows1.max(rows2), cols1.max(cols2)))
+                }
+                _ => None,
+            }
+        }
+        "softmax" => arg(0),
+        "range" => {
+            let len = match (literal(0), literal(1)) {
+                (Some(end), None) if shapes.len() == 1 => Some(end),
+                (Some(start), Some(end)) => end.checked_sub(start),
+                _ => None,
+            };
+            len.and_then(|len| usize::try_from(len.max(0)).ok())
+                .map(Shape::Vector)
+        }
+        "repeat" => match literal(1) {
+            Some(n) if n < 0 => {
+                return Err("`repeat` must be given a non-negative count.".to_owned())
+            }
+            Some(n) => usize::try_from(n).ok().map(|n| Shape::repeated(n, arg(0))),
+            None => None,
+        },
+        "concat" => {
+            let known: Option<Vec<Shape>> = shapes.iter().copied().collect();
+            match known {
+                Some(known) if !known.is_empty() => match known[0] {
+                    Shape::Matrix(_, cols)
+                        if known
+                            .iter()
+                            .all(|s| matches!(s, Shape::Matrix(_, c) if *c == cols)) =>
+                    {
+                        total_length(&known).map(|rows| Shape::Matrix(rows, cols))
+                    }
+                    _ => total_length(&known).map(Shape::Vector),
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    })
+}
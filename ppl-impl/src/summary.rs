@@ -0,0 +1,250 @@
+//! Summary statistics of the results of inference, for each number in the results.
+
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::{
+    output::{Columns, Naming},
+    types::RuntimeError,
+    DataFile, ResultValue,
+};
+
+/// How much of the posterior mass the HPD interval holds.
+const HPD_MASS: f64 = 0.95;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Continuous,
+    Integer,
+    Boolean,
+}
+
+/// The statistics of one column of the flattened results, e.g. `value[1]`, with every statistic weighted by the
+/// results' weights if they have them. Booleans are counted as 0 and 1.
+#[derive(Serialize, Debug)]
+pub struct Summary {
+    pub name: String,
+    pub kind: Kind,
+    /// How many results have this column, whatever their weight.
+    pub n: usize,
+    pub mean: f64,
+    pub sd: f64,
+    pub quantiles: Quantiles,
+    /// The narrowest interval holding 95% of the mass.
+    pub hpd_95: (f64, f64),
+    /// The most likely value, for integers and booleans only.
+    pub mode: Option<ResultValue>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Quantiles {
+    #[serde(rename = "2.5%")]
+    pub q2_5: f64,
+    #[serde(rename = "25%")]
+    pub q25: f64,
+    #[serde(rename = "50%")]
+    pub q50: f64,
+    #[serde(rename = "75%")]
+    pub q75: f64,
+    #[serde(rename = "97.5%")]
+    pub q97_5: f64,
+}
+
+pub fn summarize(data: &DataFile) -> Result<Vec<Summary>, RuntimeError> {
+    let columns = Columns::new(data, Naming::Indexed);
+    // Relative to the largest, so that very negative log weights don't all become 0.
+    let weights = match &columns.log_weights {
+        Some(log_weights) => {
+            let max = log_weights
+                .iter()
+                .copied()
+                .fold(f64::NEG_INFINITY, f64::max);
+            log_weights.iter().map(|w| (w - max).exp()).collect()
+        }
+        None => vec![1.0; columns.rows.len()],
+    };
+
+    columns
+        .names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| summarize_column(name, columns.column(i), &weights))
+        .collect()
+}
+
+fn summarize_column<'d>(
+    name: &str,
+    column: impl Iterator<Item = Option<&'d ResultValue>>,
+    weights: &[f64],
+) -> Result<Summary, RuntimeError> {
+    let mut kind = None;
+    let mut n = 0;
+    let mut samples: Vec<(f64, f64)> = Vec::new();
+    for (cell, &weight) in column.zip(weights) {
+        let cell = match cell {
+            Some(cell) => cell,
+            None => continue,
+        };
+        let (x, cell_kind) = match cell {
+            ResultValue::Float(x) => (*x, Kind::Continuous),
+            ResultValue::Int(x) => (*x as f64, Kind::Integer),
+            ResultValue::Boolean(x) => (if *x { 1.0 } else { 0.0 }, Kind::Boolean),
+        };
+        // A column of integers and floats is summarised as floats.
+        kind = match (kind, cell_kind) {
+            (None, k) => Some(k),
+            (Some(k), l) if k == l => Some(k),
+            _ => Some(Kind::Continuous),
+        };
+        n += 1;
+        if weight > 0.0 {
+            samples.push((x, weight));
+        }
+    }
+
+    let total: f64 = samples.iter().map(|(_, w)| w).sum();
+    if samples.is_empty() || !total.is_finite() {
+        return err!(
+            "Every result with `{}` has a weight of zero, so it can't be summarised.",
+            name
+        );
+    }
+    samples.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+    let kind = kind.unwrap();
+
+    let mean = samples.iter().map(|(x, w)| x * w).sum::<f64>() / total;
+    let variance = samples
+        .iter()
+        .map(|(x, w)| w * (x - mean).powi(2))
+        .sum::<f64>()
+        / total;
+
+    Ok(Summary {
+        name: name.to_owned(),
+        kind,
+        n,
+        mean,
+        sd: variance.sqrt(),
+        quantiles: Quantiles {
+            q2_5: quantile(&samples, total, 0.025),
+            q25: quantile(&samples, total, 0.25),
+            q50: quantile(&samples, total, 0.5),
+            q75: quantile(&samples, total, 0.75),
+            q97_5: quantile(&samples, total, 0.975),
+        },
+        hpd_95: hpd(&samples, total, HPD_MASS),
+        mode: mode(&samples, kind),
+    })
+}
+
+/// The smallest value with at least `q` of the mass at or below it. `samples` must be sorted.
+fn quantile(samples: &[(f64, f64)], total: f64, q: f64) -> f64 {
+    let target = q * total;
+    let mut mass = 0.0;
+    for (x, w) in samples {
+        mass += w;
+        // Allows for rounding in the sum, so that the median of [0, 1] is 0.
+        if mass >= target * (1.0 - 1e-12) {
+            return *x;
+        }
+    }
+    samples.last().unwrap().0
+}
+
+/// The narrowest interval between two samples holding at least `mass` of the total. `samples` must be sorted.
+fn hpd(samples: &[(f64, f64)], total: f64, mass: f64) -> (f64, f64) {
+    let target = mass * total * (1.0 - 1e-12);
+    let mut best = (samples[0].0, samples[samples.len() - 1].0);
+    let mut end = 0;
+    let mut inside = 0.0;
+    for start in 0..samples.len() {
+        while inside < target && end < samples.len() {
+            inside += samples[end].1;
+            end += 1;
+        }
+        if inside < target {
+            break;
+        }
+        let interval = (samples[start].0, samples[end - 1].0);
+        if interval.1 - interval.0 < best.1 - best.0 {
+            best = interval;
+        }
+        inside -= samples[start].1;
+    }
+    best
+}
+
+fn mode(samples: &[(f64, f64)], kind: Kind) -> Option<ResultValue> {
+    if kind == Kind::Continuous {
+        return None;
+    }
+    let mut masses: BTreeMap<i64, f64> = BTreeMap::new();
+    for (x, w) in samples {
+        *masses.entry(*x as i64).or_insert(0.0) += w;
+    }
+    // The smallest of equally likely values, since the map is in order and `max_by` keeps the last maximum.
+    let (x, _) = masses
+        .into_iter()
+        .rev()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))?;
+    Some(match kind {
+        Kind::Boolean => ResultValue::Boolean(x == 1),
+        _ => ResultValue::Int(x),
+    })
+}
+
+/// Prints the summaries as a table, with one row per column of the results.
+pub fn print_table(summaries: &[Summary]) {
+    let header = [
+        "", "mean", "sd", "2.5%", "25%", "50%", "75%", "97.5%", "hpd low", "hpd high", "mode",
+    ];
+    let mut rows: Vec<Vec<String>> = vec![header.iter().map(|s| s.to_string()).collect()];
+    for s in summaries {
+        let discrete = s.kind != Kind::Continuous;
+        let q = &s.quantiles;
+        let mut row = vec![s.name.clone(), number(s.mean, false), number(s.sd, false)];
+        for x in [q.q2_5, q.q25, q.q50, q.q75, q.q97_5, s.hpd_95.0, s.hpd_95.1].iter() {
+            row.push(number(*x, discrete));
+        }
+        row.push(match &s.mode {
+            Some(ResultValue::Boolean(x)) => x.to_string(),
+            Some(ResultValue::Int(x)) => x.to_string(),
+            Some(ResultValue::Float(x)) => number(*x, false),
+            None => "-".to_owned(),
+        });
+        rows.push(row);
+    }
+
+    let mut widths = vec![0; header.len()];
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    for row in rows {
+        let cells: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .enumerate()
+            .map(|(i, (cell, width))| match i {
+                0 => format!("{:<width$}", cell, width = width),
+                _ => format!("{:>width$}", cell, width = width),
+            })
+            .collect();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+/// At most four significant figures, and scientific notation for very large or small numbers.
+fn number(x: f64, integer: bool) -> String {
+    if integer && x.is_finite() {
+        format!("{}", x as i64)
+    } else if x == 0.0 || (1e-3..1e5).contains(&x.abs()) {
+        let decimals = 3 - (x.abs().log10().floor() as i32).clamp(0, 3);
+        format!("{:.*}", decimals as usize, x)
+    } else {
+        format!("{:.3e}", x)
+    }
+}
@@ -0,0 +1,64 @@
+//! Traces of single runs of a program, written by `eval-once --trace` and read by `replay`.
+
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    checkpoint::{SavedAddress, SavedFloat, SavedValue},
+    types::RuntimeError,
+};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    Sample,
+    Observe,
+}
+
+impl EventKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            EventKind::Sample => "sample",
+            EventKind::Observe => "observe",
+        }
+    }
+}
+
+/// One `sample` or `observe` made by the run, in the order they were made.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct TraceEvent {
+    pub kind: EventKind,
+    pub address: SavedAddress,
+    pub distribution: String,
+    pub params: Vec<(String, SavedValue)>,
+    /// The value the `sample` or `observe` gave the program.
+    pub value: SavedValue,
+    /// The value written in the program, for an `observe`. Single runs sample every `observe` from the prior like
+    /// `prior-only`, so this isn't the same as `value`.
+    pub observed: Option<SavedValue>,
+    /// The log density of `value`.
+    pub log_prob: SavedFloat,
+}
+
+/// Values are saved exactly, as in checkpoints, so that replaying a trace gives exactly the same run.
+#[derive(Serialize, Deserialize)]
+pub struct Trace {
+    pub events: Vec<TraceEvent>,
+    pub result: SavedValue,
+}
+
+impl Trace {
+    pub fn save(&self, path: &Path) -> Result<(), RuntimeError> {
+        let json = serde_json::to_string_pretty(self)
+            .or_else(|e| err!("Couldn't save the trace: {}", e))?;
+        std::fs::write(path, json)
+            .or_else(|e| err!("Couldn't write the trace `{}`: {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, RuntimeError> {
+        let json = std::fs::read_to_string(path)
+            .or_else(|e| err!("Couldn't read the trace `{}`: {}", path.display(), e))?;
+        serde_json::from_str(&json).or_else(|e| err!("`{}` isn't a trace: {}", path.display(), e))
+    }
+}
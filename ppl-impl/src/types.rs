@@ -4,15 +4,16 @@ use std::{
     rc::Rc,
 };
 
-use crate::distributions::Distribution;
+use crate::{distributions::Distribution, matrix::Matrix};
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum ValueType {
     Float,
     Integer,
     Boolean,
     Distribution,
     Vector,
+    Matrix,
     Null,
 }
 
@@ -29,9 +30,42 @@ pub enum Value {
     Boolean(bool),
     Distribution(Rc<dyn Distribution>),
     Vector(Vec<Value>),
+    Matrix(Rc<Matrix>),
     Null,
 }
 
+/// Writes values the way a program would read them, e.g. `[1, 2.5]` or `normal(mu=0.0, sigma=1.0)`.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T, F>(f: &mut fmt::Formatter<'_>, items: &[T], mut item: F) -> fmt::Result
+        where
+            F: FnMut(&mut fmt::Formatter<'_>, &T) -> fmt::Result,
+        {
+            write!(f, "[")?;
+            for (i, x) in items.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                item(f, x)?;
+            }
+            write!(f, "]")
+        }
+
+        match self {
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Integer(x) => write!(f, "{}", x),
+            Value::Boolean(x) => write!(f, "{}", x),
+            Value::Distribution(d) => write!(f, "{:?}", d),
+            Value::Vector(xs) => list(f, xs, |f, x| write!(f, "{}", x)),
+            Value::Matrix(m) => {
+                let rows: Vec<&[f64]> = (0..m.n_rows()).map(|i| m.row(i)).collect();
+                list(f, &rows, |f, row| list(f, row, |f, x| write!(f, "{:?}", x)))
+            }
+            Value::Null => write!(f, "null"),
+        }
+    }
+}
+
 #[allow(dead_code)]
 impl Value {
     pub fn get_type(&self) -> ValueType {
@@ -41,6 +75,7 @@ impl Value {
             Self::Boolean(_) => ValueType::Boolean,
             Self::Distribution(_) => ValueType::Distribution,
             Self::Vector(_) => ValueType::Vector,
+            Self::Matrix(_) => ValueType::Matrix,
             Self::Null => ValueType::Null,
         }
     }
@@ -106,6 +141,7 @@ impl Value {
     pub fn try_into_vector(self, message: &str) -> Result<Vec<Value>, RuntimeError> {
         match self {
             Value::Vector(x) => Ok(x),
+            Value::Matrix(m) => Ok(m.rows_as_values()),
             _ => err!("{}", message.to_owned()),
         }
     }
@@ -32,7 +32,8 @@ def render_charts():
             has_weights = data["has_weights"]
             data = data["data"]
             if has_weights:
-                weights = np.exp([d[1] for d in data])
+                # JSON has no infinities, so the weight of a run ruled out by `condition` is written as null.
+                weights = np.exp([-np.inf if d[1] is None else d[1] for d in data])
                 data = [d[0] for d in data]
             else:
                 weights = None